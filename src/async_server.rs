@@ -0,0 +1,519 @@
+use std::{
+    future::Future,
+    io::{self, BufReader, Cursor, Read, Write},
+    mem, panic,
+    sync::{Arc, OnceLock},
+    thread,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+    runtime::{Handle, Runtime, RuntimeFlavor},
+    sync::oneshot,
+    task::{self, JoinSet},
+};
+
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+use crate::{
+    connection::{self, ConnectionHandler, HeadCheck, Sending},
+    http2::{self, Start},
+    listener::{BufferedStream, ListenerKind, Stream},
+    package::Package,
+    request::{RequestHead, RequestParser},
+    response::{BodyStream, Chunk, Response, Status},
+    BinaryRepresentation, Error, HttpServer, ServerError,
+};
+
+#[cfg(feature = "tls")]
+use crate::tls;
+
+impl HttpServer {
+    /// Listens for incoming connections and handles them using the attached router, as [HttpServer::listen] does, but handling the HTTP/1.x connections as tasks of the tokio runtime it's awaited in instead of a thread per connection. Requires the `async` feature.
+    ///
+    /// Idle keep-alive connections and streamed bodies (e.g. Server-Sent Events) of HTTP/1.x connections don't hold a thread while they wait. Handlers run in the blocking thread pool of the runtime, so sync handlers can keep blocking, and async handlers (check [Router::handle_async_route]) are awaited there, holding a thread of the pool until they finish.
+    ///
+    /// Connections taken over by another protocol (HTTP/2 or the upgrades of the handlers, like WebSockets) and HTTPS connections are served by the same blocking code as [HttpServer::listen], so each of them holds a thread of its own for as long as it's open.
+    ///
+    /// **This method will not return until a listener fails or the server is shut down, and must be awaited inside a tokio runtime**
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use servidor_http::{HttpServer, request::Method, router::{Route, Router}};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut router = Router::new(String::from("/"));
+    ///     router.handle_async_route(Route::new(Method::GET, "/"), |_, _| async {
+    ///         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    ///         "Hello world!"
+    ///     });
+    ///
+    ///     let mut server = HttpServer::new(8080).unwrap();
+    ///     server.attach_router(router);
+    ///     server.listen_async().await.unwrap();
+    /// }
+    /// ```
+    ///
+    /// [Router::handle_async_route]: crate::router::Router::handle_async_route
+    pub async fn listen_async(&self) -> Result<(), Error> {
+        if self.router.is_none() && self.host_routers.is_empty() {
+            return Err(Error::ServerError(ServerError::NoRouterAttached));
+        }
+
+        let (handler, listener_handlers) = self.connection_handlers();
+
+        let mut accept_tasks = JoinSet::new();
+
+        for (listener, handler) in self.listeners.iter().zip(listener_handlers) {
+            listener.listener.set_nonblocking(true)?;
+
+            let async_listener = AsyncListener::Tcp(
+                TcpListener::from_std(listener.listener.try_clone()?)?,
+                listener.kind.clone(),
+            );
+
+            accept_tasks.spawn(accept_connections(async_listener, handler));
+        }
+
+        #[cfg(unix)]
+        for unix_listener in &self.unix_listeners {
+            unix_listener.listener.set_nonblocking(true)?;
+
+            let async_listener =
+                AsyncListener::Unix(UnixListener::from_std(unix_listener.listener.try_clone()?)?);
+
+            accept_tasks.spawn(accept_connections(async_listener, handler.clone()));
+        }
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown.shutting_down() => break,
+                Some(result) = accept_tasks.join_next() => match result {
+                    Ok(Ok(())) => (),
+                    Ok(Err(err)) => return Err(Error::Io(err)),
+                    Err(err) => return Err(Error::Io(io::Error::other(err))),
+                },
+            }
+        }
+
+        accept_tasks.abort_all();
+
+        let shutdown = self.shutdown.clone();
+        let shutdown_timeout = self.shutdown_timeout;
+
+        task::spawn_blocking(move || shutdown.wait_for_connections(shutdown_timeout))
+            .await
+            .map_err(io::Error::other)?;
+
+        Ok(())
+    }
+}
+
+/// Listener of the async server.
+enum AsyncListener {
+    Tcp(TcpListener, ListenerKind),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+/// Connection of the async server, turned into a blocking [Stream] when it's handed over to a thread.
+trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+    fn into_blocking(self) -> io::Result<Box<dyn Stream>>;
+}
+
+impl AsyncStream for tokio::net::TcpStream {
+    fn into_blocking(self) -> io::Result<Box<dyn Stream>> {
+        let stream = self.into_std()?;
+        stream.set_nonblocking(false)?;
+
+        Ok(Box::new(stream))
+    }
+}
+
+#[cfg(unix)]
+impl AsyncStream for tokio::net::UnixStream {
+    fn into_blocking(self) -> io::Result<Box<dyn Stream>> {
+        let stream = self.into_std()?;
+        stream.set_nonblocking(false)?;
+
+        Ok(Box::new(stream))
+    }
+}
+
+/// Accepts the connections of the listener until the task is aborted.
+async fn accept_connections(
+    listener: AsyncListener,
+    handler: Arc<ConnectionHandler>,
+) -> io::Result<()> {
+    loop {
+        match &listener {
+            AsyncListener::Tcp(listener, kind) => {
+                let (stream, _) = listener.accept().await?;
+
+                match kind {
+                    // The TLS handshake and the connection are handled in their own thread, as the TLS streams are blocking
+                    #[cfg(feature = "tls")]
+                    ListenerKind::Https(config) => {
+                        let config = config.clone();
+                        let stream = stream.into_std()?;
+                        stream.set_nonblocking(false)?;
+
+                        spawn_thread(&handler, move |handler| {
+                            handler.handle_connection(tls::accept(&config, stream)?)
+                        });
+                    }
+                    _ => spawn_connection(&handler, stream),
+                }
+            }
+            #[cfg(unix)]
+            AsyncListener::Unix(listener) => {
+                let (stream, _) = listener.accept().await?;
+                spawn_connection(&handler, stream);
+            }
+        }
+    }
+}
+
+/// Handles the connection in its own task, giving the errors that aren't handled by the router to the error hook.
+fn spawn_connection<S>(handler: &Arc<ConnectionHandler>, stream: S)
+where
+    S: AsyncStream,
+{
+    let handler = handler.clone();
+    let connection_guard = handler.shutdown.connection_guard();
+
+    tokio::spawn(async move {
+        if let Err(error) = handle_connection(&handler, stream).await {
+            handler.report_error(&error);
+        }
+
+        drop(connection_guard);
+    });
+}
+
+/// Handles a connection in its own thread inside the runtime, giving its error to the error hook.
+#[cfg(feature = "tls")]
+fn spawn_thread<F>(handler: &Arc<ConnectionHandler>, handle: F)
+where
+    F: FnOnce(&ConnectionHandler) -> Result<(), Error> + Send + 'static,
+{
+    let handler = handler.clone();
+    let connection_guard = handler.shutdown.connection_guard();
+    let runtime = Handle::current();
+
+    thread::spawn(move || {
+        let _runtime = runtime.enter();
+
+        if let Err(error) = handle(&handler) {
+            handler.report_error(&error);
+        }
+
+        drop(connection_guard);
+    });
+}
+
+/// Handles the HTTP/1.x requests sent through the connection until the client closes it, it stays idle for longer than the keep-alive timeout or the server shuts down. Connections taken over by another protocol are handed over to a thread.
+async fn handle_connection<S>(handler: &Arc<ConnectionHandler>, stream: S) -> Result<(), Error>
+where
+    S: AsyncStream,
+{
+    let mut reader = tokio::io::BufReader::new(stream);
+
+    while wait_for_request(handler, &mut reader).await? {
+        let mut head_buffer = Vec::new();
+
+        let request_head = match read_request_head(&mut reader, &mut head_buffer).await {
+            Ok(Some(request_head)) => Ok(request_head),
+            Ok(None) => break,
+            Err(Error::Io(err)) => return Err(Error::Io(err)),
+            Err(error) => Err(error),
+        };
+
+        let request = match handler.check_request_head(request_head) {
+            HeadCheck::Http2(start) => return serve_http2(handler, reader, start).await,
+            HeadCheck::Rejected(rejection) => {
+                reader.get_mut().write_all(&rejection.to_binary()).await?;
+                break;
+            }
+            HeadCheck::ReadBody(mut request, body_length, send_continue) => {
+                if send_continue {
+                    reader
+                        .get_mut()
+                        .write_all(&Response::new(Status::Continue).to_binary())
+                        .await?;
+                }
+
+                let mut body = vec![0; body_length];
+                reader.read_exact(&mut body).await?;
+                request.set_body(body);
+
+                Ok(request)
+            }
+            HeadCheck::Invalid(error) => Err(error),
+        };
+
+        let request = match request.map(connection::upgrade_to_h2c) {
+            Ok(Ok((switching_protocols, start))) => {
+                reader
+                    .get_mut()
+                    .write_all(&switching_protocols.to_binary())
+                    .await?;
+
+                return serve_http2(handler, reader, start).await;
+            }
+            Ok(Err(request)) => Ok(*request),
+            Err(error) => Err(error),
+        };
+
+        let blocking_handler = handler.clone();
+        let (resp, sending, error) =
+            task::spawn_blocking(move || blocking_handler.respond_http1(request))
+                .await
+                .map_err(io::Error::other)?;
+
+        reader.get_mut().write_all(&resp.to_binary()).await?;
+
+        match sending {
+            Sending::Upgrade(upgrade) => {
+                return hand_over(handler, reader, move |_, reader| upgrade.run(reader)).await
+            }
+            Sending::Stream(body_stream) => {
+                if let Some(body_stream) = body_stream {
+                    match stream_body(handler, reader.get_mut(), &body_stream).await {
+                        Err(err) if connection::is_disconnection(&err) => (),
+                        result => result?,
+                    }
+                }
+
+                return error.map_or(Ok(()), Err);
+            }
+            Sending::Complete(keep_alive) => {
+                if let Some(error) = error {
+                    return Err(error);
+                }
+
+                if !keep_alive {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Hands the connection over to a thread where it's served with HTTP/2.
+async fn serve_http2<S>(
+    handler: &Arc<ConnectionHandler>,
+    reader: tokio::io::BufReader<S>,
+    start: Start,
+) -> Result<(), Error>
+where
+    S: AsyncStream,
+{
+    hand_over(handler, reader, move |handler, mut reader| {
+        http2::serve(handler, &mut reader, start)?;
+        reader.get_mut().close()?;
+        Ok(())
+    })
+    .await
+}
+
+/// Waits until the client sends data. Returns false if the connection is closed, stays idle for longer than the keep-alive timeout or the server shuts down while waiting.
+async fn wait_for_request<S>(
+    handler: &ConnectionHandler,
+    reader: &mut tokio::io::BufReader<S>,
+) -> io::Result<bool>
+where
+    S: AsyncStream,
+{
+    if !reader.buffer().is_empty() {
+        return Ok(true);
+    }
+
+    tokio::select! {
+        buf = reader.fill_buf() => Ok(!buf?.is_empty()),
+        _ = tokio::time::sleep(handler.keep_alive_timeout) => Ok(false),
+        _ = handler.shutdown.shutting_down() => Ok(false),
+    }
+}
+
+/// Reads the head of a request with a [RequestParser], up to the empty line that separates it from the body. Returns `None` if the client closes the connection before sending the whole head.
+async fn read_request_head<'b, S>(
+    reader: &mut tokio::io::BufReader<S>,
+    buffer: &'b mut Vec<u8>,
+) -> Result<Option<RequestHead<'b>>, Error>
+where
+    S: AsyncStream,
+{
+    let mut parser = RequestParser::new();
+
+    loop {
+        let received = reader.fill_buf().await?;
+
+        if received.is_empty() {
+            return Ok(None);
+        }
+
+        let (consumed, complete) = connection::parse_received(&mut parser, buffer, received)?;
+        reader.consume(consumed);
+
+        if complete {
+            return Ok(parser.head(buffer));
+        }
+    }
+}
+
+/// Writes the chunks of a streamed body as they are produced, until the body ends or the server shuts down, after writing the chunks already produced.
+async fn stream_body<S>(
+    handler: &ConnectionHandler,
+    stream: &mut S,
+    body_stream: &BodyStream,
+) -> io::Result<()>
+where
+    S: AsyncStream,
+{
+    loop {
+        let chunk = tokio::select! {
+            chunk = body_stream.next_chunk() => chunk,
+            _ = handler.shutdown.shutting_down() => break,
+        };
+
+        match chunk {
+            Chunk::Data(data) => {
+                stream.write_all(&data).await?;
+                stream.flush().await?;
+            }
+            Chunk::Pending => (),
+            Chunk::End => return Ok(()),
+        }
+    }
+
+    for data in body_stream.produced_chunks() {
+        stream.write_all(&data).await?;
+        stream.flush().await?;
+    }
+
+    Ok(())
+}
+
+/// Hands the connection over to a thread inside the runtime, where it's handled by a blocking protocol, and waits for it to finish.
+async fn hand_over<S, F>(
+    handler: &Arc<ConnectionHandler>,
+    reader: tokio::io::BufReader<S>,
+    serve: F,
+) -> Result<(), Error>
+where
+    S: AsyncStream,
+    F: FnOnce(&ConnectionHandler, BufferedStream) -> Result<(), Error> + Send + 'static,
+{
+    let stream: Box<dyn Stream> = Box::new(HandedOverStream {
+        buffered: Cursor::new(reader.buffer().to_vec()),
+        stream: reader.into_inner().into_blocking()?,
+    });
+
+    let handler = handler.clone();
+    let runtime = Handle::current();
+    let (result_sender, result_receiver) = oneshot::channel();
+
+    thread::spawn(move || {
+        let _runtime = runtime.enter();
+        let _ = result_sender.send(serve(&handler, BufReader::new(stream)));
+    });
+
+    // The sender is only dropped without a result if the protocol panics, which ends the connection
+    result_receiver.await.unwrap_or(Ok(()))
+}
+
+/// Blocking stream of a connection handed over to a thread, which returns the bytes the async connection already read before reading from the socket.
+struct HandedOverStream {
+    buffered: Cursor<Vec<u8>>,
+    stream: Box<dyn Stream>,
+}
+
+impl Read for HandedOverStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if (self.buffered.position() as usize) < self.buffered.get_ref().len() {
+            return Read::read(&mut self.buffered, buf);
+        }
+
+        self.stream.read(buf)
+    }
+}
+
+impl Write for HandedOverStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Stream for HandedOverStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+
+    fn try_clone_reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        let buffered = mem::take(&mut self.buffered);
+        Ok(Box::new(Read::chain(
+            buffered,
+            self.stream.try_clone_reader()?,
+        )))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        self.stream.close()
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.stream.is_encrypted()
+    }
+
+    fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.stream.alpn_protocol()
+    }
+}
+
+/// Runs the future started by `start` until it completes, blocking the thread.
+///
+/// Uses the runtime of the thread if it's a multi-thread one (e.g. the blocking thread pool of [HttpServer::listen_async]), moving the tasks of the worker to other threads if it's called from one of them. Current-thread runtimes can't be blocked, as the thread may be the one driving them, so the future runs in its own thread instead. Threads without a runtime (e.g. the ones of [HttpServer::listen]) use a runtime shared by all of them, which is started the first time it's needed.
+pub(crate) fn block_on<F, Fut>(start: F) -> Result<Fut::Output, Error>
+where
+    F: FnOnce() -> Fut + Send,
+    Fut: Future,
+    Fut::Output: Send,
+{
+    static RUNTIME: OnceLock<io::Result<Runtime>> = OnceLock::new();
+
+    match Handle::try_current() {
+        Ok(runtime) if runtime.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            Ok(task::block_in_place(|| runtime.block_on(start())))
+        }
+        Ok(_) => thread::scope(|scope| {
+            scope
+                .spawn(|| block_on(start))
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload))
+        }),
+        Err(_) => {
+            let runtime = RUNTIME
+                .get_or_init(|| {
+                    tokio::runtime::Builder::new_multi_thread()
+                        .enable_all()
+                        .build()
+                })
+                .as_ref()
+                .map_err(|err| io::Error::new(err.kind(), err.to_string()))?;
+
+            Ok(runtime.block_on(start()))
+        }
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    io::{self, prelude::*, BufReader, ErrorKind},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    http2::{self, Start},
+    listener::{BufferedStream, Stream},
+    package::Package,
+    request::{Method, ParseStatus, Request, RequestError, RequestHead, RequestParser},
+    response::{BodyStream, Chunk, IntoResponse, ProtocolUpgrade, Response, Status},
+    router::Router,
+    shutdown::ShutdownHandle,
+    virtual_host::{self, HostPattern},
+    BinaryRepresentation, ContinueHook, Error, ErrorHook, ServerError,
+};
+
+/// Time between the checks of an idle connection for a shutdown of the server.
+pub(crate) const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Routers and settings shared by the connections of an [crate::HttpServer].
+pub(crate) struct ConnectionHandler {
+    pub(crate) router: Option<Router>,
+    pub(crate) host_routers: Vec<(HostPattern, Router)>,
+    /// Port assumed for the `Host` headers without one, 443 for HTTPS listeners and 80 for the rest.
+    pub(crate) default_port: u16,
+    pub(crate) keep_alive_timeout: Duration,
+    pub(crate) max_body_size: usize,
+    pub(crate) continue_hook: Option<ContinueHook>,
+    pub(crate) error_hook: Option<ErrorHook>,
+    pub(crate) shutdown: ShutdownHandle,
+}
+
+impl ConnectionHandler {
+    /// Returns the router for the host of the request. HTTP/1.1 requests without a `Host` header are rejected with [RequestError::MissingHost].
+    fn select_router(&self, request: &Request) -> Result<&Router, Error> {
+        let host = match request.host() {
+            Some(host) => host,
+            None if request.http_version() == "HTTP/1.1" => {
+                return Err(Error::RequestError(RequestError::MissingHost))
+            }
+            None => "",
+        };
+
+        virtual_host::find_host_router(&self.host_routers, host, self.default_port)
+            .or(self.router.as_ref())
+            .ok_or_else(|| Error::ServerError(ServerError::UnknownHost(String::from(host))))
+    }
+
+    /// Handles the request with the router of its host. Returns the response, and the error it answers if the request failed.
+    pub(crate) fn respond(&self, request: Result<Request, Error>) -> (Response, Option<Error>) {
+        let result =
+            request.and_then(|request| self.select_router(&request)?.handle_request(request));
+
+        match result {
+            Ok(resp) => (resp, None),
+            Err(error) => ((&error).into_response(), Some(error)),
+        }
+    }
+
+    /// Gives an error that isn't handled by the router to the error hook, if there's one.
+    pub(crate) fn report_error(&self, error: &Error) {
+        if let Some(error_hook) = &self.error_hook {
+            error_hook(error);
+        }
+    }
+
+    /// Checks the head of a request before its body is read, returning the response that rejects it if any. Requests whose `Content-Length` is over the body size limit are rejected with 413 Payload Too Large, requests with an expectation other than `100-continue` with 417 Expectation Failed, and the rest are given to the continue hook if there's one.
+    pub(crate) fn reject_request_head(&self, request: &Request) -> Option<Response> {
+        if request
+            .content_length()
+            .is_some_and(|length| length > self.max_body_size)
+        {
+            return Some(Status::PayloadTooLarge.into_response());
+        }
+
+        if let Some(expectation) = expectation(request) {
+            if !expectation.eq_ignore_ascii_case("100-continue") {
+                return Some(Status::ExpectationFailed.into_response());
+            }
+        }
+
+        self.continue_hook
+            .as_ref()
+            .and_then(|continue_hook| continue_hook(request))
+    }
+
+    /// Handles the requests sent through the connection until the client closes it, it stays idle for longer than the keep-alive timeout or the server shuts down.
+    ///
+    /// Connections whose client negotiated `h2` through ALPN, starts with the HTTP/2 connection preface or asks to upgrade to `h2c` are handled with HTTP/2.
+    pub(crate) fn handle_connection<S>(&self, stream: S) -> Result<(), Error>
+    where
+        S: Stream,
+    {
+        let stream: Box<dyn Stream> = Box::new(stream);
+        let mut reader = BufReader::new(stream);
+
+        // The protocol negotiated through ALPN is only known once the handshake is done
+        match reader.get_mut().handshake() {
+            Ok(()) => (),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(())
+            }
+            Err(err) => return Err(Error::Io(err)),
+        }
+
+        match reader.get_ref().alpn_protocol() {
+            Some(b"h2") => http2::serve(self, &mut reader, Start::Preface(0))?,
+            _ => {
+                if let Some(upgrade) = self.handle_http1_connection(&mut reader)? {
+                    return upgrade.run(reader);
+                }
+            }
+        }
+
+        reader.get_mut().close()?;
+
+        Ok(())
+    }
+
+    /// Handles the HTTP/1.x requests of the connection, returning the protocol that takes over the connection if a response switches to one.
+    fn handle_http1_connection(
+        &self,
+        reader: &mut BufferedStream,
+    ) -> Result<Option<ProtocolUpgrade>, Error> {
+        while self.wait_for_request(reader)? {
+            let mut head_buffer = Vec::new();
+
+            let request_head = match read_request_head(reader, &mut head_buffer) {
+                Ok(Some(request_head)) => Ok(request_head),
+                Ok(None) => break,
+                Err(Error::Io(err)) => return Err(Error::Io(err)),
+                Err(error) => Err(error),
+            };
+
+            let request = match self.check_request_head(request_head) {
+                HeadCheck::Http2(start) => {
+                    http2::serve(self, reader, start)?;
+                    return Ok(None);
+                }
+                HeadCheck::Rejected(rejection) => {
+                    reader.get_mut().write_all(&rejection.to_binary())?;
+                    break;
+                }
+                HeadCheck::ReadBody(mut request, body_length, send_continue) => {
+                    if send_continue {
+                        reader
+                            .get_mut()
+                            .write_all(&Response::new(Status::Continue).to_binary())?;
+                    }
+
+                    let mut body = vec![0; body_length];
+                    reader.read_exact(&mut body)?;
+                    request.set_body(body);
+
+                    Ok(request)
+                }
+                HeadCheck::Invalid(error) => Err(error),
+            };
+
+            let request = match request {
+                Ok(request) if !reader.get_ref().is_encrypted() => match upgrade_to_h2c(request) {
+                    Ok((switching_protocols, start)) => {
+                        reader
+                            .get_mut()
+                            .write_all(&switching_protocols.to_binary())?;
+
+                        http2::serve(self, reader, start)?;
+                        return Ok(None);
+                    }
+                    Err(request) => Ok(*request),
+                },
+                request => request.map(|request| *request),
+            };
+
+            let (resp, sending, error) = self.respond_http1(request);
+            reader.get_mut().write_all(&resp.to_binary())?;
+
+            match sending {
+                Sending::Upgrade(upgrade) => return Ok(Some(upgrade)),
+                Sending::Stream(body_stream) => {
+                    if let Some(body_stream) = body_stream {
+                        match self.stream_body(reader.get_mut(), &body_stream) {
+                            Err(err) if is_disconnection(&err) => (),
+                            result => result?,
+                        }
+                    }
+
+                    return error.map_or(Ok(None), Err);
+                }
+                Sending::Complete(keep_alive) => {
+                    if let Some(error) = error {
+                        return Err(error);
+                    }
+
+                    if !keep_alive {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Checks the head of an HTTP/1.x request, returning how the connection goes on with it.
+    pub(crate) fn check_request_head(&self, request_head: Result<RequestHead, Error>) -> HeadCheck {
+        let request_head = match request_head {
+            Ok(request_head) => request_head,
+            Err(error) => return HeadCheck::Invalid(error),
+        };
+
+        if http2::PREFACE.starts_with(request_head.as_bytes()) {
+            return HeadCheck::Http2(Start::Preface(request_head.as_bytes().len()));
+        }
+
+        let body_length = request_head.content_length().unwrap_or(0);
+
+        let request = match Request::try_from(request_head) {
+            Ok(request) => request,
+            Err(error) => return HeadCheck::Invalid(error),
+        };
+
+        // The body isn't read, so the connection can't be used for more requests
+        if let Some(mut rejection) = self.reject_request_head(&request) {
+            rejection.add_header("Connection", "close");
+            rejection.pack();
+
+            return HeadCheck::Rejected(rejection);
+        }
+
+        let send_continue = body_length > 0 && expects_continue(&request);
+
+        HeadCheck::ReadBody(Box::new(request), body_length, send_continue)
+    }
+
+    /// Handles an HTTP/1.x request with the router of its host. Returns the response, how it's sent, and the error it answers if the request failed.
+    pub(crate) fn respond_http1(
+        &self,
+        request: Result<Request, Error>,
+    ) -> (Response, Sending, Option<Error>) {
+        let wants_keep_alive = request.as_ref().is_ok_and(wants_keep_alive);
+        let is_head = request.as_ref().is_ok_and(is_head);
+
+        let (mut resp, error) = self.respond(request);
+
+        if let Some(upgrade) = resp.upgrade.take() {
+            return (resp, Sending::Upgrade(upgrade), error);
+        }
+
+        // Streamed bodies end when the connection is closed, as their length isn't known
+        if let Some(body_stream) = resp.body_stream.take() {
+            resp.add_header("Connection", "close");
+
+            // Responses to HEAD requests only send the head
+            let body_stream = (!is_head).then_some(body_stream);
+
+            return (resp, Sending::Stream(body_stream), error);
+        }
+
+        let keep_alive = wants_keep_alive && error.is_none() && !self.shutdown.is_shutting_down();
+
+        resp.add_header(
+            "Connection",
+            if keep_alive { "keep-alive" } else { "close" },
+        );
+        resp.pack();
+
+        if is_head {
+            resp.set_body(Vec::new());
+        }
+
+        (resp, Sending::Complete(keep_alive), error)
+    }
+
+    /// Writes the chunks of a streamed body as they are produced, until the body ends or the server shuts down, after writing the chunks already produced.
+    fn stream_body(
+        &self,
+        stream: &mut Box<dyn Stream>,
+        body_stream: &BodyStream,
+    ) -> io::Result<()> {
+        while !self.shutdown.is_shutting_down() {
+            match body_stream.poll(IDLE_POLL_INTERVAL) {
+                Chunk::Data(data) => {
+                    stream.write_all(&data)?;
+                    stream.flush()?;
+                }
+                Chunk::Pending => (),
+                Chunk::End => return Ok(()),
+            }
+        }
+
+        for data in body_stream.produced_chunks() {
+            stream.write_all(&data)?;
+            stream.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the client sends data. Returns false if the connection is closed, stays idle for longer than the keep-alive timeout or the server shuts down while waiting.
+    fn wait_for_request<S>(&self, reader: &mut BufReader<S>) -> io::Result<bool>
+    where
+        S: Stream,
+    {
+        let idle_since = Instant::now();
+
+        reader
+            .get_ref()
+            .set_read_timeout(Some(IDLE_POLL_INTERVAL))?;
+
+        let has_request = loop {
+            if !reader.buffer().is_empty() {
+                break true;
+            }
+
+            match reader.fill_buf() {
+                Ok(buf) => break !buf.is_empty(),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+                Err(err) => return Err(err),
+            }
+
+            if self.shutdown.is_shutting_down() || idle_since.elapsed() >= self.keep_alive_timeout {
+                break false;
+            }
+        };
+
+        reader.get_ref().set_read_timeout(None)?;
+
+        Ok(has_request)
+    }
+}
+
+/// How an HTTP/1.x connection goes on after reading the head of a request.
+pub(crate) enum HeadCheck {
+    /// The connection starts with the HTTP/2 connection preface, so it's handled with HTTP/2.
+    Http2(Start),
+    /// The request is answered with the response without reading its body, closing the connection.
+    Rejected(Response),
+    /// The body of the request, of the given length, has to be read. It's preceded by a 100 Continue response if the flag is set.
+    ReadBody(Box<Request>, usize, bool),
+    /// The head isn't a valid request, the error is answered instead.
+    Invalid(Error),
+}
+
+/// How the response to an HTTP/1.x request is sent, after writing its head.
+pub(crate) enum Sending {
+    /// The protocol takes over the connection.
+    Upgrade(ProtocolUpgrade),
+    /// The body is streamed until it ends, closing the connection afterwards. Responses to HEAD requests have no body to stream.
+    Stream(Option<BodyStream>),
+    /// The whole response was written, and the connection is kept alive for more requests if the flag is set.
+    Complete(bool),
+}
+
+/// Returns the 101 Switching Protocols response and the start of the HTTP/2 connection if the request asks to upgrade to `h2c`, or gives the request back otherwise.
+pub(crate) fn upgrade_to_h2c(request: Box<Request>) -> Result<(Response, Start), Box<Request>> {
+    let Some(settings) = http2::upgrade_settings(&request) else {
+        return Err(request);
+    };
+
+    let mut switching_protocols = Response::new(Status::SwitchingProtocol);
+    switching_protocols.add_header("Connection", "Upgrade");
+    switching_protocols.add_header("Upgrade", "h2c");
+
+    Ok((switching_protocols, Start::Upgrade(request, settings)))
+}
+
+/// HTTP/1.1 connections are kept alive unless the client asks to close them, HTTP/1.0 connections are closed unless the client asks to keep them alive.
+fn wants_keep_alive(request: &Request) -> bool {
+    let connection = request
+        .find_header("Connection")
+        .map(|connection| connection.to_ascii_lowercase());
+
+    match request.http_version() {
+        "HTTP/1.1" => !connection.is_some_and(|connection| connection.contains("close")),
+        _ => connection.is_some_and(|connection| connection.contains("keep-alive")),
+    }
+}
+
+/// Returns true if the request is a HEAD request, whose response is sent without its body.
+fn is_head(request: &Request) -> bool {
+    request.path.method == Method::HEAD
+}
+
+/// Returns true if the error means the client closed the connection.
+pub(crate) fn is_disconnection(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+    )
+}
+
+/// Returns the expectation of the `Expect` header. HTTP/1.0 requests have no expectations, as the header was added in HTTP/1.1.
+fn expectation(request: &Request) -> Option<&str> {
+    match request.http_version() {
+        "HTTP/1.0" => None,
+        _ => request.find_header("Expect").map(str::trim),
+    }
+}
+
+/// Returns true if the client waits for a 100 Continue before sending the body.
+fn expects_continue(request: &Request) -> bool {
+    expectation(request).is_some_and(|expectation| expectation.eq_ignore_ascii_case("100-continue"))
+}
+
+/// Reads the head of a request with a [RequestParser], up to the empty line that separates it from the body. Returns `None` if the client closes the connection before sending the whole head.
+fn read_request_head<'b, R>(
+    reader: &mut R,
+    buffer: &'b mut Vec<u8>,
+) -> Result<Option<RequestHead<'b>>, Error>
+where
+    R: BufRead,
+{
+    let mut parser = RequestParser::new();
+
+    loop {
+        let received = reader.fill_buf()?;
+
+        if received.is_empty() {
+            return Ok(None);
+        }
+
+        let (consumed, complete) = parse_received(&mut parser, buffer, received)?;
+        reader.consume(consumed);
+
+        if complete {
+            return Ok(parser.head(buffer));
+        }
+    }
+}
+
+/// Adds the bytes received by the connection to the head being parsed. Returns how many of them have to be consumed from the reader, and if the head is complete.
+pub(crate) fn parse_received(
+    parser: &mut RequestParser,
+    buffer: &mut Vec<u8>,
+    received: &[u8],
+) -> Result<(usize, bool), Error> {
+    let parsed = buffer.len();
+    buffer.extend_from_slice(received);
+
+    // Only the bytes of the head are consumed, the rest belong to the body or the next request
+    match parser.parse(buffer)? {
+        ParseStatus::Complete(head_length) => Ok((head_length - parsed, true)),
+        ParseStatus::Partial => Ok((received.len(), false)),
+    }
+}
@@ -0,0 +1,223 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Minimum length, in bytes, of the secret used to build a [Key].
+pub const MIN_SECRET_LENGTH: usize = 32;
+
+const SIGNATURE_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+
+/// Cryptographic key used by the [CookieJar] to sign and encrypt cookies. Both the signing and the encryption keys are derived from a single secret.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl Key {
+    /// Derives a new key from the given secret. The secret must be at least [MIN_SECRET_LENGTH] bytes long, else it will return [CookieJarError::KeyTooShort].
+    pub fn from_secret(secret: &[u8]) -> Result<Self, crate::Error> {
+        if secret.len() < MIN_SECRET_LENGTH {
+            return Err(crate::Error::CookieJarError(CookieJarError::KeyTooShort(
+                secret.len(),
+            )));
+        }
+
+        Ok(Key {
+            signing: Self::derive(secret, b"servidor_http signing key"),
+            encryption: Self::derive(secret, b"servidor_http encryption key"),
+        })
+    }
+
+    /// Generates a new key from a random secret provided by the operating system.
+    pub fn generate() -> Result<Self, crate::Error> {
+        let mut secret = [0; MIN_SECRET_LENGTH];
+        getrandom::getrandom(&mut secret).map_err(|err| {
+            crate::Error::CookieJarError(CookieJarError::RandomUnavailable(err.to_string()))
+        })?;
+
+        Self::from_secret(&secret)
+    }
+
+    fn derive(secret: &[u8], purpose: &[u8]) -> [u8; 32] {
+        let mut mac =
+            <HmacSha256 as Mac>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+        mac.update(purpose);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn mac(&self, name: &str, value: &str) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.signing)
+            .expect("HMAC accepts keys of any size");
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(value.as_bytes());
+        mac
+    }
+
+    fn signature(&self, name: &str, value: &str) -> [u8; SIGNATURE_LENGTH] {
+        self.mac(name, value).finalize().into_bytes().into()
+    }
+
+    fn verify(&self, name: &str, value: &str, signature: &[u8]) -> bool {
+        self.mac(name, value).verify_slice(signature).is_ok()
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new_from_slice(&self.encryption).expect("Encryption key is 32 bytes")
+    }
+}
+
+/// The key material is never printed.
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key { .. }")
+    }
+}
+
+/// Signs and encrypts the cookies of a [crate::response::Response] and verifies or decrypts the cookies of a [crate::request::Request].
+///
+/// The jar contains a current key, used to sign and encrypt new cookies, and a list of rotated keys that are only used to read cookies generated before the last key rotation.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::{cookie_jar::{CookieJar, Key}, request::Request, response::{Response, Status}};
+///
+/// let jar = CookieJar::new(Key::from_secret(b"a very long secret that nobody can guess").unwrap());
+///
+/// let mut res = Response::new(Status::OK);
+/// res.set_signed_cookie(&jar, "user", "42").unwrap();
+///
+/// let set_cookie = res.get_cookie("user").unwrap();
+/// let req = Request::try_from(format!("GET / HTTP/1.1\r\nCookie: user={}\r\n", set_cookie).as_str()).unwrap();
+///
+/// assert_eq!(req.cookies.get_signed(&jar, "user"), Some(String::from("42")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    key: Key,
+    rotated_keys: Vec<Key>,
+}
+
+impl CookieJar {
+    /// Generates a new cookie jar that signs and encrypts the cookies with the given key.
+    pub fn new(key: Key) -> Self {
+        CookieJar {
+            key,
+            rotated_keys: Vec::new(),
+        }
+    }
+
+    /// Replaces the current key with a new one. The old key is kept in order to read the cookies generated with it.
+    pub fn rotate(&mut self, key: Key) {
+        let old_key = std::mem::replace(&mut self.key, key);
+        self.rotated_keys.insert(0, old_key);
+    }
+
+    /// Adds a key that will only be used to read cookies generated before a key rotation.
+    pub fn add_rotated_key(&mut self, key: Key) {
+        self.rotated_keys.push(key);
+    }
+
+    /// Removes all the rotated keys, cookies generated with them will no longer be valid.
+    pub fn clear_rotated_keys(&mut self) {
+        self.rotated_keys.clear();
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.key).chain(self.rotated_keys.iter())
+    }
+
+    /// Returns the signed representation of a cookie value. The value remains readable by the client but it can't be modified without invalidating the signature.
+    pub fn sign(&self, name: &str, value: &str) -> String {
+        let signature = self.key.signature(name, value);
+        format!("{}{}", URL_SAFE_NO_PAD.encode(signature), value)
+    }
+
+    /// Verifies a signed cookie value and returns the original value. Returns None if the signature doesn't match any of the keys of the jar.
+    pub fn verify(&self, name: &str, signed_value: &str) -> Option<String> {
+        let encoded_length = URL_SAFE_NO_PAD.encode([0; SIGNATURE_LENGTH]).len();
+
+        if !signed_value.is_char_boundary(encoded_length) {
+            return None;
+        }
+
+        let (encoded_signature, value) = signed_value.split_at(encoded_length);
+        let signature = URL_SAFE_NO_PAD.decode(encoded_signature).ok()?;
+
+        self.keys()
+            .any(|key| key.verify(name, value, &signature))
+            .then(|| String::from(value))
+    }
+
+    /// Returns the encrypted representation of a cookie value. The value can't be read nor modified by the client. Returns [CookieJarError::RandomUnavailable] if the operating system can't provide the random nonce.
+    pub fn encrypt(&self, name: &str, value: &str) -> Result<String, crate::Error> {
+        let mut nonce = [0; NONCE_LENGTH];
+        getrandom::getrandom(&mut nonce).map_err(|err| {
+            crate::Error::CookieJarError(CookieJarError::RandomUnavailable(err.to_string()))
+        })?;
+
+        let ciphertext = self
+            .key
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: name.as_bytes(),
+                },
+            )
+            .expect("Cookie value too long to be encrypted");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+
+        Ok(URL_SAFE_NO_PAD.encode(sealed))
+    }
+
+    /// Decrypts an encrypted cookie value. Returns None if the value can't be decrypted with any of the keys of the jar.
+    pub fn decrypt(&self, name: &str, encrypted_value: &str) -> Option<String> {
+        let sealed = URL_SAFE_NO_PAD.decode(encrypted_value).ok()?;
+
+        if sealed.len() < NONCE_LENGTH {
+            return None;
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+
+        self.keys().find_map(|key| {
+            let plaintext = key
+                .cipher()
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: name.as_bytes(),
+                    },
+                )
+                .ok()?;
+
+            String::from_utf8(plaintext).ok()
+        })
+    }
+}
+
+/// Errors that can occur when using the [CookieJar].
+#[derive(Debug, thiserror::Error)]
+pub enum CookieJarError {
+    /// The secret used to generate a [Key] is shorter than [MIN_SECRET_LENGTH].
+    #[error("Cookie key secret too short: {0} bytes, at least 32 bytes needed")]
+    KeyTooShort(usize),
+
+    /// The operating system couldn't provide random bytes to generate a [Key] or encrypt a cookie.
+    #[error("Random source unavailable: {0}")]
+    RandomUnavailable(String),
+}
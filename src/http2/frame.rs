@@ -0,0 +1,326 @@
+use crate::BinaryRepresentation;
+
+use super::Http2Error;
+
+/// Connection preface every client sends before its first frame.
+pub(crate) const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Size of the header that precedes the payload of every frame.
+const FRAME_HEADER_SIZE: usize = 9;
+
+/// Size of the flow control windows when a connection starts.
+pub(crate) const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+
+/// Largest size a flow control window can reach.
+pub(crate) const MAX_WINDOW_SIZE: i64 = 2_147_483_647;
+
+/// Largest payload a frame can carry unless the receiver allows bigger ones, it's also the smallest limit a receiver can set.
+pub(crate) const DEFAULT_MAX_FRAME_SIZE: u32 = 16_384;
+
+/// Largest payload a frame can ever carry.
+const MAX_FRAME_SIZE_LIMIT: u32 = 16_777_215;
+
+/// Flag of the DATA and HEADERS frames that closes the stream for the sender.
+pub(crate) const END_STREAM: u8 = 0x1;
+
+/// Flag of the SETTINGS and PING frames that acknowledges the ones received.
+pub(crate) const ACK: u8 = 0x1;
+
+/// Flag of the HEADERS and CONTINUATION frames that ends the header block.
+pub(crate) const END_HEADERS: u8 = 0x4;
+
+/// Flag of the DATA and HEADERS frames whose payload is padded.
+const PADDED: u8 = 0x8;
+
+/// Flag of the HEADERS frames whose payload starts with the priority of the stream.
+const PRIORITY: u8 = 0x20;
+
+/// Setting with the maximum number of streams the sender allows to be open at the same time.
+pub(crate) const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+
+/// Setting with the maximum size of the header fields the sender accepts, the size of each field being its name and value lengths plus 32 bytes.
+pub(crate) const SETTINGS_MAX_HEADER_LIST_SIZE: u16 = 0x6;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+/// Types of frame, frames of unknown types are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FrameKind {
+    Data,
+    Headers,
+    Priority,
+    RstStream,
+    Settings,
+    PushPromise,
+    Ping,
+    GoAway,
+    WindowUpdate,
+    Continuation,
+    Unknown(u8),
+}
+
+impl From<u8> for FrameKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0x0 => FrameKind::Data,
+            0x1 => FrameKind::Headers,
+            0x2 => FrameKind::Priority,
+            0x3 => FrameKind::RstStream,
+            0x4 => FrameKind::Settings,
+            0x5 => FrameKind::PushPromise,
+            0x6 => FrameKind::Ping,
+            0x7 => FrameKind::GoAway,
+            0x8 => FrameKind::WindowUpdate,
+            0x9 => FrameKind::Continuation,
+            kind => FrameKind::Unknown(kind),
+        }
+    }
+}
+
+impl From<FrameKind> for u8 {
+    fn from(kind: FrameKind) -> Self {
+        match kind {
+            FrameKind::Data => 0x0,
+            FrameKind::Headers => 0x1,
+            FrameKind::Priority => 0x2,
+            FrameKind::RstStream => 0x3,
+            FrameKind::Settings => 0x4,
+            FrameKind::PushPromise => 0x5,
+            FrameKind::Ping => 0x6,
+            FrameKind::GoAway => 0x7,
+            FrameKind::WindowUpdate => 0x8,
+            FrameKind::Continuation => 0x9,
+            FrameKind::Unknown(kind) => kind,
+        }
+    }
+}
+
+/// Error codes of the RST_STREAM and GOAWAY frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorCode {
+    NoError = 0x0,
+    ProtocolError = 0x1,
+    FlowControlError = 0x3,
+    StreamClosed = 0x5,
+    FrameSizeError = 0x6,
+    RefusedStream = 0x7,
+    CompressionError = 0x9,
+}
+
+/// Frame of an HTTP/2 connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    pub(crate) kind: FrameKind,
+    pub(crate) flags: u8,
+    pub(crate) stream_id: u32,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl Frame {
+    pub(crate) fn new(kind: FrameKind, flags: u8, stream_id: u32, payload: Vec<u8>) -> Self {
+        Frame {
+            kind,
+            flags,
+            stream_id,
+            payload,
+        }
+    }
+
+    /// Parses the frame at the start of the buffer, returning it with its size or `None` if the buffer doesn't hold the whole frame yet.
+    pub(crate) fn parse(buffer: &[u8]) -> Result<Option<(Frame, usize)>, Http2Error> {
+        if buffer.len() < FRAME_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let length = u32::from_be_bytes([0, buffer[0], buffer[1], buffer[2]]);
+
+        if length > DEFAULT_MAX_FRAME_SIZE {
+            return Err(Http2Error::FrameSize(format!(
+                "Frame of {} bytes is bigger than the maximum frame size",
+                length
+            )));
+        }
+
+        let frame_size = FRAME_HEADER_SIZE + length as usize;
+
+        if buffer.len() < frame_size {
+            return Ok(None);
+        }
+
+        let stream_id =
+            u32::from_be_bytes([buffer[5], buffer[6], buffer[7], buffer[8]]) & 0x7fff_ffff;
+
+        let frame = Frame::new(
+            FrameKind::from(buffer[3]),
+            buffer[4],
+            stream_id,
+            buffer[FRAME_HEADER_SIZE..frame_size].to_vec(),
+        );
+
+        Ok(Some((frame, frame_size)))
+    }
+
+    pub(crate) fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Returns the data of a DATA frame or the header block fragment of a HEADERS frame, without the padding and the priority.
+    pub(crate) fn content(&self) -> Result<&[u8], Http2Error> {
+        let mut content = self.payload.as_slice();
+
+        if self.has_flag(PADDED) {
+            let (&padding, rest) = content.split_first().ok_or_else(|| {
+                Http2Error::FrameSize(String::from("Padded frame without padding length"))
+            })?;
+
+            content = rest
+                .len()
+                .checked_sub(padding as usize)
+                .map(|length| &rest[..length])
+                .ok_or_else(|| {
+                    Http2Error::Protocol(String::from("Padding longer than the frame payload"))
+                })?;
+        }
+
+        if self.kind == FrameKind::Headers && self.has_flag(PRIORITY) {
+            content = content.get(5..).ok_or_else(|| {
+                Http2Error::FrameSize(String::from("HEADERS frame too short for its priority"))
+            })?;
+        }
+
+        Ok(content)
+    }
+
+    /// Reads the 31 bits integer a WINDOW_UPDATE frame carries.
+    pub(crate) fn window_size_increment(&self) -> Result<u32, Http2Error> {
+        match self.payload.as_slice() {
+            &[a, b, c, d] => Ok(u32::from_be_bytes([a, b, c, d]) & 0x7fff_ffff),
+            _ => Err(Http2Error::FrameSize(String::from(
+                "WINDOW_UPDATE frame payload must be 4 bytes long",
+            ))),
+        }
+    }
+
+    pub(crate) fn settings(settings: &[(u16, u32)]) -> Self {
+        let payload = settings
+            .iter()
+            .flat_map(|(identifier, value)| {
+                identifier
+                    .to_be_bytes()
+                    .into_iter()
+                    .chain(value.to_be_bytes())
+            })
+            .collect();
+
+        Frame::new(FrameKind::Settings, 0, 0, payload)
+    }
+
+    pub(crate) fn settings_ack() -> Self {
+        Frame::new(FrameKind::Settings, ACK, 0, Vec::new())
+    }
+
+    pub(crate) fn ping_ack(payload: Vec<u8>) -> Self {
+        Frame::new(FrameKind::Ping, ACK, 0, payload)
+    }
+
+    pub(crate) fn window_update(stream_id: u32, increment: u32) -> Self {
+        Frame::new(
+            FrameKind::WindowUpdate,
+            0,
+            stream_id,
+            increment.to_be_bytes().to_vec(),
+        )
+    }
+
+    pub(crate) fn rst_stream(stream_id: u32, error_code: ErrorCode) -> Self {
+        Frame::new(
+            FrameKind::RstStream,
+            0,
+            stream_id,
+            (error_code as u32).to_be_bytes().to_vec(),
+        )
+    }
+
+    pub(crate) fn go_away(last_stream_id: u32, error_code: ErrorCode) -> Self {
+        let mut payload = last_stream_id.to_be_bytes().to_vec();
+        payload.extend_from_slice(&(error_code as u32).to_be_bytes());
+
+        Frame::new(FrameKind::GoAway, 0, 0, payload)
+    }
+}
+
+impl BinaryRepresentation for Frame {
+    fn to_binary(&self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + self.payload.len());
+
+        frame.extend_from_slice(&(self.payload.len() as u32).to_be_bytes()[1..]);
+        frame.push(u8::from(self.kind));
+        frame.push(self.flags);
+        frame.extend_from_slice(&self.stream_id.to_be_bytes());
+        frame.extend_from_slice(&self.payload);
+
+        frame
+    }
+}
+
+/// Settings of the client that affect the frames sent by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Settings {
+    pub(crate) initial_window_size: u32,
+    pub(crate) max_frame_size: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            initial_window_size: DEFAULT_WINDOW_SIZE,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+}
+
+impl Settings {
+    /// Applies the settings of a SETTINGS frame payload, ignoring the unknown ones and the ones that don't affect the server.
+    pub(crate) fn apply(&mut self, payload: &[u8]) -> Result<(), Http2Error> {
+        if !payload.len().is_multiple_of(6) {
+            return Err(Http2Error::FrameSize(String::from(
+                "SETTINGS frame payload must be a multiple of 6 bytes long",
+            )));
+        }
+
+        for setting in payload.chunks_exact(6) {
+            let identifier = u16::from_be_bytes([setting[0], setting[1]]);
+            let value = u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]);
+
+            match identifier {
+                SETTINGS_ENABLE_PUSH if value > 1 => {
+                    return Err(Http2Error::Protocol(format!(
+                        "Invalid SETTINGS_ENABLE_PUSH value {}",
+                        value
+                    )))
+                }
+                SETTINGS_INITIAL_WINDOW_SIZE if value as i64 > MAX_WINDOW_SIZE => {
+                    return Err(Http2Error::FlowControl(format!(
+                        "Invalid SETTINGS_INITIAL_WINDOW_SIZE value {}",
+                        value
+                    )))
+                }
+                SETTINGS_INITIAL_WINDOW_SIZE => self.initial_window_size = value,
+                SETTINGS_MAX_FRAME_SIZE
+                    if !(DEFAULT_MAX_FRAME_SIZE..=MAX_FRAME_SIZE_LIMIT).contains(&value) =>
+                {
+                    return Err(Http2Error::Protocol(format!(
+                        "Invalid SETTINGS_MAX_FRAME_SIZE value {}",
+                        value
+                    )))
+                }
+                SETTINGS_MAX_FRAME_SIZE => self.max_frame_size = value,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
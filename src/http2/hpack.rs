@@ -0,0 +1,334 @@
+use std::collections::VecDeque;
+
+use super::{huffman, Http2Error};
+
+/// Header fields every connection knows, from the appendix A of RFC 7541. Their indexes start at 1.
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// Bytes every entry of the dynamic table takes on top of its name and value.
+const ENTRY_OVERHEAD: usize = 32;
+
+/// Size of the dynamic table the decoder allows, it's the default one so it doesn't need to be sent in the settings.
+pub(crate) const DEFAULT_TABLE_SIZE: usize = 4096;
+
+/// Decoder of the header blocks sent by a client, it keeps the dynamic table shared by every block of the connection.
+#[derive(Debug)]
+pub(crate) struct Decoder {
+    entries: VecDeque<(Vec<u8>, Vec<u8>)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Decoder {
+    pub(crate) fn new() -> Self {
+        Decoder {
+            entries: VecDeque::new(),
+            size: 0,
+            max_size: DEFAULT_TABLE_SIZE,
+        }
+    }
+
+    /// Decodes a header block into its header fields, updating the dynamic table. Fails once the size of the fields (their name and value lengths plus 32 bytes each) is over the given limit, as a small block can reference big entries many times.
+    pub(crate) fn decode(
+        &mut self,
+        block: &[u8],
+        max_list_size: usize,
+    ) -> Result<Vec<(String, String)>, Http2Error> {
+        let mut fields = Vec::new();
+        let mut list_size = 0;
+        let mut position = 0;
+
+        while let Some(&first_byte) = block.get(position) {
+            let (name, value) = match first_byte {
+                // Indexed header field
+                0x80..=0xff => {
+                    let index = decode_integer(block, &mut position, 7)?;
+                    self.entry(index)?
+                }
+
+                // Literal header field with incremental indexing
+                0x40..=0x7f => {
+                    let (name, value) = self.decode_literal(block, &mut position, 6)?;
+                    self.insert(name.clone(), value.clone());
+                    (name, value)
+                }
+
+                // Dynamic table size update
+                0x20..=0x3f => {
+                    if !fields.is_empty() {
+                        return Err(Http2Error::Compression(String::from(
+                            "Dynamic table size update after a header field",
+                        )));
+                    }
+
+                    let max_size = decode_integer(block, &mut position, 5)?;
+
+                    if max_size > DEFAULT_TABLE_SIZE {
+                        return Err(Http2Error::Compression(format!(
+                            "Dynamic table size {} is over the limit",
+                            max_size
+                        )));
+                    }
+
+                    self.max_size = max_size;
+                    self.evict(0);
+                    continue;
+                }
+
+                // Literal header field without indexing or never indexed
+                _ => self.decode_literal(block, &mut position, 4)?,
+            };
+
+            list_size += name.len() + value.len() + ENTRY_OVERHEAD;
+
+            if list_size > max_list_size {
+                return Err(Http2Error::HeadersTooLarge(max_list_size));
+            }
+
+            fields.push((
+                String::from_utf8_lossy(&name).to_string(),
+                String::from_utf8_lossy(&value).to_string(),
+            ));
+        }
+
+        Ok(fields)
+    }
+
+    /// Returns the entry of the static or the dynamic table at the given index.
+    fn entry(&self, index: usize) -> Result<(Vec<u8>, Vec<u8>), Http2Error> {
+        match index {
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Ok((name.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            }
+            _ => index
+                .checked_sub(STATIC_TABLE.len() + 1)
+                .and_then(|index| self.entries.get(index))
+                .cloned()
+                .ok_or_else(|| Http2Error::Compression(format!("Invalid table index {}", index))),
+        }
+    }
+
+    /// Decodes a literal header field whose name index uses the given prefix, reading the name as a string when the index is 0.
+    fn decode_literal(
+        &self,
+        block: &[u8],
+        position: &mut usize,
+        prefix_bits: u8,
+    ) -> Result<(Vec<u8>, Vec<u8>), Http2Error> {
+        let name = match decode_integer(block, position, prefix_bits)? {
+            0 => decode_string(block, position)?,
+            index => self.entry(index)?.0,
+        };
+
+        let value = decode_string(block, position)?;
+
+        Ok((name, value))
+    }
+
+    /// Adds an entry at the start of the dynamic table, evicting the oldest entries until it fits.
+    fn insert(&mut self, name: Vec<u8>, value: Vec<u8>) {
+        let entry_size = name.len() + value.len() + ENTRY_OVERHEAD;
+
+        self.evict(entry_size);
+
+        if entry_size <= self.max_size {
+            self.size += entry_size;
+            self.entries.push_front((name, value));
+        }
+    }
+
+    /// Evicts the oldest entries until the given size fits in the dynamic table.
+    fn evict(&mut self, size: usize) {
+        while self.size + size > self.max_size {
+            match self.entries.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => {
+                    self.size = 0;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Encodes the header fields into a header block. Fields are never added to the dynamic table, so the block doesn't depend on the state of the connection.
+pub(crate) fn encode(fields: &[(String, String)]) -> Vec<u8> {
+    let mut block = Vec::new();
+
+    for (name, value) in fields {
+        let name_index = STATIC_TABLE
+            .iter()
+            .position(|(static_name, _)| static_name == name);
+        let field_index = STATIC_TABLE
+            .iter()
+            .position(|(static_name, static_value)| static_name == name && static_value == value);
+
+        match (field_index, name_index) {
+            (Some(index), _) => encode_integer(&mut block, index + 1, 7, 0x80),
+            (None, Some(index)) => {
+                encode_integer(&mut block, index + 1, 4, 0x00);
+                encode_string(&mut block, value.as_bytes());
+            }
+            (None, None) => {
+                block.push(0x00);
+                encode_string(&mut block, name.as_bytes());
+                encode_string(&mut block, value.as_bytes());
+            }
+        }
+    }
+
+    block
+}
+
+/// Decodes an integer whose first byte uses the given number of bits, moving the position past it.
+fn decode_integer(
+    block: &[u8],
+    position: &mut usize,
+    prefix_bits: u8,
+) -> Result<usize, Http2Error> {
+    let truncated = || Http2Error::Compression(String::from("Truncated integer"));
+
+    let mask = (1 << prefix_bits) - 1;
+    let mut value = (block.get(*position).ok_or_else(truncated)? & mask) as usize;
+    *position += 1;
+
+    if value < mask as usize {
+        return Ok(value);
+    }
+
+    let mut shift = 0;
+
+    loop {
+        let byte = *block.get(*position).ok_or_else(truncated)?;
+        *position += 1;
+
+        if shift > 28 {
+            return Err(Http2Error::Compression(String::from("Integer overflow")));
+        }
+
+        value += ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+/// Decodes a string literal, Huffman encoded or not, moving the position past it.
+fn decode_string(block: &[u8], position: &mut usize) -> Result<Vec<u8>, Http2Error> {
+    let huffman_encoded = block
+        .get(*position)
+        .is_some_and(|first_byte| first_byte & 0x80 != 0);
+    let length = decode_integer(block, position, 7)?;
+
+    let bytes = block
+        .get(*position..*position + length)
+        .ok_or_else(|| Http2Error::Compression(String::from("Truncated string")))?;
+    *position += length;
+
+    match huffman_encoded {
+        true => huffman::decode(bytes),
+        false => Ok(bytes.to_vec()),
+    }
+}
+
+/// Encodes an integer using the given number of bits of the first byte, whose other bits are taken from the flags.
+fn encode_integer(block: &mut Vec<u8>, value: usize, prefix_bits: u8, flags: u8) {
+    let mask = (1 << prefix_bits) - 1;
+
+    if value < mask {
+        block.push(flags | value as u8);
+        return;
+    }
+
+    block.push(flags | mask as u8);
+
+    let mut value = value - mask;
+
+    while value >= 0x80 {
+        block.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    block.push(value as u8);
+}
+
+/// Encodes a string literal, using the Huffman code when it makes the string shorter.
+fn encode_string(block: &mut Vec<u8>, bytes: &[u8]) {
+    let encoded = huffman::encode(bytes);
+
+    match encoded.len() < bytes.len() {
+        true => {
+            encode_integer(block, encoded.len(), 7, 0x80);
+            block.extend_from_slice(&encoded);
+        }
+        false => {
+            encode_integer(block, bytes.len(), 7, 0x00);
+            block.extend_from_slice(bytes);
+        }
+    }
+}
@@ -0,0 +1,1204 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, BufReader, ErrorKind, Read},
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc,
+    },
+    task::{Wake, Waker},
+    thread::{self, Scope},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::{
+    connection::{ConnectionHandler, IDLE_POLL_INTERVAL},
+    listener::Stream,
+    request::{Method, Package, Request, DEFAULT_MAX_HEAD_SIZE},
+    response::{BodyStream, Chunk, IntoResponse, Response, Status},
+    BinaryRepresentation, Error,
+};
+
+mod frame;
+mod hpack;
+mod huffman;
+
+use frame::{
+    ErrorCode, Frame, FrameKind, Settings, ACK, DEFAULT_MAX_FRAME_SIZE, DEFAULT_WINDOW_SIZE,
+    END_HEADERS, END_STREAM, MAX_WINDOW_SIZE, SETTINGS_MAX_CONCURRENT_STREAMS,
+    SETTINGS_MAX_HEADER_LIST_SIZE,
+};
+
+pub(crate) use frame::PREFACE;
+
+/// Streams a client can have open at the same time, the ones opened over the limit are refused.
+const MAX_CONCURRENT_STREAMS: u32 = 100;
+
+/// Size limit of the header fields of a request, advertised to the client. Bigger header blocks close the connection, as they can't be decoded.
+const MAX_HEADER_LIST_SIZE: u32 = DEFAULT_MAX_HEAD_SIZE as u32;
+
+/// Events that can wait for the connection to handle them, so a client sending frames faster than they are handled is slowed down.
+const EVENT_QUEUE_SIZE: usize = 16;
+
+/// Header fields that only make sense for HTTP/1.1 connections, requests containing them are malformed and responses never contain them.
+const CONNECTION_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// How an HTTP/2 connection starts.
+pub(crate) enum Start {
+    /// The client starts with the connection preface (prior knowledge, or `h2` negotiated through ALPN), of which the given number of bytes was already read.
+    Preface(usize),
+
+    /// The client upgraded the connection with an HTTP/1.1 request (h2c) whose response is sent in stream 1. Contains the request and the settings of its `HTTP2-Settings` header.
+    Upgrade(Box<Request>, Vec<u8>),
+}
+
+/// Returns the settings of the client if the request asks to upgrade the connection to HTTP/2 over cleartext (h2c), decoded from its `HTTP2-Settings` header.
+pub(crate) fn upgrade_settings(request: &Request) -> Option<Vec<u8>> {
+    let upgrade = request.find_header("Upgrade")?;
+
+    let wants_h2c = upgrade
+        .split(',')
+        .any(|protocol| protocol.trim().eq_ignore_ascii_case("h2c"));
+
+    if request.http_version() != "HTTP/1.1" || !wants_h2c {
+        return None;
+    }
+
+    URL_SAFE_NO_PAD
+        .decode(request.find_header("HTTP2-Settings")?.trim())
+        .ok()
+        .filter(|settings| Settings::default().apply(settings).is_ok())
+}
+
+/// Handles an HTTP/2 connection until the client closes it, it stays idle for longer than the keep-alive timeout or the server shuts down. Every request is handled in its own thread, so the streams of the connection are answered concurrently.
+pub(crate) fn serve<S>(
+    handler: &ConnectionHandler,
+    reader: &mut BufReader<S>,
+    start: Start,
+) -> Result<(), Error>
+where
+    S: Stream,
+{
+    thread::scope(|scope| {
+        let mut connection = Connection::new(handler, reader);
+
+        let result = connection.run(scope, start);
+
+        if let Err(Error::Http2Error(error)) = &result {
+            connection.go_away(error.error_code());
+            let _ = connection.flush();
+        }
+
+        result
+    })
+}
+
+/// Header block being received through a HEADERS frame and its CONTINUATION frames.
+struct HeaderBlock {
+    stream_id: u32,
+    end_stream: bool,
+    fragments: Vec<u8>,
+}
+
+/// State of a stream of the connection.
+struct StreamState {
+    /// Request built from the header block, handled once its body is received.
+    request: Option<Result<Request, Error>>,
+    body: Vec<u8>,
+    /// Whether the client finished sending the request.
+    remote_closed: bool,
+    /// Whether the request was answered before the client finished sending it, so the rest of its body is discarded.
+    discarding_body: bool,
+    head: bool,
+    send_window: i64,
+    receive_window: i64,
+    /// Bytes of the connection window taken by the body, given back once the body is handed to the handler or discarded.
+    connection_window_used: u32,
+    /// Body of the response waiting for the flow control windows, and the bytes of it already sent.
+    pending_data: Option<(Vec<u8>, usize)>,
+    /// Streamed body of the response whose chunks are added to the pending data as they are produced.
+    body_stream: Option<BodyStream>,
+}
+
+impl StreamState {
+    fn new(send_window: u32) -> Self {
+        StreamState {
+            request: None,
+            body: Vec::new(),
+            remote_closed: false,
+            discarding_body: false,
+            head: false,
+            send_window: send_window as i64,
+            receive_window: DEFAULT_WINDOW_SIZE as i64,
+            connection_window_used: 0,
+            pending_data: None,
+            body_stream: None,
+        }
+    }
+}
+
+/// Response of a stream, sent by the thread that handled its request.
+struct HandledRequest {
+    stream_id: u32,
+    response: Response,
+    error: Option<Error>,
+}
+
+/// Event of a connection, received by the thread handling it.
+enum Event {
+    /// Bytes sent by the client, read by the reader thread of the connection.
+    Input(Vec<u8>),
+
+    /// The client closed the connection, or reading from it failed.
+    Closed(io::Result<()>),
+
+    /// A request was handled.
+    Handled(Box<HandledRequest>),
+
+    /// A streamed body produced a chunk or ended.
+    Chunk,
+}
+
+/// Wakes the connection when a streamed body (e.g. an event stream) produces a chunk.
+struct ChunkNotifier(SyncSender<Event>);
+
+impl Wake for ChunkNotifier {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        // If the queue is full the connection is already awake, and polls the streamed bodies once it handles the queued events
+        let _ = self.0.try_send(Event::Chunk);
+    }
+}
+
+enum Incoming {
+    Frame(Frame),
+    Nothing,
+    Closed,
+}
+
+struct Connection<'a, 'env, S>
+where
+    S: Stream,
+{
+    handler: &'env ConnectionHandler,
+    reader: &'a mut BufReader<S>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    decoder: hpack::Decoder,
+    settings: Settings,
+    streams: HashMap<u32, StreamState>,
+    last_stream_id: u32,
+    send_window: i64,
+    receive_window: i64,
+    header_block: Option<HeaderBlock>,
+    going_away: bool,
+    idle_since: Instant,
+    event_sender: SyncSender<Event>,
+    events: Receiver<Event>,
+    chunk_waker: Waker,
+    /// Whether the reader thread keeps reading from the client, it stops once the connection is dropped.
+    reading: Arc<AtomicBool>,
+    closed: bool,
+    handled: VecDeque<HandledRequest>,
+}
+
+impl<'a, 'env, S> Connection<'a, 'env, S>
+where
+    S: Stream,
+{
+    fn new(handler: &'env ConnectionHandler, reader: &'a mut BufReader<S>) -> Self {
+        let (event_sender, events) = mpsc::sync_channel(EVENT_QUEUE_SIZE);
+
+        Connection {
+            handler,
+            reader,
+            input: Vec::new(),
+            output: Vec::new(),
+            decoder: hpack::Decoder::new(),
+            settings: Settings::default(),
+            streams: HashMap::new(),
+            last_stream_id: 0,
+            send_window: DEFAULT_WINDOW_SIZE as i64,
+            receive_window: DEFAULT_WINDOW_SIZE as i64,
+            header_block: None,
+            going_away: false,
+            idle_since: Instant::now(),
+            chunk_waker: Waker::from(Arc::new(ChunkNotifier(event_sender.clone()))),
+            event_sender,
+            events,
+            reading: Arc::new(AtomicBool::new(true)),
+            closed: false,
+            handled: VecDeque::new(),
+        }
+    }
+
+    fn run<'scope>(
+        &mut self,
+        scope: &'scope Scope<'scope, 'env>,
+        start: Start,
+    ) -> Result<(), Error> {
+        self.write_frame(&Frame::settings(&[
+            (SETTINGS_MAX_CONCURRENT_STREAMS, MAX_CONCURRENT_STREAMS),
+            (SETTINGS_MAX_HEADER_LIST_SIZE, MAX_HEADER_LIST_SIZE),
+        ]));
+
+        // The connection window fits a whole body, as it's only refilled once the bodies are handed to the handlers
+        let connection_window = (self.handler.max_body_size as i64)
+            .saturating_add(1)
+            .min(MAX_WINDOW_SIZE);
+
+        if connection_window > self.receive_window {
+            let increment = (connection_window - self.receive_window) as u32;
+
+            self.write_frame(&Frame::window_update(0, increment));
+            self.receive_window = connection_window;
+        }
+
+        self.spawn_reader(scope)?;
+
+        let read_preface = match start {
+            Start::Preface(read_preface) => read_preface,
+            Start::Upgrade(request, settings) => {
+                self.settings.apply(&settings)?;
+                self.last_stream_id = 1;
+
+                let mut stream = StreamState::new(self.settings.initial_window_size);
+                stream.remote_closed = true;
+                stream.head = request.path.method == Method::HEAD;
+                self.streams.insert(1, stream);
+
+                self.spawn_handler(scope, 1, Ok(*request));
+                0
+            }
+        };
+
+        self.flush()?;
+        self.read_preface(read_preface)?;
+
+        loop {
+            self.send_responses()?;
+
+            if self.going_away && self.streams.is_empty() {
+                break;
+            }
+
+            let timed_out = self.streams.is_empty()
+                && self.idle_since.elapsed() >= self.handler.keep_alive_timeout;
+
+            if timed_out || self.handler.shutdown.is_shutting_down() {
+                self.go_away(ErrorCode::NoError);
+            }
+
+            self.flush()?;
+
+            match self.read_frame()? {
+                Incoming::Frame(frame) => self.handle_frame(scope, frame)?,
+                Incoming::Nothing => (),
+                Incoming::Closed => break,
+            }
+        }
+
+        self.flush()
+    }
+
+    /// Reads from the client in a thread of its own, so the connection waits for the frames of the client and the responses of the handlers at the same time. The bytes already buffered are taken first.
+    fn spawn_reader<'scope>(&mut self, scope: &'scope Scope<'scope, 'env>) -> Result<(), Error> {
+        let buffered = self.reader.buffer().len();
+        self.input.extend_from_slice(self.reader.buffer());
+        self.reader.consume(buffered);
+
+        let mut reader = self.reader.get_mut().try_clone_reader()?;
+        let event_sender = self.event_sender.clone();
+        let reading = self.reading.clone();
+
+        // The reads time out so the thread notices when the connection ends
+        self.reader
+            .get_ref()
+            .set_read_timeout(Some(IDLE_POLL_INTERVAL))?;
+
+        scope.spawn(move || {
+            let mut buffer = [0; DEFAULT_MAX_FRAME_SIZE as usize];
+
+            while reading.load(Ordering::Relaxed) {
+                let event = match reader.read(&mut buffer) {
+                    Ok(0) => Event::Closed(Ok(())),
+                    Ok(bytes_read) => Event::Input(buffer[..bytes_read].to_vec()),
+                    Err(err)
+                        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                    {
+                        continue
+                    }
+                    Err(err) => Event::Closed(Err(err)),
+                };
+
+                let closed = matches!(event, Event::Closed(_));
+
+                if event_sender.send(event).is_err() || closed {
+                    break;
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Reads the rest of the connection preface, given the number of bytes of it already read.
+    fn read_preface(&mut self, read_preface: usize) -> Result<(), Error> {
+        let expected_preface = &PREFACE[read_preface.min(PREFACE.len())..];
+
+        while self.input.len() < expected_preface.len() {
+            if self.closed {
+                return Err(Error::Http2Error(Http2Error::InvalidPreface));
+            }
+
+            self.receive_events()?;
+
+            if self.handler.shutdown.is_shutting_down()
+                || self.idle_since.elapsed() >= self.handler.keep_alive_timeout
+            {
+                self.going_away = true;
+                return Ok(());
+            }
+        }
+
+        if !self.input.starts_with(expected_preface) {
+            return Err(Error::Http2Error(Http2Error::InvalidPreface));
+        }
+
+        self.input.drain(..expected_preface.len());
+
+        Ok(())
+    }
+
+    /// Returns the next frame sent by the client, waiting a little for it if it isn't complete yet. Returns [Incoming::Nothing] if other events arrive in the meantime (e.g. a response).
+    fn read_frame(&mut self) -> Result<Incoming, Error> {
+        loop {
+            if let Some((frame, frame_size)) = Frame::parse(&self.input)? {
+                self.input.drain(..frame_size);
+                return Ok(Incoming::Frame(frame));
+            }
+
+            if self.closed {
+                return Ok(Incoming::Closed);
+            }
+
+            let input_length = self.input.len();
+            self.receive_events()?;
+
+            if self.input.len() == input_length && !self.closed {
+                return Ok(Incoming::Nothing);
+            }
+        }
+    }
+
+    /// Waits for the next events of the connection, up to the time between the checks of the idle connection, and takes the ones that follow it.
+    fn receive_events(&mut self) -> Result<(), Error> {
+        let mut next_event = self.events.recv_timeout(IDLE_POLL_INTERVAL).ok();
+
+        while let Some(event) = next_event {
+            match event {
+                Event::Input(input) => self.input.extend_from_slice(&input),
+                Event::Closed(result) => {
+                    self.closed = true;
+                    result?;
+                }
+                Event::Handled(handled_request) => self.handled.push_back(*handled_request),
+                // The streamed bodies are polled once the events are taken
+                Event::Chunk => (),
+            }
+
+            next_event = self.events.try_recv().ok();
+        }
+
+        Ok(())
+    }
+
+    fn handle_frame<'scope>(
+        &mut self,
+        scope: &'scope Scope<'scope, 'env>,
+        frame: Frame,
+    ) -> Result<(), Error> {
+        self.idle_since = Instant::now();
+
+        if let Some(mut header_block) = self.header_block.take() {
+            if frame.kind != FrameKind::Continuation || frame.stream_id != header_block.stream_id {
+                return Err(protocol_error("Header block interrupted by another frame"));
+            }
+
+            header_block.fragments.extend_from_slice(&frame.payload);
+            check_header_block_size(&header_block)?;
+
+            if !frame.has_flag(END_HEADERS) {
+                self.header_block = Some(header_block);
+                return Ok(());
+            }
+
+            return self.end_header_block(scope, header_block);
+        }
+
+        let is_connection_frame = matches!(
+            frame.kind,
+            FrameKind::Settings | FrameKind::Ping | FrameKind::GoAway
+        );
+
+        if is_connection_frame != (frame.stream_id == 0)
+            && !matches!(frame.kind, FrameKind::WindowUpdate | FrameKind::Unknown(_))
+        {
+            return Err(protocol_error("Frame sent on the wrong stream"));
+        }
+
+        match frame.kind {
+            FrameKind::Data => self.handle_data(scope, frame),
+            FrameKind::Headers => {
+                if frame.stream_id.is_multiple_of(2) {
+                    return Err(protocol_error("Clients must use odd stream identifiers"));
+                }
+
+                let header_block = HeaderBlock {
+                    stream_id: frame.stream_id,
+                    end_stream: frame.has_flag(END_STREAM),
+                    fragments: frame.content()?.to_vec(),
+                };
+
+                check_header_block_size(&header_block)?;
+
+                if !frame.has_flag(END_HEADERS) {
+                    self.header_block = Some(header_block);
+                    return Ok(());
+                }
+
+                self.end_header_block(scope, header_block)
+            }
+            FrameKind::Priority if frame.payload.len() != 5 => Err(frame_size_error(
+                "PRIORITY frame payload must be 5 bytes long",
+            )),
+            FrameKind::RstStream if frame.payload.len() != 4 => Err(frame_size_error(
+                "RST_STREAM frame payload must be 4 bytes long",
+            )),
+            FrameKind::RstStream if frame.stream_id > self.last_stream_id => {
+                Err(protocol_error("RST_STREAM frame sent on an idle stream"))
+            }
+            FrameKind::RstStream => {
+                self.remove_stream(frame.stream_id);
+                Ok(())
+            }
+            FrameKind::Settings => self.handle_settings(frame),
+            FrameKind::PushPromise => Err(protocol_error("Clients can't push streams")),
+            FrameKind::Ping if frame.payload.len() != 8 => {
+                Err(frame_size_error("PING frame payload must be 8 bytes long"))
+            }
+            FrameKind::Ping if !frame.has_flag(ACK) => {
+                self.write_frame(&Frame::ping_ack(frame.payload));
+                Ok(())
+            }
+            FrameKind::GoAway => {
+                self.going_away = true;
+                Ok(())
+            }
+            FrameKind::WindowUpdate => self.handle_window_update(frame),
+            FrameKind::Continuation => {
+                Err(protocol_error("CONTINUATION frame without a header block"))
+            }
+            FrameKind::Priority | FrameKind::Ping | FrameKind::Unknown(_) => Ok(()),
+        }
+    }
+
+    fn end_header_block<'scope>(
+        &mut self,
+        scope: &'scope Scope<'scope, 'env>,
+        header_block: HeaderBlock,
+    ) -> Result<(), Error> {
+        let stream_id = header_block.stream_id;
+
+        // Every header block is decoded, even the ones of refused streams, so the dynamic table stays in sync with the client.
+        let fields = self
+            .decoder
+            .decode(&header_block.fragments, MAX_HEADER_LIST_SIZE as usize)?;
+        let refused = self.going_away || self.streams.len() >= MAX_CONCURRENT_STREAMS as usize;
+
+        match self.streams.get_mut(&stream_id) {
+            Some(stream) if !stream.remote_closed => {
+                if !header_block.end_stream {
+                    return Err(protocol_error("Trailers must end the stream"));
+                }
+
+                stream.remote_closed = true;
+                self.dispatch(scope, stream_id);
+            }
+            Some(_) => return Err(Error::Http2Error(Http2Error::StreamClosed(stream_id))),
+            None if stream_id <= self.last_stream_id => {
+                return Err(Error::Http2Error(Http2Error::StreamClosed(stream_id)))
+            }
+            None if refused => {
+                self.write_frame(&Frame::rst_stream(stream_id, ErrorCode::RefusedStream));
+            }
+            None => {
+                self.last_stream_id = stream_id;
+                self.open_stream(scope, stream_id, fields, header_block.end_stream);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Opens a stream with the request of its header block, which is checked before its body is received. Malformed requests are reset, and the ones rejected by [ConnectionHandler::reject_request_head] are answered right away, without accepting their body.
+    fn open_stream<'scope>(
+        &mut self,
+        scope: &'scope Scope<'scope, 'env>,
+        stream_id: u32,
+        fields: Vec<(String, String)>,
+        end_stream: bool,
+    ) {
+        let request = build_request(fields);
+
+        if let Err(Error::Http2Error(_)) = request {
+            self.write_frame(&Frame::rst_stream(stream_id, ErrorCode::ProtocolError));
+            return;
+        }
+
+        let rejection = request
+            .as_ref()
+            .ok()
+            .and_then(|request| self.handler.reject_request_head(request));
+
+        let mut stream = StreamState::new(self.settings.initial_window_size);
+        stream.head = request
+            .as_ref()
+            .is_ok_and(|request| request.path.method == Method::HEAD);
+        stream.request = Some(request);
+        stream.remote_closed = end_stream;
+        self.streams.insert(stream_id, stream);
+
+        match rejection {
+            Some(response) => self.answer_early(stream_id, response),
+            None if end_stream => self.dispatch(scope, stream_id),
+            None => (),
+        }
+    }
+
+    fn handle_data<'scope>(
+        &mut self,
+        scope: &'scope Scope<'scope, 'env>,
+        frame: Frame,
+    ) -> Result<(), Error> {
+        let stream_id = frame.stream_id;
+        let flow_controlled_length = frame.payload.len() as u32;
+        let end_stream = frame.has_flag(END_STREAM);
+
+        if flow_controlled_length as i64 > self.receive_window {
+            return Err(Error::Http2Error(Http2Error::FlowControl(String::from(
+                "DATA frame bigger than the connection window",
+            ))));
+        }
+
+        self.receive_window -= flow_controlled_length as i64;
+
+        let Some(stream) = self.streams.get_mut(&stream_id) else {
+            if stream_id > self.last_stream_id {
+                return Err(protocol_error("DATA frame sent on an idle stream"));
+            }
+
+            self.release_connection_window(flow_controlled_length);
+            return Ok(());
+        };
+
+        let error_code = match stream.remote_closed {
+            true => Some(ErrorCode::StreamClosed),
+            false if flow_controlled_length as i64 > stream.receive_window => {
+                Some(ErrorCode::FlowControlError)
+            }
+            false => None,
+        };
+
+        if let Some(error_code) = error_code {
+            self.remove_stream(stream_id);
+            self.release_connection_window(flow_controlled_length);
+            self.write_frame(&Frame::rst_stream(stream_id, error_code));
+            return Ok(());
+        }
+
+        stream.receive_window -= flow_controlled_length as i64;
+        stream.remote_closed = end_stream;
+
+        if stream.discarding_body {
+            self.release_connection_window(flow_controlled_length);
+            return Ok(());
+        }
+
+        let content = frame.content()?;
+        let max_body_size = self.handler.max_body_size;
+        stream.connection_window_used += flow_controlled_length;
+
+        if stream.body.len() + content.len() > max_body_size {
+            self.answer_early(stream_id, Status::PayloadTooLarge.into_response());
+            return Ok(());
+        }
+
+        stream.body.extend_from_slice(content);
+
+        if end_stream {
+            self.dispatch(scope, stream_id);
+            return Ok(());
+        }
+
+        // The stream window is refilled as the body is received, up to one byte over the limit so bigger bodies are noticed
+        let receive_window = (max_body_size - stream.body.len())
+            .saturating_add(1)
+            .min(DEFAULT_WINDOW_SIZE as usize) as i64;
+
+        if receive_window > stream.receive_window {
+            let increment = (receive_window - stream.receive_window) as u32;
+
+            stream.receive_window = receive_window;
+            self.write_frame(&Frame::window_update(stream_id, increment));
+        }
+
+        self.refuse_stalled_stream();
+
+        Ok(())
+    }
+
+    /// Answers a request before the client finishes sending it, discarding the rest of its body. The stream is reset with NO_ERROR once the response is sent, so the client stops sending it.
+    fn answer_early(&mut self, stream_id: u32, response: Response) {
+        let Some(stream) = self.streams.get_mut(&stream_id) else {
+            return;
+        };
+
+        stream.discarding_body = true;
+        stream.request = None;
+        stream.body = Vec::new();
+
+        let connection_window_used = mem::take(&mut stream.connection_window_used);
+        self.release_connection_window(connection_window_used);
+
+        self.handled.push_back(HandledRequest {
+            stream_id,
+            response,
+            error: None,
+        });
+    }
+
+    /// Refuses the newest stream whose body is being received once the connection window is almost exhausted by incomplete bodies, as they are only handed to the handlers once complete and none of them could finish otherwise. Refused streams weren't handled, so the client can retry them.
+    fn refuse_stalled_stream(&mut self) {
+        if self.receive_window >= DEFAULT_MAX_FRAME_SIZE as i64 {
+            return;
+        }
+
+        let receiving_streams = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| stream.connection_window_used > 0)
+            .map(|(stream_id, _)| *stream_id);
+
+        if receiving_streams.clone().count() < 2 {
+            return;
+        }
+
+        if let Some(stream_id) = receiving_streams.max() {
+            self.remove_stream(stream_id);
+            self.write_frame(&Frame::rst_stream(stream_id, ErrorCode::RefusedStream));
+        }
+    }
+
+    fn handle_settings(&mut self, frame: Frame) -> Result<(), Error> {
+        if frame.has_flag(ACK) {
+            return match frame.payload.is_empty() {
+                true => Ok(()),
+                false => Err(frame_size_error("SETTINGS acknowledgement with a payload")),
+            };
+        }
+
+        let initial_window_size = self.settings.initial_window_size as i64;
+        self.settings.apply(&frame.payload)?;
+        let window_size_change = self.settings.initial_window_size as i64 - initial_window_size;
+
+        for stream in self.streams.values_mut() {
+            stream.send_window += window_size_change;
+
+            if stream.send_window > MAX_WINDOW_SIZE {
+                return Err(Error::Http2Error(Http2Error::FlowControl(String::from(
+                    "SETTINGS_INITIAL_WINDOW_SIZE overflows a stream window",
+                ))));
+            }
+        }
+
+        self.write_frame(&Frame::settings_ack());
+        Ok(())
+    }
+
+    fn handle_window_update(&mut self, frame: Frame) -> Result<(), Error> {
+        let increment = frame.window_size_increment()?;
+
+        if frame.stream_id == 0 {
+            if increment == 0 {
+                return Err(protocol_error("WINDOW_UPDATE frame with an increment of 0"));
+            }
+
+            self.send_window += increment as i64;
+
+            if self.send_window > MAX_WINDOW_SIZE {
+                return Err(Error::Http2Error(Http2Error::FlowControl(String::from(
+                    "WINDOW_UPDATE overflows the connection window",
+                ))));
+            }
+
+            return Ok(());
+        }
+
+        let error_code = match self.streams.get_mut(&frame.stream_id) {
+            Some(_) if increment == 0 => ErrorCode::ProtocolError,
+            Some(stream) => {
+                stream.send_window += increment as i64;
+
+                match stream.send_window > MAX_WINDOW_SIZE {
+                    true => ErrorCode::FlowControlError,
+                    false => return Ok(()),
+                }
+            }
+            None if frame.stream_id > self.last_stream_id => {
+                return Err(protocol_error("WINDOW_UPDATE frame sent on an idle stream"))
+            }
+            None => return Ok(()),
+        };
+
+        self.remove_stream(frame.stream_id);
+        self.write_frame(&Frame::rst_stream(frame.stream_id, error_code));
+        Ok(())
+    }
+
+    /// Hands the request of a stream the client finished sending, with its body, to a handler in its own thread.
+    fn dispatch<'scope>(&mut self, scope: &'scope Scope<'scope, 'env>, stream_id: u32) {
+        let Some(stream) = self.streams.get_mut(&stream_id) else {
+            return;
+        };
+
+        let Some(mut request) = stream.request.take() else {
+            return;
+        };
+
+        if let Ok(request) = &mut request {
+            request.set_body(mem::take(&mut stream.body));
+        }
+
+        let connection_window_used = mem::take(&mut stream.connection_window_used);
+
+        self.spawn_handler(scope, stream_id, request);
+        self.release_connection_window(connection_window_used);
+    }
+
+    fn spawn_handler<'scope>(
+        &self,
+        scope: &'scope Scope<'scope, 'env>,
+        stream_id: u32,
+        request: Result<Request, Error>,
+    ) {
+        let handler = self.handler;
+        let event_sender = self.event_sender.clone();
+
+        scope.spawn(move || {
+            let (response, error) = handler.respond(request);
+
+            let _ = event_sender.send(Event::Handled(Box::new(HandledRequest {
+                stream_id,
+                response,
+                error,
+            })));
+        });
+    }
+
+    /// Sends the headers of the responses that are ready and as much of their bodies as the flow control windows allow.
+    fn send_responses(&mut self) -> Result<(), Error> {
+        while let Some(handled_request) = self.handled.pop_front() {
+            let HandledRequest {
+                stream_id,
+                mut response,
+                error,
+            } = handled_request;
+
+            // The response already answers the error, so only the stream of the request fails
+            if let Some(error) = error {
+                self.handler.report_error(&error);
+            }
+
+            let Some(stream) = self.streams.get_mut(&stream_id) else {
+                continue;
+            };
+
+            let body_stream = response.body_stream.take().filter(|_| !stream.head);
+
+            if body_stream.is_none() {
+                response.pack();
+            }
+
+            let body = match stream.head {
+                true => Vec::new(),
+                false => response.get_body().unwrap_or_default(),
+            };
+            let ends_stream = body.is_empty() && body_stream.is_none();
+
+            let header_block = hpack::encode(&response_fields(&response));
+            let max_frame_size = self.settings.max_frame_size as usize;
+
+            let mut fragments = header_block.chunks(max_frame_size).peekable();
+            let mut kind = FrameKind::Headers;
+            let mut flags = if ends_stream { END_STREAM } else { 0 };
+
+            while let Some(fragment) = fragments.next() {
+                if fragments.peek().is_none() {
+                    flags |= END_HEADERS;
+                }
+
+                let frame = Frame::new(kind, flags, stream_id, fragment.to_vec());
+                self.output.extend_from_slice(&frame.to_binary());
+
+                kind = FrameKind::Continuation;
+                flags = 0;
+            }
+
+            match ends_stream {
+                true => self.finish_stream(stream_id),
+                false => {
+                    stream.pending_data = Some((body, 0));
+                    stream.body_stream = body_stream;
+                }
+            }
+        }
+
+        self.poll_body_streams();
+        self.send_pending_data()
+    }
+
+    /// Adds the chunks the streamed bodies produced to their pending data, ending the streamed bodies once the server shuts down.
+    fn poll_body_streams(&mut self) {
+        let shutting_down = self.handler.shutdown.is_shutting_down();
+
+        for stream in self.streams.values_mut() {
+            let Some(body_stream) = &stream.body_stream else {
+                continue;
+            };
+
+            body_stream.register_waker(&self.chunk_waker);
+
+            let (body, _) = stream.pending_data.get_or_insert_default();
+
+            let ended = loop {
+                match body_stream.poll(Duration::ZERO) {
+                    Chunk::Data(data) => body.extend_from_slice(&data),
+                    Chunk::Pending => break shutting_down,
+                    Chunk::End => break true,
+                }
+            };
+
+            if ended {
+                stream.body_stream = None;
+            }
+        }
+    }
+
+    /// Sends a DATA frame of each stream in turn until the bodies are sent or the flow control windows are exhausted.
+    fn send_pending_data(&mut self) -> Result<(), Error> {
+        let mut stream_ids = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| stream.pending_data.is_some())
+            .map(|(stream_id, _)| *stream_id)
+            .collect::<Vec<_>>();
+        stream_ids.sort_unstable();
+
+        let mut sent_data = true;
+
+        while sent_data {
+            sent_data = false;
+
+            for stream_id in &stream_ids {
+                let Some(stream) = self.streams.get_mut(stream_id) else {
+                    continue;
+                };
+
+                let streaming = stream.body_stream.is_some();
+
+                let Some((body, sent)) = &mut stream.pending_data else {
+                    continue;
+                };
+
+                let length = (body.len() - *sent)
+                    .min(self.settings.max_frame_size as usize)
+                    .min(self.send_window.max(0) as usize)
+                    .min(stream.send_window.max(0) as usize);
+
+                let end = *sent + length;
+                let ends_stream = end == body.len() && !streaming;
+
+                // Streamed bodies can end after all their data is sent, with an empty frame
+                if length == 0 && !ends_stream {
+                    continue;
+                }
+
+                let flags = if ends_stream { END_STREAM } else { 0 };
+
+                let frame = Frame::new(
+                    FrameKind::Data,
+                    flags,
+                    *stream_id,
+                    body[*sent..end].to_vec(),
+                );
+                self.output.extend_from_slice(&frame.to_binary());
+
+                *sent = end;
+                stream.send_window -= length as i64;
+                self.send_window -= length as i64;
+                sent_data = true;
+
+                if ends_stream {
+                    self.finish_stream(*stream_id);
+                } else if end == body.len() {
+                    body.clear();
+                    *sent = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes a stream whose response was sent. If the client didn't finish sending the request, the stream is reset with NO_ERROR so it stops sending it.
+    fn finish_stream(&mut self, stream_id: u32) {
+        if let Some(stream) = self.remove_stream(stream_id) {
+            if !stream.remote_closed {
+                self.write_frame(&Frame::rst_stream(stream_id, ErrorCode::NoError));
+            }
+        }
+    }
+
+    /// Removes a stream, giving back the connection window its body took.
+    fn remove_stream(&mut self, stream_id: u32) -> Option<StreamState> {
+        let stream = self.streams.remove(&stream_id)?;
+        self.release_connection_window(stream.connection_window_used);
+
+        Some(stream)
+    }
+
+    /// Refills the connection window with the bytes of the bodies that no longer take it.
+    fn release_connection_window(&mut self, length: u32) {
+        if length > 0 {
+            self.receive_window += length as i64;
+            self.write_frame(&Frame::window_update(0, length));
+        }
+    }
+
+    /// Tells the client no more streams are accepted, the ones already open are still answered.
+    fn go_away(&mut self, error_code: ErrorCode) {
+        if self.going_away && error_code == ErrorCode::NoError {
+            return;
+        }
+
+        self.going_away = true;
+        self.write_frame(&Frame::go_away(self.last_stream_id, error_code));
+    }
+
+    /// Queues a frame, frames are sent together when the output is flushed.
+    fn write_frame(&mut self, frame: &Frame) {
+        self.output.extend_from_slice(&frame.to_binary());
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if !self.output.is_empty() {
+            self.reader.get_mut().write_all(&self.output)?;
+            self.output.clear();
+        }
+
+        Ok(())
+    }
+}
+
+impl<S> Drop for Connection<'_, '_, S>
+where
+    S: Stream,
+{
+    fn drop(&mut self) {
+        self.reading.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Closes the connection if a header block grows over the size limit, before the rest of it is received.
+fn check_header_block_size(header_block: &HeaderBlock) -> Result<(), Error> {
+    match header_block.fragments.len() > MAX_HEADER_LIST_SIZE as usize {
+        true => Err(Error::Http2Error(Http2Error::HeadersTooLarge(
+            MAX_HEADER_LIST_SIZE as usize,
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Builds a request from the header fields of a stream, whose pseudo-header fields replace the request line. Header names are capitalized (e.g. `content-type` becomes `Content-Type`) like the ones usually sent through HTTP/1.1, and the `:authority` field becomes the `Host` header.
+fn build_request(fields: Vec<(String, String)>) -> Result<Request, Error> {
+    let mut method = None;
+    let mut path = None;
+    let mut scheme = None;
+    let mut authority = None;
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for (name, value) in fields {
+        if let Some(pseudo_header) = name.strip_prefix(':') {
+            let field = match pseudo_header {
+                "method" => &mut method,
+                "path" => &mut path,
+                "scheme" => &mut scheme,
+                "authority" => &mut authority,
+                _ => return Err(malformed_request("Unknown pseudo-header field")),
+            };
+
+            if !headers.is_empty() || field.replace(value).is_some() {
+                return Err(malformed_request(
+                    "Misplaced or repeated pseudo-header field",
+                ));
+            }
+
+            continue;
+        }
+
+        if name.bytes().any(|byte| byte.is_ascii_uppercase())
+            || CONNECTION_HEADERS.contains(&name.as_str())
+        {
+            return Err(malformed_request("Invalid header field name"));
+        }
+
+        let separator = if name == "cookie" { "; " } else { ", " };
+
+        match headers
+            .iter_mut()
+            .find(|(header_name, _)| *header_name == name)
+        {
+            Some((_, header_value)) => {
+                header_value.push_str(separator);
+                header_value.push_str(&value);
+            }
+            None => headers.push((name, value)),
+        }
+    }
+
+    let (Some(method), Some(path), Some(_)) = (method, path, scheme) else {
+        return Err(malformed_request("Missing pseudo-header field"));
+    };
+
+    let mut request = Request::from_target(&method, &path, "HTTP/2")?;
+
+    if let Some(authority) = authority {
+        if !headers.iter().any(|(name, _)| name == "host") {
+            headers.push((String::from("host"), authority));
+        }
+    }
+
+    for (name, value) in headers {
+        request.add_header(&capitalize_header_name(&name), &value);
+    }
+
+    request.parse_cookies()?;
+
+    Ok(request)
+}
+
+/// Returns the header fields of a response: the `:status` pseudo-header field followed by its headers, whose names are lowercase.
+fn response_fields(response: &Response) -> Vec<(String, String)> {
+    let mut fields = vec![(String::from(":status"), response.status.code().to_string())];
+
+    fields.extend(
+        response
+            .header_fields()
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), String::from(value)))
+            .filter(|(name, _)| !CONNECTION_HEADERS.contains(&name.as_str())),
+    );
+
+    fields
+}
+
+fn capitalize_header_name(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn malformed_request(reason: &str) -> Error {
+    Error::Http2Error(Http2Error::MalformedRequest(String::from(reason)))
+}
+
+fn protocol_error(reason: &str) -> Error {
+    Error::Http2Error(Http2Error::Protocol(String::from(reason)))
+}
+
+fn frame_size_error(reason: &str) -> Error {
+    Error::Http2Error(Http2Error::FrameSize(String::from(reason)))
+}
+
+/// Errors that can occur in HTTP/2 connections. Except for malformed requests, which only reset their stream, these errors close the connection.
+#[derive(Debug, thiserror::Error)]
+pub enum Http2Error {
+    /// The connection didn't start with the HTTP/2 connection preface.
+    #[error("Invalid HTTP/2 connection preface")]
+    InvalidPreface,
+
+    /// The client didn't follow the HTTP/2 protocol.
+    #[error("HTTP/2 protocol error: {0}")]
+    Protocol(String),
+
+    /// A frame has an invalid size.
+    #[error("HTTP/2 frame size error: {0}")]
+    FrameSize(String),
+
+    /// The client made a flow control window bigger than allowed.
+    #[error("HTTP/2 flow control error: {0}")]
+    FlowControl(String),
+
+    /// A header block couldn't be decoded.
+    #[error("HPACK decoding error: {0}")]
+    Compression(String),
+
+    /// The header fields of a request are bigger than the limit in bytes.
+    #[error("HTTP/2 header fields larger than {0} bytes")]
+    HeadersTooLarge(usize),
+
+    /// The client sent frames on a stream it had already closed.
+    #[error("HTTP/2 stream {0} is closed")]
+    StreamClosed(u32),
+
+    /// The header fields of a request are invalid (e.g. missing the `:method` pseudo-header field).
+    #[error("Malformed HTTP/2 request: {0}")]
+    MalformedRequest(String),
+}
+
+impl Http2Error {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Http2Error::InvalidPreface
+            | Http2Error::Protocol(_)
+            | Http2Error::MalformedRequest(_) => ErrorCode::ProtocolError,
+            Http2Error::FrameSize(_) => ErrorCode::FrameSizeError,
+            Http2Error::FlowControl(_) => ErrorCode::FlowControlError,
+            Http2Error::Compression(_) | Http2Error::HeadersTooLarge(_) => {
+                ErrorCode::CompressionError
+            }
+            Http2Error::StreamClosed(_) => ErrorCode::StreamClosed,
+        }
+    }
+}
@@ -2,30 +2,105 @@
 
 //! Simple HTTP server crate that allows you to create a server and attach a router to it. The router will handle the requests and return the responses. The server listens on a given port and handles the requests using the attached router.
 
+/// Contains the [cookie_jar::CookieJar] struct, used to sign and encrypt cookies, and [cookie_jar::CookieJarError] error handling enum.
+pub mod cookie_jar;
+
 /// Contains the [package::Package] trait and its implementations for the [request::Request] and [response::Response] structs.
 pub mod package;
 
-/// Contains the [request::Request] struct, its implementations and [request::RequestError] error handling enum.
+/// Contains the [http2::Http2Error] error handling enum, for the errors of the HTTP/2 connections. HTTP/2 is used by the clients that negotiate it through ALPN (`h2`), start the connection with the HTTP/2 preface or upgrade an HTTP/1.1 connection (`h2c`).
+pub mod http2;
+
+/// Contains the [request::Request] struct, its implementations, the [request::RequestParser] incremental parser of HTTP/1.x requests and [request::RequestError] error handling enum.
 pub mod request;
 
-/// Contains the [response::Response] struct, its implementations and [response::Response] error handling enum.
+/// Contains the [response::Response] struct, its implementations and [response::ResponseError] error handling enum.
 pub mod response;
 
 /// Contains the [Router] struct, its implementations and [router::RouterError] error handling enum.
 pub mod router;
 
+/// Contains the [tls::TlsConfig] struct, used to serve HTTPS, and [tls::TlsError] error handling enum. Requires the `tls` feature.
+#[cfg(feature = "tls")]
+pub mod tls;
+
+/// Contains the [session::SessionMiddleware] struct, the [session::SessionStore] trait and its implementations and [session::SessionError] error handling enum.
+pub mod session;
+
+/// Contains the [websocket::WebSocket] struct, used by the handlers of [Router::handle_websocket] to exchange messages with the client, and [websocket::WebSocketError] error handling enum.
+pub mod websocket;
+
+#[cfg(feature = "async")]
+mod async_server;
+mod connection;
+mod listener;
+mod shutdown;
+mod url;
+mod virtual_host;
+
 use std::{
-    io::{self, prelude::*, BufReader},
-    net::{TcpListener, TcpStream},
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::Duration,
 };
 
+#[cfg(unix)]
+use std::path::Path;
+
+use connection::ConnectionHandler;
+#[cfg(unix)]
+use listener::UnixSocketListener;
+use listener::{ListenerKind, Stream, TcpSocketListener};
+use request::Request;
+use response::{IntoResponse, Response};
 use router::Router;
+use virtual_host::HostPattern;
+
+#[cfg(unix)]
+pub use listener::UnixSocketOptions;
+pub use shutdown::ShutdownHandle;
+
+/// Size limit of the body of a request, unless [HttpServer::set_max_body_size] is used.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 8 * 1024 * 1024;
 
 /// Struct that represents an HTTP server, it listens on a given port and handles requests from a given router. If no router is attached, it will return an error when calling the handle_connection() method.
-#[derive(Debug)]
+///
+/// Several hostnames can be served by the same server attaching a router per host with [HttpServer::attach_host_router]. Each connection is handled in its own thread and kept alive between requests, and the server can be stopped gracefully with a [ShutdownHandle].
 pub struct HttpServer {
-    listener: TcpListener,
+    listeners: Vec<TcpSocketListener>,
+    #[cfg(unix)]
+    unix_listeners: Vec<UnixSocketListener>,
     router: Option<Router>,
+    host_routers: Vec<(HostPattern, Router)>,
+    error_hook: Option<ErrorHook>,
+    continue_hook: Option<ContinueHook>,
+    shutdown: ShutdownHandle,
+    shutdown_timeout: Duration,
+    keep_alive_timeout: Duration,
+    max_body_size: usize,
+}
+
+pub(crate) type ErrorHook = Arc<dyn Fn(&Error) + Send + Sync>;
+pub(crate) type ContinueHook = Arc<dyn Fn(&Request) -> Option<Response> + Send + Sync>;
+
+impl std::fmt::Debug for HttpServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpServer")
+            .field("listeners", &self.listeners)
+            .field("unix_listeners", &self.unix_listener_paths())
+            .field("router", &self.router)
+            .field("host_routers", &self.host_routers)
+            .field("error_hook", &self.error_hook.is_some())
+            .field("continue_hook", &self.continue_hook.is_some())
+            .field("shutdown", &self.shutdown)
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .field("keep_alive_timeout", &self.keep_alive_timeout)
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
 }
 
 /// Possible errors that can occur when using the crate.
@@ -46,6 +121,31 @@ pub enum Error {
     /// Checkout [request::RequestError] for more details
     #[error(transparent)]
     RequestError(#[from] request::RequestError),
+
+    /// Checkout [response::ResponseError] for more details
+    #[error(transparent)]
+    ResponseError(#[from] response::ResponseError),
+
+    /// Checkout [cookie_jar::CookieJarError] for more details
+    #[error(transparent)]
+    CookieJarError(#[from] cookie_jar::CookieJarError),
+
+    /// Checkout [session::SessionError] for more details
+    #[error(transparent)]
+    SessionError(#[from] session::SessionError),
+
+    /// Checkout [http2::Http2Error] for more details
+    #[error(transparent)]
+    Http2Error(#[from] http2::Http2Error),
+
+    /// Checkout [websocket::WebSocketError] for more details
+    #[error(transparent)]
+    WebSocketError(#[from] websocket::WebSocketError),
+
+    /// Checkout [tls::TlsError] for more details
+    #[cfg(feature = "tls")]
+    #[error(transparent)]
+    TlsError(#[from] tls::TlsError),
 }
 
 /// Possible errors that can occur when using the [HttpServer] struct.
@@ -67,6 +167,22 @@ pub enum ServerError {
     /// ```
     #[error("HttpServer has no router attached")]
     NoRouterAttached,
+
+    /// The host pattern given to [HttpServer::attach_host_router] is invalid.
+    #[error("Invalid host pattern: {0}")]
+    InvalidHostPattern(String),
+
+    /// No router is attached for the host of the request and there's no default router, check [HttpServer::attach_router].
+    #[error("No router attached for host {0}")]
+    UnknownHost(String),
+
+    /// The server has no TCP listener, so it has no socket address. Happens when calling [HttpServer::local_addr] on a server that only listens on Unix domain sockets.
+    #[error("HttpServer has no TCP listener")]
+    NoTcpListener,
+
+    /// The path given to [HttpServer::add_unix_listener] exists and isn't a socket, so it can't be replaced.
+    #[error("Path isn't a socket: {0}")]
+    NotASocket(PathBuf),
 }
 
 /// # Example
@@ -92,81 +208,471 @@ pub enum ServerError {
 ///
 /// ```
 impl HttpServer {
-    /// Creates a new instance of the [HttpServer] struct that listens on the given port. Can return an io error if the port is already in use
+    /// Creates a new instance of the [HttpServer] struct that listens on the given port of every IPv4 interface. Can return an io error if the port is already in use
     pub fn new(port: u16) -> Result<Self, Error> {
-        let listener = TcpListener::bind(format!("0.0.0.0:{}", port))?;
+        Self::bind(("0.0.0.0", port))
+    }
+
+    /// Creates a new instance of the [HttpServer] struct that listens on the given address (e.g. `"127.0.0.1:8080"`, `"[::1]:8080"` or `("localhost", 0)` for a port chosen by the operating system, check [HttpServer::local_addr]). Can return an io error if the address is already in use
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::HttpServer;
+    ///
+    /// let server = HttpServer::bind("127.0.0.1:0").unwrap();
+    ///
+    /// let address = server.local_addr().unwrap();
+    /// assert!(address.ip().is_loopback());
+    /// assert_ne!(address.port(), 0);
+    /// ```
+    pub fn bind<A>(address: A) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let mut server = Self::with_listeners(Vec::new());
+        server.add_listener(address)?;
+
+        Ok(server)
+    }
+
+    /// Creates a new instance of the [HttpServer] struct that serves HTTPS on the given address, check [HttpServer::add_tls_listener].
+    #[cfg(feature = "tls")]
+    pub fn bind_tls<A>(address: A, config: &tls::TlsConfig) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let mut server = Self::with_listeners(Vec::new());
+        server.add_tls_listener(address, config)?;
+
+        Ok(server)
+    }
+
+    /// Creates a new instance of the [HttpServer] struct that listens on a Unix domain socket at the given path, check [HttpServer::add_unix_listener].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use servidor_http::{HttpServer, UnixSocketOptions};
+    ///
+    /// let mut options = UnixSocketOptions::new();
+    /// options.set_permissions(0o660);
+    ///
+    /// let server = HttpServer::bind_unix("/run/servidor_http.sock", &options).unwrap();
+    /// ```
+    #[cfg(unix)]
+    pub fn bind_unix<P>(path: P, options: &UnixSocketOptions) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut server = Self::with_listeners(Vec::new());
+        server.add_unix_listener(path, options)?;
 
-        let server = HttpServer {
-            listener,
-            router: None,
-        };
         Ok(server)
     }
 
+    fn with_listeners(listeners: Vec<TcpSocketListener>) -> Self {
+        HttpServer {
+            listeners,
+            #[cfg(unix)]
+            unix_listeners: Vec::new(),
+            router: None,
+            host_routers: Vec::new(),
+            error_hook: None,
+            continue_hook: None,
+            shutdown: ShutdownHandle::new(),
+            shutdown_timeout: Duration::from_secs(30),
+            keep_alive_timeout: Duration::from_secs(5),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Adds another address for the server to listen on, so it can serve several addresses at once (e.g. an IPv4 and an IPv6 one). Returns the address the listener is bound to.
+    pub fn add_listener<A>(&mut self, address: A) -> Result<SocketAddr, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        self.add_tcp_listener(address, ListenerKind::Http)
+    }
+
+    /// Adds an address for the server to serve HTTPS on, using the certificates of the config. Returns the address the listener is bound to.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use servidor_http::{HttpServer, tls::TlsConfig};
+    ///
+    /// let config = TlsConfig::from_pem_files("cert.pem", "key.pem").unwrap();
+    ///
+    /// let mut server = HttpServer::bind_tls("0.0.0.0:443", &config).unwrap();
+    /// server.add_https_redirect_listener("0.0.0.0:80", 443).unwrap();
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn add_tls_listener<A>(
+        &mut self,
+        address: A,
+        config: &tls::TlsConfig,
+    ) -> Result<SocketAddr, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        self.add_tcp_listener(address, ListenerKind::Https(config.server_config()?))
+    }
+
+    /// Adds an address for the server to listen on with plain HTTP, redirecting every request to the same URL using HTTPS on the given port with a 308 Permanent Redirect status. Returns the address the listener is bound to.
+    #[cfg(feature = "tls")]
+    pub fn add_https_redirect_listener<A>(
+        &mut self,
+        address: A,
+        https_port: u16,
+    ) -> Result<SocketAddr, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        self.add_tcp_listener(address, ListenerKind::HttpsRedirect(https_port))
+    }
+
+    fn add_tcp_listener<A>(&mut self, address: A, kind: ListenerKind) -> Result<SocketAddr, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+
+        self.listeners.push(TcpSocketListener { listener, kind });
+
+        Ok(local_addr)
+    }
+
+    /// Adds a Unix domain socket for the server to listen on (e.g. for a reverse proxy running in the same machine). The socket file is removed when the server is dropped.
+    #[cfg(unix)]
+    pub fn add_unix_listener<P>(
+        &mut self,
+        path: P,
+        options: &UnixSocketOptions,
+    ) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let listener = UnixSocketListener::bind(path.as_ref(), options)?;
+        self.unix_listeners.push(listener);
+
+        Ok(())
+    }
+
+    fn unix_listener_paths(&self) -> Vec<PathBuf> {
+        #[cfg(unix)]
+        return self
+            .unix_listeners
+            .iter()
+            .map(|listener| listener.path.clone())
+            .collect();
+
+        #[cfg(not(unix))]
+        Vec::new()
+    }
+
+    /// Returns the address the server was bound to, including the port chosen by the operating system when binding to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        let listener = self
+            .listeners
+            .first()
+            .ok_or(Error::ServerError(ServerError::NoTcpListener))?;
+
+        Ok(listener.listener.local_addr()?)
+    }
+
+    /// Returns the addresses of every TCP listener of the server, in the order they were added.
+    pub fn local_addrs(&self) -> Result<Vec<SocketAddr>, Error> {
+        self.listeners
+            .iter()
+            .map(|listener| Ok(listener.listener.local_addr()?))
+            .collect()
+    }
+
     /// Attaches a router to the server, the router will handle the requests and return the response to the client.
+    ///
+    /// When routers are attached for specific hosts, this router handles the requests whose host doesn't match any of them.
     pub fn attach_router(&mut self, router: Router) {
         self.router = Some(router);
     }
 
-    /// Listens for incoming connections and handles them using the attached router. If no router is attached, it will return an error.
+    /// Attaches a router that handles the requests whose `Host` header matches the pattern, replacing the router previously attached for the same pattern.
+    ///
+    /// Patterns can be exact hosts (`example.com`) or wildcards matching any subdomain (`*.example.com`, which doesn't match `example.com` itself), and are case insensitive. Patterns with a port (`example.com:8080`) only match requests to that port, the port of requests without one is assumed to be 80. When several patterns match, exact hosts win over wildcards and longer wildcards win over shorter ones.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use servidor_http::{HttpServer, router::{Route, Router}, request::Method};
+    ///
+    /// let mut server = HttpServer::new(8080).unwrap();
+    ///
+    /// let mut api_router = Router::new(String::from("/"));
+    /// api_router.handle_route(Route::new(Method::GET, "/"), |_, _| "API");
+    ///
+    /// let mut tenant_router = Router::new(String::from("/"));
+    /// tenant_router.handle_route(Route::new(Method::GET, "/"), |req, _| {
+    ///     format!("Tenant {}", req.host().unwrap_or_default())
+    /// });
+    ///
+    /// server.attach_host_router("api.example.com", api_router).unwrap();
+    /// server.attach_host_router("*.example.com", tenant_router).unwrap();
+    /// ```
+    pub fn attach_host_router(&mut self, pattern: &str, router: Router) -> Result<(), Error> {
+        let pattern = HostPattern::try_from(pattern)?;
+
+        self.host_routers
+            .retain(|(attached_pattern, _)| attached_pattern != &pattern);
+        self.host_routers.push((pattern, router));
+
+        Ok(())
+    }
+
+    /// Sets a hook that receives the errors that occur while handling a connection and weren't handled by the router (e.g. clients resetting the connection or failed TLS handshakes), which are ignored otherwise.
+    pub fn set_error_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.error_hook = Some(Arc::new(hook));
+    }
+
+    /// Sets a hook that checks the head of every request before its body is read, rejecting the request with the response it returns (e.g. 413 Payload Too Large for bodies over a limit). Rejected HTTP/1.x requests have their connection closed without reading the body, and rejected HTTP/2 requests are answered as soon as their headers arrive, resetting their stream so the client stops sending the body.
+    ///
+    /// Clients sending `Expect: 100-continue` wait for a 100 Continue before sending the body, which is only sent once the hook accepts the request. Requests with any other expectation are rejected with 417 Expectation Failed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::{response::Status, HttpServer};
+    ///
+    /// let mut server = HttpServer::new(8080).unwrap();
+    ///
+    /// server.set_continue_hook(|req| match req.content_length() {
+    ///     Some(length) if length > 1024 * 1024 => Err(Status::PayloadTooLarge),
+    ///     _ => Ok(()),
+    /// });
+    /// ```
+    pub fn set_continue_hook<F, R>(&mut self, hook: F)
+    where
+        F: Fn(&Request) -> Result<(), R> + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.continue_hook = Some(Arc::new(move |req| {
+            hook(req).err().map(IntoResponse::into_response)
+        }));
+    }
+
+    /// Returns a handle that stops the server when [ShutdownHandle::shutdown] is called, it can be cloned and sent to other threads (e.g. a signal handler).
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Sets how long the server waits, once shut down, for the requests being handled to finish. Defaults to 30 seconds.
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+    }
+
+    /// Sets how long a connection is kept open waiting for the next request of the client. Defaults to 5 seconds.
+    pub fn set_keep_alive_timeout(&mut self, timeout: Duration) {
+        self.keep_alive_timeout = timeout;
+    }
+
+    /// Sets the size limit of the body of a request in bytes, bigger requests are rejected with 413 Payload Too Large before their body is read, or once it grows over the limit if its length isn't announced (HTTP/2 requests without `Content-Length`). Defaults to [DEFAULT_MAX_BODY_SIZE].
+    pub fn set_max_body_size(&mut self, max_body_size: usize) {
+        self.max_body_size = max_body_size;
+    }
+
+    /// Listens for incoming connections and handles them using the attached router, each connection in its own thread. If no router is attached, it will return an error.
     ///
-    /// **This method will enter a loop to check if any client has connected and will not return until an unhandled error appears**
+    /// Errors not handled by the router are answered with a generic error page and given to the error hook if there's one (check [HttpServer::set_error_hook]), they close HTTP/1.x connections and only fail their stream in HTTP/2 connections. Panicking handlers are answered with a 500 Internal Server Error.
+    ///
+    /// Once the server is shut down (check [HttpServer::shutdown_handle]) it stops accepting connections and returns when the requests being handled finish or the shutdown timeout expires, whatever happens first. Connections still open by then are abandoned.
+    ///
+    /// **This method blocks accepting connections and will not return until a listener fails or the server is shut down**
     pub fn listen(&self) -> Result<(), Error> {
-        if self.router.is_none() {
+        if self.router.is_none() && self.host_routers.is_empty() {
             return Err(Error::ServerError(ServerError::NoRouterAttached));
         }
 
-        for stream_result in self.listener.incoming() {
-            let stream = stream_result?;
+        let (handler, listener_handlers) = self.connection_handlers();
 
-            let router = self.router.clone().unwrap();
-            Self::handle_connection(stream, router)?;
+        for listener in &self.listeners {
+            listener.listener.set_nonblocking(false)?;
         }
 
-        Ok(())
-    }
+        #[cfg(unix)]
+        for unix_listener in &self.unix_listeners {
+            unix_listener.listener.set_nonblocking(false)?;
+        }
 
-    fn handle_connection(mut stream: TcpStream, router: Router) -> Result<(), Error> {
-        let mut buf_reader = BufReader::new(&mut stream);
+        let result = thread::scope(|scope| {
+            let mut accept_threads = Vec::new();
 
-        let mut request_bytes: Vec<u8> = Vec::new();
-        let mut body_size = 0;
+            for (listener, handler) in self.listeners.iter().zip(&listener_handlers) {
+                accept_threads.push(scope.spawn(|| self.accept_tcp_connections(listener, handler)));
+            }
 
-        loop {
-            let mut line_str = String::new();
-            let bytes_read = buf_reader.read_line(&mut line_str)?;
+            #[cfg(unix)]
+            for unix_listener in &self.unix_listeners {
+                let handler = &handler;
+                accept_threads
+                    .push(scope.spawn(|| self.accept_unix_connections(unix_listener, handler)));
+            }
+
+            // The listeners block until a client connects, so they are woken up connecting to them
+            scope.spawn(|| {
+                self.shutdown.wait_for_shutdown();
 
-            request_bytes.write_all(line_str.as_bytes())?;
+                for listener in &self.listeners {
+                    listener.wake();
+                }
 
-            if body_size == 0 && line_str.to_lowercase().contains("content-length") {
-                let mut parts = line_str.split(':');
-                let value = parts.nth(1).unwrap();
+                #[cfg(unix)]
+                for unix_listener in &self.unix_listeners {
+                    unix_listener.wake();
+                }
+            });
 
-                body_size = value.trim().parse::<usize>().unwrap();
+            accept_threads
+                .into_iter()
+                .map(|accept_thread| accept_thread.join().unwrap_or(Ok(())))
+                .fold(Ok(()), Result::and)
+        });
+
+        self.shutdown.wait_for_connections(self.shutdown_timeout);
+
+        result
+    }
+
+    /// Accepts the connections of a TCP listener until the server shuts down. If the listener fails, the server is shut down and the error returned.
+    fn accept_tcp_connections(
+        &self,
+        listener: &TcpSocketListener,
+        handler: &Arc<ConnectionHandler>,
+    ) -> Result<(), Error> {
+        loop {
+            let accepted = listener.listener.accept();
+
+            if self.shutdown.is_shutting_down() {
+                return Ok(());
             }
 
-            if line_str.trim().is_empty() || bytes_read == 0 {
-                break;
+            match accepted {
+                Ok((stream, _)) => match &listener.kind {
+                    #[cfg(feature = "tls")]
+                    ListenerKind::Https(config) => match tls::accept(config, stream) {
+                        Ok(stream) => self.spawn_connection(handler, stream),
+                        Err(error) => handler.report_error(&error),
+                    },
+                    _ => self.spawn_connection(handler, stream),
+                },
+                Err(err) if is_connection_error(&err) => (),
+                Err(err) => {
+                    self.shutdown.shutdown();
+                    return Err(Error::Io(err));
+                }
             }
         }
+    }
 
-        let mut body_bytes_buffer = vec![0; body_size];
-        buf_reader.read_exact(&mut body_bytes_buffer)?;
-
-        request_bytes.write_all(&body_bytes_buffer)?;
+    /// Accepts the connections of a Unix domain socket listener until the server shuts down. If the listener fails, the server is shut down and the error returned.
+    #[cfg(unix)]
+    fn accept_unix_connections(
+        &self,
+        unix_listener: &UnixSocketListener,
+        handler: &Arc<ConnectionHandler>,
+    ) -> Result<(), Error> {
+        loop {
+            let accepted = unix_listener.listener.accept();
 
-        let request = request::Request::try_from(request_bytes)?;
+            if self.shutdown.is_shutting_down() {
+                return Ok(());
+            }
 
-        let mut resp = router.handle_request(request)?;
+            match accepted {
+                Ok((stream, _)) => self.spawn_connection(handler, stream),
+                Err(err) if is_connection_error(&err) => (),
+                Err(err) => {
+                    self.shutdown.shutdown();
+                    return Err(Error::Io(err));
+                }
+            }
+        }
+    }
 
-        resp.pack();
+    /// Returns the handler shared by the connections, and the handler of each TCP listener, as the HTTPS listeners use port 443 by default and the HTTPS redirect listeners use their own router.
+    fn connection_handlers(&self) -> (Arc<ConnectionHandler>, Vec<Arc<ConnectionHandler>>) {
+        let handler = Arc::new(ConnectionHandler {
+            router: self.router.clone(),
+            host_routers: self.host_routers.clone(),
+            default_port: 80,
+            keep_alive_timeout: self.keep_alive_timeout,
+            max_body_size: self.max_body_size,
+            continue_hook: self.continue_hook.clone(),
+            error_hook: self.error_hook.clone(),
+            shutdown: self.shutdown.clone(),
+        });
+
+        let listener_handlers = self
+            .listeners
+            .iter()
+            .map(|listener| match listener.kind {
+                #[cfg(feature = "tls")]
+                ListenerKind::Https(_) => Arc::new(ConnectionHandler {
+                    router: self.router.clone(),
+                    host_routers: self.host_routers.clone(),
+                    default_port: 443,
+                    keep_alive_timeout: self.keep_alive_timeout,
+                    max_body_size: self.max_body_size,
+                    continue_hook: self.continue_hook.clone(),
+                    error_hook: self.error_hook.clone(),
+                    shutdown: self.shutdown.clone(),
+                }),
+                #[cfg(feature = "tls")]
+                ListenerKind::HttpsRedirect(https_port) => Arc::new(ConnectionHandler {
+                    router: Some(tls::https_redirect_router(https_port)),
+                    host_routers: Vec::new(),
+                    default_port: 80,
+                    keep_alive_timeout: self.keep_alive_timeout,
+                    max_body_size: self.max_body_size,
+                    continue_hook: self.continue_hook.clone(),
+                    error_hook: self.error_hook.clone(),
+                    shutdown: self.shutdown.clone(),
+                }),
+                _ => handler.clone(),
+            })
+            .collect();
+
+        (handler, listener_handlers)
+    }
 
-        stream.write_all(&resp.to_binary())?;
+    /// Handles the connection in its own thread, giving the errors that aren't handled by the router to the error hook.
+    fn spawn_connection<S>(&self, handler: &Arc<ConnectionHandler>, stream: S)
+    where
+        S: Stream,
+    {
+        let handler = handler.clone();
+        let connection_guard = self.shutdown.connection_guard();
+
+        thread::spawn(move || {
+            if let Err(error) = handler.handle_connection(stream) {
+                handler.report_error(&error);
+            }
 
-        Ok(())
+            drop(connection_guard);
+        });
     }
 }
 
+/// Returns true if accepting a connection failed because of the connection itself (e.g. the client reset it before it was accepted) rather than the listener.
+fn is_connection_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::Interrupted
+    )
+}
+
 /// Trait that represents a binary representation of a struct. It should return a Vec<u8> with the binary representation of the struct. Used to send responses to the client.
 pub trait BinaryRepresentation {
     /// Returns a Vec<u8> with the binary representation of the struct.
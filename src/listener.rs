@@ -0,0 +1,249 @@
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener, TcpStream},
+    time::Duration,
+};
+
+/// Time given to the connection that wakes a listener blocked accepting connections.
+const WAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+#[cfg(feature = "tls")]
+use rustls::ServerConfig;
+
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+#[cfg(unix)]
+use crate::{Error, ServerError};
+
+/// Stream a connection is read from and written to, implemented by the TCP and Unix domain socket streams.
+pub(crate) trait Stream: Read + Write + Send + 'static {
+    /// Sets the timeout of the reads, `None` blocks until there's data to read.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Returns a reader of the same connection, so it can be read from one thread while it's written from another. The timeout of its reads is set with [Stream::set_read_timeout], and the bytes it reads are no longer read through the stream.
+    fn try_clone_reader(&mut self) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Closes the stream once the last response is sent (e.g. sending the TLS close notification).
+    fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Completes the handshake of the encryption of the stream (e.g. the TLS handshake), if any, before anything is read from it.
+    fn handshake(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns true if the stream is encrypted (e.g. with TLS).
+    fn is_encrypted(&self) -> bool {
+        false
+    }
+
+    /// Returns the protocol the client and the server agreed on through ALPN, if any.
+    fn alpn_protocol(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl<S> Stream for Box<S>
+where
+    S: Stream + ?Sized,
+{
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    fn try_clone_reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        (**self).try_clone_reader()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        (**self).close()
+    }
+
+    fn handshake(&mut self) -> io::Result<()> {
+        (**self).handshake()
+    }
+
+    fn is_encrypted(&self) -> bool {
+        (**self).is_encrypted()
+    }
+
+    fn alpn_protocol(&self) -> Option<&[u8]> {
+        (**self).alpn_protocol()
+    }
+}
+
+/// Connection whose requests are read through a buffer, used by the protocols that take over the connection after an HTTP/1.1 response.
+pub(crate) type BufferedStream = BufReader<Box<dyn Stream>>;
+
+/// TCP listener of an [crate::HttpServer] and the way its connections are handled.
+#[derive(Debug)]
+pub(crate) struct TcpSocketListener {
+    pub(crate) listener: TcpListener,
+    pub(crate) kind: ListenerKind,
+}
+
+impl TcpSocketListener {
+    /// Connects to the listener, so the thread blocked accepting its connections returns.
+    pub(crate) fn wake(&self) {
+        let Ok(mut address) = self.listener.local_addr() else {
+            return;
+        };
+
+        // Listeners bound to every interface are reached through the loopback one
+        match address.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => address.set_ip(Ipv4Addr::LOCALHOST.into()),
+            IpAddr::V6(ip) if ip.is_unspecified() => address.set_ip(Ipv6Addr::LOCALHOST.into()),
+            _ => (),
+        }
+
+        let _ = TcpStream::connect_timeout(&address, WAKE_TIMEOUT);
+    }
+}
+
+/// How the connections of a TCP listener are handled.
+#[derive(Debug, Clone)]
+pub(crate) enum ListenerKind {
+    /// Plain HTTP connections.
+    Http,
+
+    /// HTTPS connections using the given TLS config.
+    #[cfg(feature = "tls")]
+    Https(Arc<ServerConfig>),
+
+    /// Plain HTTP connections whose requests are redirected to HTTPS on the given port.
+    #[cfg(feature = "tls")]
+    HttpsRedirect(u16),
+}
+
+impl Stream for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone_reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn try_clone_reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+}
+
+/// Options of a Unix domain socket listener, check [crate::HttpServer::add_unix_listener].
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixSocketOptions {
+    permissions: Option<u32>,
+    remove_stale: bool,
+}
+
+#[cfg(unix)]
+impl Default for UnixSocketOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(unix)]
+impl UnixSocketOptions {
+    /// Generates the default options: the socket keeps the permissions given by the umask of the process and stale sockets are removed.
+    pub fn new() -> Self {
+        UnixSocketOptions {
+            permissions: None,
+            remove_stale: true,
+        }
+    }
+
+    /// Sets the permissions of the socket file (e.g. `0o660` so only the owner and the group, such as the one of a reverse proxy, can connect).
+    pub fn set_permissions(&mut self, mode: u32) {
+        self.permissions = Some(mode);
+    }
+
+    /// Sets whether a socket file left behind by a server that is no longer running is removed before binding. Sockets that still accept connections are never removed.
+    pub fn set_remove_stale(&mut self, remove_stale: bool) {
+        self.remove_stale = remove_stale;
+    }
+}
+
+/// Unix domain socket listener that removes its socket file when dropped.
+#[cfg(unix)]
+#[derive(Debug)]
+pub(crate) struct UnixSocketListener {
+    pub(crate) listener: UnixListener,
+    pub(crate) path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketListener {
+    pub(crate) fn bind(path: &Path, options: &UnixSocketOptions) -> Result<Self, Error> {
+        if options.remove_stale && is_stale_socket(path)? {
+            fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+
+        let listener = UnixSocketListener {
+            listener,
+            path: PathBuf::from(path),
+        };
+
+        if let Some(mode) = options.permissions {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+
+        Ok(listener)
+    }
+
+    /// Connects to the listener, so the thread blocked accepting its connections returns.
+    pub(crate) fn wake(&self) {
+        let _ = UnixStream::connect(&self.path);
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Returns true if the path is a socket nobody is listening on. Paths that exist but aren't sockets are rejected, so they are never removed.
+#[cfg(unix)]
+fn is_stale_socket(path: &Path) -> Result<bool, Error> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(Error::Io(err)),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(Error::ServerError(ServerError::NotASocket(PathBuf::from(
+            path,
+        ))));
+    }
+
+    match UnixStream::connect(path) {
+        Ok(_) => Ok(false),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => Ok(true),
+        Err(err) => Err(Error::Io(err)),
+    }
+}
@@ -1,6 +1,7 @@
 use servidor_http::{
     package::Package,
     request::{self},
+    response::Response,
     router::{self, Router},
     Error, HttpServer,
 };
@@ -20,19 +21,21 @@ fn main() {
     );
 
     router.handle_route(
-        router::Route::new(request::Method::GET, "/test"),
-        |_, mut res| {
+        router::Route::new(request::Method::GET, "/test").with_name("test_page"),
+        |_, mut res| -> Result<Response, Error> {
             let path = "tests/res/test.html";
-            res.send_file(path).unwrap();
-            res
+            res.send_file(path)?;
+            Ok(res)
         },
     );
 
+    let test_url = router.url_for("test_page", &[]).unwrap();
+
     router.handle_route(
         router::Route::new(request::Method::GET, "/redirect"),
-        |_, mut res| {
-            res.redirect("/test");
-            res
+        move |_, mut res| -> Result<Response, Error> {
+            res.redirect(&test_url)?;
+            Ok(res)
         },
     );
 
@@ -49,7 +52,7 @@ fn main() {
 
             res.set_body(format!("<h1>Cookie: {}</h1>", cookie).into_bytes());
             res.add_header("Content-Type", "text/html");
-            res.set_session_cookie("cookie", "got cookie");
+            res.set_session_cookie("cookie", "got_cookie").unwrap();
             res
         },
     );
@@ -72,16 +75,25 @@ fn main() {
 
     router.handle_router(sub_router);
 
+    println!("Serving routes:\n{}", router.route_table());
+
     server.attach_router(router);
-    let mut error_message_reg: Option<router::Route> = None;
-    loop {
-        if let Err(Error::RouterError(router::RouterError::RouteNotFound(msg))) = server.listen() {
-            if Some(&msg) != error_message_reg.as_ref() {
-                println!("Route not found: {:?}", msg);
-                error_message_reg = Some(msg);
-            }
-        } else {
-            error_message_reg = None;
-        }
+
+    #[cfg(feature = "ctrlc")]
+    {
+        let shutdown_handle = server.shutdown_handle();
+        ctrlc::set_handler(move || {
+            println!("Shutting down...");
+            shutdown_handle.shutdown();
+        })
+        .unwrap();
     }
+
+    server.set_error_hook(|error| {
+        if let Error::RouterError(router::RouterError::RouteNotFound(route)) = error {
+            println!("Route not found: {:?}", route);
+        }
+    });
+
+    server.listen().unwrap();
 }
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use super::RequestError;
+use crate::cookie_jar::CookieJar;
 
 /// Contains a list of cookies.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -22,7 +23,7 @@ impl TryFrom<&str> for CookieList {
                 break;
             }
 
-            let mut splited_cookie_pair = cookie_pair.split('=');
+            let mut splited_cookie_pair = cookie_pair.splitn(2, '=');
 
             let cookie_key = match splited_cookie_pair.next() {
                 Some(key) => key,
@@ -83,4 +84,14 @@ impl CookieList {
     pub fn contains(&self, name: &str) -> bool {
         self.cookies.contains_key(name)
     }
+
+    /// Returns the value of a cookie signed with [crate::response::Response::set_signed_cookie]. Returns None if the cookie doesn't exist or has been tampered with.
+    pub fn get_signed(&self, jar: &CookieJar, name: &str) -> Option<String> {
+        self.get(name).and_then(|value| jar.verify(name, value))
+    }
+
+    /// Returns the value of a cookie encrypted with [crate::response::Response::set_private_cookie]. Returns None if the cookie doesn't exist or can't be decrypted.
+    pub fn get_private(&self, jar: &CookieJar, name: &str) -> Option<String> {
+        self.get(name).and_then(|value| jar.decrypt(name, value))
+    }
 }
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 /// Contains all the supported request methods.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
@@ -7,6 +9,10 @@ pub enum Method {
     PUT,
     DELETE,
     HEAD,
+    PATCH,
+    OPTIONS,
+    TRACE,
+    CONNECT,
     Other(String),
 }
 
@@ -42,5 +48,38 @@ gen_try_from_and_from!(
     "POST" => Method::POST,
     "PUT" => Method::PUT,
     "DELETE" => Method::DELETE,
-    "HEAD" => Method::HEAD
+    "HEAD" => Method::HEAD,
+    "PATCH" => Method::PATCH,
+    "OPTIONS" => Method::OPTIONS,
+    "TRACE" => Method::TRACE,
+    "CONNECT" => Method::CONNECT
 );
+
+impl Method {
+    /// Returns true if the string is a valid method name (a non empty HTTP token), even if the method is not one of the standard ones.
+    pub fn is_valid(method_str: &str) -> bool {
+        !method_str.is_empty()
+            && method_str
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let method_str = match self {
+            Method::GET => "GET",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::DELETE => "DELETE",
+            Method::HEAD => "HEAD",
+            Method::PATCH => "PATCH",
+            Method::OPTIONS => "OPTIONS",
+            Method::TRACE => "TRACE",
+            Method::CONNECT => "CONNECT",
+            Method::Other(method) => method.as_str(),
+        };
+
+        write!(f, "{}", method_str)
+    }
+}
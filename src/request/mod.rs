@@ -2,15 +2,19 @@ use std::collections::HashMap;
 
 use crate::package;
 use crate::router::Route;
+use crate::session::Session;
 
 pub use crate::package::Package;
 
 mod cookie_list;
 mod method;
+mod parser;
 mod query;
 
 pub use cookie_list::CookieList;
 pub use method::Method;
+pub(crate) use parser::is_token;
+pub use parser::{ParseStatus, RequestHead, RequestParser, DEFAULT_MAX_HEAD_SIZE};
 pub use query::Query;
 
 /// Represents a request made by a client.
@@ -25,6 +29,12 @@ pub struct Request {
     /// The cookies of the request.
     pub cookies: CookieList,
 
+    pub(crate) session: Option<Session>,
+    pub(crate) mount_path: String,
+    pub(crate) params: HashMap<String, String>,
+
+    http_version: String,
+    target: String,
     headers: HashMap<String, String>,
     body: Option<Vec<u8>>,
 }
@@ -34,13 +44,16 @@ package::generate_package_getters_setters!(Request[Vec<u8>]);
 impl Request {
     /// Generates a new request method, with the given method and path.
     pub fn new(method: Method, path: &str, query: Option<Query>) -> Self {
-        let path = Route::new(method, path);
-
         Request {
-            path,
+            path: Route::new(method, path),
             headers: HashMap::new(),
             query,
             cookies: CookieList::new(),
+            session: None,
+            mount_path: String::new(),
+            params: HashMap::new(),
+            http_version: String::from("HTTP/1.1"),
+            target: String::from(path),
             body: None,
         }
     }
@@ -53,123 +66,129 @@ impl Request {
         }
     }
 
-    fn parse_header_str(header_string: &str) -> Result<Request, crate::Error> {
-        let mut lines = header_string.lines();
-
-        let mut request = match lines.next() {
-            Some(request_line) => {
-                let mut request_line_parts = request_line.split_whitespace();
-
-                let request_method_string = match request_line_parts.next() {
-                    Some(method) => method,
-                    None => {
-                        return Err(crate::Error::RequestError(RequestError::InvalidRequest(
-                            String::from(header_string),
-                        )))
-                    }
-                };
-
-                let request_method = match Method::try_from(request_method_string) {
-                    Ok(method) => method,
-                    Err(_) => {
-                        return Err(crate::Error::RequestError(
-                            RequestError::InvalidRequestMethod(String::from(request_method_string)),
-                        ))
-                    }
-                };
-
-                let request_path_with_query = match request_line_parts.next() {
-                    Some(url) => url,
-                    None => return Err(crate::Error::RequestError(RequestError::NoUrlFound)),
-                };
-
-                let (request_path, query) = match request_path_with_query.contains('?') {
-                    true => {
-                        let mut url_and_query = request_path_with_query.splitn(2, '?');
-
-                        let request_path = match url_and_query.next() {
-                            Some(url) => url,
-                            None => {
-                                return Err(crate::Error::RequestError(RequestError::NoUrlFound))
-                            }
-                        };
-
-                        let query_string = match url_and_query.next() {
-                            Some(query) => query,
-                            None => {
-                                return Err(crate::Error::RequestError(RequestError::QueryError(
-                                    request_path_with_query.to_string(),
-                                )))
-                            }
-                        };
-
-                        let query = Query::try_from(query_string)?;
-
-                        (request_path, Some(query))
-                    }
-                    false => (request_path_with_query, None),
-                };
-
-                let http_version = match request_line_parts.next() {
-                    Some(version) => version,
-                    None => {
-                        return Err(crate::Error::RequestError(RequestError::InvalidRequest(
-                            String::from(header_string),
-                        )))
-                    }
-                };
-
-                if !http_version.contains("HTTP/") {
-                    return Err(crate::Error::RequestError(
-                        RequestError::HttpVersionNotSupported(String::from(http_version)),
-                    ));
-                }
-
-                Request::new(request_method, request_path, query)
+    /// Returns the prefix of the path consumed by the routers the request went through (e.g. `/api/v2` for a request handled by a router mounted at `/v2` inside a router mounted at `/api`).
+    pub fn mount_path(&self) -> &str {
+        &self.mount_path
+    }
+
+    /// Returns the part of the path relative to the router handling the request (e.g. `/users` for a request to `/api/v2/users` handled by a router mounted at `/api/v2`). Check [Request::path] for the original path.
+    pub fn remaining_path(&self) -> &str {
+        match self.path.path.get(self.mount_path.len()..) {
+            Some("") | None => "/",
+            Some(remaining_path) => remaining_path,
+        }
+    }
+
+    /// Returns the value of a parameter of the route (e.g. the value of `id` for a request to `/users/42` handled by the `/users/:id` route).
+    pub fn param(&self, name: &str) -> Option<&String> {
+        self.params.get(name)
+    }
+
+    /// Returns the target of the request exactly as the client sent it, the path and the query (e.g. `/users?name=%C3%A9`). Unlike [Request::path], dot segments and escapes are kept.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the query of the request exactly as the client sent it, without the leading `?` (e.g. `name=%C3%A9&tag=a&tag=b`). Unlike [Request::query], repeated keys and escapes are kept.
+    pub fn query_string(&self) -> Option<&str> {
+        self.target
+            .split_once('?')
+            .map(|(_, query_string)| query_string)
+    }
+
+    /// Returns the HTTP version of the request (e.g. `HTTP/1.1`).
+    pub fn http_version(&self) -> &str {
+        &self.http_version
+    }
+
+    /// Returns the value of the `Host` header, including the port if the client sent it.
+    pub fn host(&self) -> Option<&str> {
+        self.find_header("Host")
+    }
+
+    /// Returns the length of the body announced in the `Content-Length` header, if the header is valid.
+    pub fn content_length(&self) -> Option<usize> {
+        self.find_header("Content-Length")
+            .and_then(|length| length.trim().parse().ok())
+    }
+
+    /// Returns the id of the last event the client received, sent in the `Last-Event-ID` header when it reconnects to a [crate::response::EventStream].
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.find_header("Last-Event-ID")
+    }
+
+    /// Returns the value of the header with the given name, ignoring its case.
+    pub(crate) fn find_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the session of the request. Only available when a [crate::session::SessionMiddleware] is attached to the router.
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    /// Parses a request held in memory, whose head ends with the data if it doesn't end with an empty line. Returns the request and the data after its head.
+    fn parse_in_memory(mut data: Vec<u8>) -> Result<(Request, Vec<u8>), crate::Error> {
+        let data_length = data.len();
+        let mut parser = RequestParser::new();
+
+        let head_length = match parser.parse(&mut data)? {
+            ParseStatus::Complete(head_length) => head_length,
+            ParseStatus::Partial => {
+                data.extend_from_slice(b"\n\n");
+                parser.parse(&mut data)?;
+                data_length
             }
+        };
+
+        let request = match parser.head(&data) {
+            Some(head) => Request::try_from(head)?,
             None => {
                 return Err(crate::Error::RequestError(RequestError::InvalidRequest(
-                    String::from(header_string),
-                )));
+                    String::from_utf8_lossy(&data[..data_length]).into_owned(),
+                )))
             }
         };
 
-        for header in lines.by_ref() {
-            if header.is_empty() {
-                break;
-            }
+        data.truncate(data_length);
 
-            let mut header_parts = header.splitn(2, ':');
+        Ok((request, data.split_off(head_length)))
+    }
 
-            let header_key = match header_parts.next() {
-                Some(key) => key,
-                None => {
-                    return Err(crate::Error::RequestError(RequestError::InvalidHeader(
-                        String::from(header),
-                    )))
-                }
-            };
+    /// Generates a request from the parts of its request line: the method, the target (the path and the query) and the HTTP version. Used as well by HTTP/2, which sends them as pseudo-header fields.
+    pub(crate) fn from_target(
+        method: &str,
+        target: &str,
+        http_version: &str,
+    ) -> Result<Request, crate::Error> {
+        if !Method::is_valid(method) {
+            return Err(crate::Error::RequestError(
+                RequestError::InvalidRequestMethod(String::from(method)),
+            ));
+        }
 
-            let header_value = match header_parts.next() {
-                Some(value) => value.trim(),
-                None => {
-                    return Err(crate::Error::RequestError(RequestError::InvalidHeader(
-                        String::from(header),
-                    )))
-                }
-            };
+        let (path, query) = match target.split_once('?') {
+            Some((path, query_string)) => (path, Some(Query::try_from(query_string)?)),
+            None => (target, None),
+        };
 
-            request.add_header(header_key, header_value);
-        }
+        let mut request = Request::new(Method::from(method), path, query);
+        request.http_version = String::from(http_version);
+        request.target = String::from(target);
 
-        let _a = request.get_header_list().get("Cookie");
-        if let Some(cookies) = request.get_header_list().get("Cookie") {
-            let cookie_list = CookieList::try_from(cookies.as_str())?;
+        Ok(request)
+    }
 
-            request.cookies = cookie_list;
+    /// Parses the cookies of the `Cookie` header, once every header is added.
+    pub(crate) fn parse_cookies(&mut self) -> Result<(), crate::Error> {
+        if let Some(cookies) = self.find_header("Cookie") {
+            self.cookies = CookieList::try_from(cookies)?;
         }
 
-        Ok(request)
+        Ok(())
     }
 }
 
@@ -179,45 +198,61 @@ impl From<Route> for Request {
     }
 }
 
-impl TryFrom<&str> for Request {
+/// Generates a request from its head, checking the HTTP version is supported and there's at most one `Host` header. Header values are decoded as UTF-8, or as Latin-1 if they aren't valid UTF-8, so no byte is lost.
+impl TryFrom<RequestHead<'_>> for Request {
     type Error = crate::Error;
 
-    fn try_from(req: &str) -> Result<Self, Self::Error> {
-        let request = Request::parse_header_str(req)?;
+    fn try_from(head: RequestHead<'_>) -> Result<Self, Self::Error> {
+        if !matches!(head.http_version(), "HTTP/1.0" | "HTTP/1.1") {
+            return Err(crate::Error::RequestError(
+                RequestError::HttpVersionNotSupported(String::from(head.http_version())),
+            ));
+        }
+
+        let host_headers = head
+            .headers()
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Host"))
+            .count();
+
+        if host_headers > 1 {
+            return Err(crate::Error::RequestError(RequestError::DuplicateHost));
+        }
+
+        let mut request = Request::from_target(head.method(), head.target(), head.http_version())?;
+
+        for (name, value) in head.headers() {
+            let value = match std::str::from_utf8(value) {
+                Ok(value) => String::from(value),
+                Err(_) => value.iter().map(|&byte| char::from(byte)).collect(),
+            };
+
+            request.headers.insert(String::from(*name), value);
+        }
+
+        request.parse_cookies()?;
 
         Ok(request)
     }
 }
 
-macro_rules! split_sequence {
-    ($sequence:expr, $($separator:expr),*) => {{
-        let mut header = Vec::new();
-        let mut body = $sequence.to_vec();
-
-        $(
-            if let Some(pos) = body
-                .windows($separator.len())
-                .position(|window| window == $separator)
-            {
-                let (header_reference, body_reference) = body.split_at(pos);
-                header = header_reference.to_vec();
-                body = body_reference[$separator.len()..].to_vec();
-            }
-        )*
+/// Parses the head of a request, the data after the empty line that ends it is ignored.
+impl TryFrom<&str> for Request {
+    type Error = crate::Error;
 
-        (header, body)
-    }};
+    fn try_from(req: &str) -> Result<Self, Self::Error> {
+        let (request, _) = Request::parse_in_memory(req.as_bytes().to_vec())?;
+
+        Ok(request)
+    }
 }
 
+/// Parses a whole request, the data after the empty line that ends the head is the body.
 impl TryFrom<Vec<u8>> for Request {
     type Error = crate::Error;
 
     fn try_from(binary_data: Vec<u8>) -> Result<Self, Self::Error> {
-        let (header, body) = split_sequence!(binary_data, b"\r\n\r\n", b"\n\n");
-
-        let header_string = String::from_utf8_lossy(&header);
-
-        let mut request = Request::parse_header_str(header_string.as_ref())?;
+        let (mut request, body) = Request::parse_in_memory(binary_data)?;
 
         request.set_body(body);
 
@@ -233,7 +268,7 @@ pub enum RequestError {
     #[error("Invalid request\nRaw data:\n{0}")]
     InvalidRequest(String),
 
-    /// The request method is invalid. Check [crate::request::RequestMethod] for valid methods.
+    /// The request method is not a valid HTTP token. Check [crate::request::Method] for the standard methods.
     #[error("Invalid request method: {0}")]
     InvalidRequestMethod(String),
 
@@ -249,6 +284,22 @@ pub enum RequestError {
     #[error("Invalid header")]
     InvalidHeader(String),
 
+    /// The `Content-Length` header isn't a decimal number.
+    #[error("Invalid Content-Length: {0}")]
+    InvalidContentLength(String),
+
+    /// The request has several `Content-Length` headers, so the length of its body is ambiguous.
+    #[error("Duplicate Content-Length header")]
+    DuplicateContentLength,
+
+    /// The request has a `Transfer-Encoding` header, bodies sent with transfer codings (e.g. `chunked`) aren't supported.
+    #[error("Transfer-Encoding not supported: {0}")]
+    UnsupportedTransferEncoding(String),
+
+    /// The head of the request (the request line and the headers) is bigger than the limit in bytes.
+    #[error("Request head larger than {0} bytes")]
+    HeadTooLarge(usize),
+
     /// Error while getting the query from the request
     #[error("Error parsing query: {0}")]
     QueryError(String),
@@ -256,4 +307,12 @@ pub enum RequestError {
     /// Error while parsing cookies
     #[error("Error parsing cookies: {0}")]
     CookieError(String),
+
+    /// HTTP/1.1 requests must have a `Host` header.
+    #[error("Missing Host header")]
+    MissingHost,
+
+    /// The request has several `Host` headers, so the host it's meant for is ambiguous.
+    #[error("Duplicate Host header")]
+    DuplicateHost,
 }
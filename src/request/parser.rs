@@ -0,0 +1,440 @@
+use std::ops::Range;
+
+use super::RequestError;
+use crate::Error;
+
+/// Size limit of the head of a request (the request line and the headers), unless [RequestParser::set_max_head_size] is used.
+pub const DEFAULT_MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// Incremental parser of the head of HTTP/1.x requests. The bytes can be given as they are received, the parser resumes where the previous call stopped instead of parsing the whole buffer again, and the parsed head borrows the buffer instead of copying it.
+///
+/// Lines can end with CRLF or a bare LF, while bare CRs are rejected. Headers continued in the next line (obs-fold) are joined replacing the line break with spaces, in the buffer itself. Requests with several `Content-Length` headers or a `Transfer-Encoding` header are rejected, as their body can't be read reliably.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::request::{ParseStatus, RequestParser};
+///
+/// let mut parser = RequestParser::new();
+/// let mut buffer = b"GET /index.html HTTP/1.1\r\nHost: exa".to_vec();
+///
+/// assert_eq!(parser.parse(&mut buffer).unwrap(), ParseStatus::Partial);
+///
+/// buffer.extend_from_slice(b"mple.com\r\nContent-Length: 5\r\n\r\nHello");
+/// assert_eq!(parser.parse(&mut buffer).unwrap(), ParseStatus::Complete(66));
+///
+/// let head = parser.head(&buffer).unwrap();
+/// assert_eq!(head.method(), "GET");
+/// assert_eq!(head.target(), "/index.html");
+/// assert_eq!(head.header("host"), Some(&b"example.com"[..]));
+/// assert_eq!(head.content_length(), Some(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequestParser {
+    state: State,
+    position: usize,
+    line_start: usize,
+    line_end: usize,
+    request_line: Option<RequestLine>,
+    header_line: Option<Range<usize>>,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    content_length: Option<usize>,
+    max_head_size: usize,
+}
+
+/// Result of giving bytes to a [RequestParser].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseStatus {
+    /// The head is complete, its length in bytes is given. The bytes after it belong to the body or to the next request.
+    Complete(usize),
+
+    /// More bytes are needed to complete the head.
+    Partial,
+}
+
+/// Head of a request parsed by a [RequestParser], borrowing the buffer it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestHead<'b> {
+    bytes: &'b [u8],
+    method: &'b str,
+    target: &'b str,
+    http_version: &'b str,
+    headers: Vec<(&'b str, &'b [u8])>,
+    content_length: Option<usize>,
+}
+
+/// Part of the head the parser is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Skipping the empty lines received before the request line.
+    Start,
+    StartCr,
+    RequestLine,
+    RequestLineCr,
+    /// At the first byte of a line after the request line: a header, the continuation of the previous one or the empty line that ends the head.
+    LineStart,
+    Header,
+    HeaderCr,
+    EmptyLineCr,
+    Done,
+}
+
+/// Position of the parts of the request line in the buffer.
+#[derive(Debug, Clone)]
+struct RequestLine {
+    method: Range<usize>,
+    target: Range<usize>,
+    http_version: Range<usize>,
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestParser {
+    /// Generates a parser for the head of a request.
+    pub fn new() -> Self {
+        RequestParser {
+            state: State::Start,
+            position: 0,
+            line_start: 0,
+            line_end: 0,
+            request_line: None,
+            header_line: None,
+            headers: Vec::new(),
+            content_length: None,
+            max_head_size: DEFAULT_MAX_HEAD_SIZE,
+        }
+    }
+
+    /// Sets the size limit of the head, heads that don't end before it are rejected with [RequestError::HeadTooLarge].
+    pub fn set_max_head_size(&mut self, max_head_size: usize) {
+        self.max_head_size = max_head_size;
+    }
+
+    /// Parses the bytes received so far. The buffer must start with the bytes given in the previous calls, as only the bytes after them are parsed.
+    pub fn parse(&mut self, buffer: &mut [u8]) -> Result<ParseStatus, Error> {
+        let end = buffer.len().min(self.max_head_size);
+
+        while self.state != State::Done && self.position < end {
+            let byte = buffer[self.position];
+
+            self.state = match (self.state, byte) {
+                (State::Start, b'\r') => State::StartCr,
+                (State::Start | State::StartCr, b'\n') => State::Start,
+                (State::StartCr, _) => return Err(invalid_request(&buffer[..=self.position])),
+                (State::Start, _) => {
+                    self.line_start = self.position;
+                    State::RequestLine
+                }
+
+                (State::RequestLine, b'\r') => {
+                    self.line_end = self.position;
+                    State::RequestLineCr
+                }
+                (State::RequestLine, b'\n') => {
+                    self.line_end = self.position;
+                    self.parse_request_line(buffer)?;
+                    State::LineStart
+                }
+                (State::RequestLine, _) => State::RequestLine,
+                (State::RequestLineCr, b'\n') => {
+                    self.parse_request_line(buffer)?;
+                    State::LineStart
+                }
+                (State::RequestLineCr, _) => {
+                    return Err(invalid_request(&buffer[self.line_start..=self.position]))
+                }
+
+                (State::LineStart, b'\r') => State::EmptyLineCr,
+                (State::LineStart, b'\n') => {
+                    self.finish_header(buffer)?;
+                    State::Done
+                }
+                // Obsolete line folding, the line break is replaced with spaces so the value stays contiguous
+                (State::LineStart, b' ' | b'\t') => match self.header_line {
+                    Some(_) => {
+                        buffer[self.line_end..self.position].fill(b' ');
+                        State::Header
+                    }
+                    None => return Err(invalid_header(&buffer[self.position..end])),
+                },
+                (State::LineStart, _) => {
+                    self.finish_header(buffer)?;
+                    self.line_start = self.position;
+                    State::Header
+                }
+
+                (State::Header, b'\r') => {
+                    self.line_end = self.position;
+                    State::HeaderCr
+                }
+                (State::Header, b'\n') => {
+                    self.line_end = self.position;
+                    self.header_line = Some(self.line_start..self.line_end);
+                    State::LineStart
+                }
+                (State::Header, _) => State::Header,
+                (State::HeaderCr, b'\n') => {
+                    self.header_line = Some(self.line_start..self.line_end);
+                    State::LineStart
+                }
+                (State::HeaderCr, _) => {
+                    return Err(invalid_header(&buffer[self.line_start..=self.position]))
+                }
+
+                (State::EmptyLineCr, b'\n') => {
+                    self.finish_header(buffer)?;
+                    State::Done
+                }
+                (State::EmptyLineCr, _) => {
+                    return Err(invalid_header(&buffer[self.position - 1..=self.position]))
+                }
+
+                (State::Done, _) => State::Done,
+            };
+
+            self.position += 1;
+        }
+
+        match self.state {
+            State::Done => Ok(ParseStatus::Complete(self.position)),
+            _ if self.position >= self.max_head_size => Err(Error::RequestError(
+                RequestError::HeadTooLarge(self.max_head_size),
+            )),
+            _ => Ok(ParseStatus::Partial),
+        }
+    }
+
+    /// Returns the head once it's complete, borrowing the buffer given to [RequestParser::parse].
+    pub fn head<'b>(&self, buffer: &'b [u8]) -> Option<RequestHead<'b>> {
+        let request_line = self.request_line.as_ref()?;
+
+        if self.state != State::Done || buffer.len() < self.position {
+            return None;
+        }
+
+        // The request line and the names of the headers are checked to be ASCII while parsing
+        let as_str = |range: &Range<usize>| std::str::from_utf8(&buffer[range.clone()]).ok();
+
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| Some((as_str(name)?, &buffer[value.clone()])))
+            .collect::<Option<_>>()?;
+
+        Some(RequestHead {
+            bytes: &buffer[..self.position],
+            method: as_str(&request_line.method)?,
+            target: as_str(&request_line.target)?,
+            http_version: as_str(&request_line.http_version)?,
+            headers,
+            content_length: self.content_length,
+        })
+    }
+
+    /// Checks the request line once it's received: `method SP request-target SP HTTP-version`.
+    fn parse_request_line(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let line = &buffer[self.line_start..self.line_end];
+
+        let method_end = line
+            .iter()
+            .position(|&byte| byte == b' ')
+            .filter(|&method_end| method_end > 0)
+            .ok_or_else(|| invalid_request(line))?;
+
+        let method = &line[..method_end];
+
+        if !method.iter().all(|&byte| is_token(byte)) {
+            return Err(Error::RequestError(RequestError::InvalidRequestMethod(
+                String::from_utf8_lossy(method).into_owned(),
+            )));
+        }
+
+        let target_start = method_end + 1;
+        let target_end = line[target_start..]
+            .iter()
+            .position(|&byte| byte == b' ')
+            .map(|target_length| target_start + target_length)
+            .ok_or_else(|| invalid_request(line))?;
+
+        let target = &line[target_start..target_end];
+
+        if target.is_empty() {
+            return Err(Error::RequestError(RequestError::NoUrlFound));
+        }
+
+        if !target.iter().all(u8::is_ascii_graphic) {
+            return Err(invalid_request(line));
+        }
+
+        let http_version = &line[target_end + 1..];
+
+        if !is_http_version(http_version) {
+            return Err(invalid_request(line));
+        }
+
+        let offset =
+            |range: Range<usize>| self.line_start + range.start..self.line_start + range.end;
+
+        self.request_line = Some(RequestLine {
+            method: offset(0..method_end),
+            target: offset(target_start..target_end),
+            http_version: offset(target_end + 1..line.len()),
+        });
+
+        Ok(())
+    }
+
+    /// Checks the header received before the current line, once it's known the current line doesn't continue it: `field-name ":" OWS field-value OWS`.
+    fn finish_header(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        let Some(line_range) = self.header_line.take() else {
+            return Ok(());
+        };
+
+        let line = &buffer[line_range.clone()];
+
+        // Whitespace between the name and the colon isn't allowed, as the name would be interpreted differently by other servers
+        let name_end = line
+            .iter()
+            .position(|&byte| byte == b':')
+            .filter(|&name_end| name_end > 0 && line[..name_end].iter().all(|&byte| is_token(byte)))
+            .ok_or_else(|| invalid_header(line))?;
+
+        let is_whitespace = |byte: &u8| matches!(byte, b' ' | b'\t');
+        let value_start = line[name_end + 1..]
+            .iter()
+            .position(|byte| !is_whitespace(byte))
+            .map_or(line.len(), |offset| name_end + 1 + offset);
+        let value_end = line
+            .iter()
+            .rposition(|byte| !is_whitespace(byte))
+            .map_or(value_start, |last| (last + 1).max(value_start));
+
+        let value = &line[value_start..value_end];
+
+        if !value.iter().all(|&byte| is_field_value(byte)) {
+            return Err(invalid_header(line));
+        }
+
+        let name = &line[..name_end];
+
+        if name.eq_ignore_ascii_case(b"Content-Length") {
+            if self.content_length.is_some() {
+                return Err(Error::RequestError(RequestError::DuplicateContentLength));
+            }
+
+            self.content_length = Some(parse_content_length(value)?);
+        }
+
+        if name.eq_ignore_ascii_case(b"Transfer-Encoding") {
+            return Err(Error::RequestError(
+                RequestError::UnsupportedTransferEncoding(
+                    String::from_utf8_lossy(value).into_owned(),
+                ),
+            ));
+        }
+
+        self.headers.push((
+            line_range.start..line_range.start + name_end,
+            line_range.start + value_start..line_range.start + value_end,
+        ));
+
+        Ok(())
+    }
+}
+
+impl<'b> RequestHead<'b> {
+    /// Returns the method of the request (e.g. `GET`).
+    pub fn method(&self) -> &'b str {
+        self.method
+    }
+
+    /// Returns the target of the request, the path and the query (e.g. `/search?q=rust`).
+    pub fn target(&self) -> &'b str {
+        self.target
+    }
+
+    /// Returns the HTTP version of the request (e.g. `HTTP/1.1`).
+    pub fn http_version(&self) -> &'b str {
+        self.http_version
+    }
+
+    /// Returns the headers in the order they were received, with the whitespace around the values removed.
+    pub fn headers(&self) -> &[(&'b str, &'b [u8])] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header with the given name, ignoring its case.
+    pub fn header(&self, name: &str) -> Option<&'b [u8]> {
+        self.headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns the length of the body, announced in the `Content-Length` header.
+    pub fn content_length(&self) -> Option<usize> {
+        self.content_length
+    }
+
+    /// Returns the bytes of the head, including the empty line that ends it.
+    pub fn as_bytes(&self) -> &'b [u8] {
+        self.bytes
+    }
+}
+
+/// Returns true if the byte can be part of a token (e.g. a method or the name of a header).
+pub(crate) fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Returns true if the byte can be part of the value of a header: visible characters, spaces, tabs and obs-text.
+fn is_field_value(byte: u8) -> bool {
+    matches!(byte, b'\t' | b' '..=b'~' | 0x80..=0xff)
+}
+
+/// Returns true if the version has the `HTTP/x.y` format, even if the version isn't supported.
+fn is_http_version(version: &[u8]) -> bool {
+    matches!(
+        version,
+        [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+            if major.is_ascii_digit() && minor.is_ascii_digit()
+    )
+}
+
+/// Parses the value of a `Content-Length` header, which must be a single decimal number.
+fn parse_content_length(value: &[u8]) -> Result<usize, Error> {
+    let invalid_content_length = || {
+        Error::RequestError(RequestError::InvalidContentLength(
+            String::from_utf8_lossy(value).into_owned(),
+        ))
+    };
+
+    if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+        return Err(invalid_content_length());
+    }
+
+    value
+        .iter()
+        .try_fold(0usize, |length, &digit| {
+            length
+                .checked_mul(10)?
+                .checked_add(usize::from(digit - b'0'))
+        })
+        .ok_or_else(invalid_content_length)
+}
+
+fn invalid_request(line: &[u8]) -> Error {
+    Error::RequestError(RequestError::InvalidRequest(
+        String::from_utf8_lossy(line).into_owned(),
+    ))
+}
+
+fn invalid_header(line: &[u8]) -> Error {
+    Error::RequestError(RequestError::InvalidHeader(
+        String::from_utf8_lossy(line).into_owned(),
+    ))
+}
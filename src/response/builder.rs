@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use super::{Response, Status};
+use crate::package::Package;
+
+/// Chainable builder of a [Response], generated with [Response::builder].
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::response::{Package, Response, Status};
+///
+/// let res = Response::builder()
+///     .status(Status::Created)
+///     .header("Location", "/users/42")
+///     .cookie("last_user", "42")?
+///     .json(r#"{"id": 42}"#)
+///     .build();
+///
+/// assert_eq!(res.status, Status::Created);
+/// assert_eq!(res.get_header_list().get("Content-Type").unwrap(), "application/json");
+/// # Ok::<(), servidor_http::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseBuilder {
+    /// Generates a new builder of a response with a 200 OK status.
+    pub fn new() -> Self {
+        ResponseBuilder {
+            response: Response::new(Status::OK),
+        }
+    }
+
+    /// Sets the status of the response.
+    pub fn status(mut self, status: Status) -> Self {
+        self.response.status = status;
+        self
+    }
+
+    /// Adds a header to the response.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.response.add_header(key, value);
+        self
+    }
+
+    /// Sets a session cookie, check [Response::set_session_cookie].
+    pub fn cookie(mut self, name: &str, value: &str) -> Result<Self, crate::Error> {
+        self.response.set_session_cookie(name, value)?;
+        Ok(self)
+    }
+
+    /// Sets a cookie with the given attributes, check [Response::set_cookie].
+    pub fn cookie_with_attributes(
+        mut self,
+        name: &str,
+        value: &str,
+        attributes: &[&str],
+    ) -> Result<Self, crate::Error> {
+        self.response.set_cookie(name, value, attributes)?;
+        Ok(self)
+    }
+
+    /// Sets the body of the response.
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.response.set_body(body);
+        self
+    }
+
+    /// Sets a plain text body.
+    pub fn text<S>(self, body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.content("text/plain; charset=utf-8", body.into())
+    }
+
+    /// Sets an HTML body.
+    pub fn html<S>(self, body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.content("text/html; charset=utf-8", body.into())
+    }
+
+    /// Sets an already serialized JSON body.
+    pub fn json<S>(self, body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.content("application/json", body.into())
+    }
+
+    fn content(self, content_type: &str, body: String) -> Self {
+        self.header("Content-Type", content_type)
+            .body(body.into_bytes())
+    }
+
+    /// Returns the built response.
+    pub fn build(self) -> Response {
+        self.response
+    }
+}
+
+impl From<ResponseBuilder> for Response {
+    fn from(builder: ResponseBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Package<Vec<u8>> for ResponseBuilder {
+    fn get_header_list(&self) -> HashMap<String, String> {
+        self.response.get_header_list()
+    }
+
+    fn set_header_list(&mut self, headers: HashMap<String, String>) {
+        self.response.set_header_list(headers);
+    }
+
+    fn set_body(&mut self, body: Vec<u8>) {
+        self.response.set_body(body);
+    }
+
+    fn get_body(&self) -> Option<Vec<u8>> {
+        self.response.get_body()
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    fmt::Display,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+use super::{IntoResponse, Response, ResponseError, Status};
+use crate::{package::Package, Error};
+
+/// Time without events after which a keep-alive comment is sent, unless [EventStream::set_keep_alive_interval] is used.
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Comment sent to keep idle connections open, clients ignore it.
+const KEEP_ALIVE_COMMENT: &[u8] = b": keep-alive\n\n";
+
+/// Event sent through an [EventStream].
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use servidor_http::response::Event;
+///
+/// let event = Event::new("{\"cpu\": 12}")
+///     .with_id("42")
+///     .with_event("stats")
+///     .with_retry(Duration::from_secs(5));
+///
+/// assert_eq!(event.to_string(), "id: 42\nevent: stats\nretry: 5000\ndata: {\"cpu\": 12}\n\n");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    retry: Option<Duration>,
+    data: String,
+}
+
+impl Event {
+    /// Generates an event with the given data, data with several lines is sent as several `data` fields.
+    pub fn new<S>(data: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Event {
+            id: None,
+            event: None,
+            retry: None,
+            data: data.into(),
+        }
+    }
+
+    /// Sets the id of the event, the client sends it back in the `Last-Event-ID` header when it reconnects (check [crate::request::Request::last_event_id]).
+    pub fn with_id<S>(mut self, id: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the type of the event, the client dispatches it to the listeners of that type instead of `message`.
+    pub fn with_event<S>(mut self, event: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Sets the time the client waits before reconnecting when the connection is lost.
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+/// Formats the event as it's sent to the client. Line breaks are removed from the id and the type so they can't add fields to the event.
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", single_line(id))?;
+        }
+
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", single_line(event))?;
+        }
+
+        if let Some(retry) = &self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+
+        for line in self.data.replace("\r\n", "\n").split(['\r', '\n']) {
+            writeln!(f, "data: {}", line)?;
+        }
+
+        writeln!(f)
+    }
+}
+
+/// Connection waiting for the chunks of a [BodyStream], woken by the senders.
+type ChunkWaker = Arc<Mutex<Option<Waker>>>;
+
+/// Sends events to an [EventStream], it can be cloned and moved to other threads.
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    // Only taken when the sender is dropped, so the stream is closed before waking the connection
+    sender: Option<Sender<Vec<u8>>>,
+    waker: ChunkWaker,
+}
+
+impl EventSender {
+    /// Sends an event to the client. Fails with [ResponseError::EventStreamClosed] once the connection is closed.
+    pub fn send(&self, event: Event) -> Result<(), Error> {
+        self.sender
+            .as_ref()
+            .ok_or(ResponseError::EventStreamClosed)?
+            .send(event.to_string().into_bytes())
+            .map_err(|_| ResponseError::EventStreamClosed)?;
+
+        wake(&self.waker);
+
+        Ok(())
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        wake(&self.waker);
+    }
+}
+
+/// Wakes the connection waiting for the chunks of the stream, if any.
+fn wake(waker: &ChunkWaker) {
+    if let Some(waker) = waker
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+    {
+        waker.wake();
+    }
+}
+
+/// Long-lived `text/event-stream` response (Server-Sent Events). The connection stays open after the handler returns, sending the events of the [EventSender]s until all of them are dropped, the client disconnects or the server shuts down.
+///
+/// # Example
+///
+/// ```rust
+/// use std::thread;
+///
+/// use servidor_http::{request::Method, response::{Event, EventStream}, router::{Route, Router}};
+///
+/// let mut router = Router::new(String::from("/"));
+///
+/// router.handle_route(Route::new(Method::GET, "/events"), |req, _| {
+///     let (sender, stream) = EventStream::channel();
+///     let first_id = req.last_event_id().and_then(|id| id.parse().ok()).unwrap_or(0) + 1;
+///
+///     thread::spawn(move || {
+///         for id in first_id.. {
+///             if sender.send(Event::new("tick").with_id(id.to_string())).is_err() {
+///                 break;
+///             }
+///         }
+///     });
+///
+///     stream
+/// });
+/// ```
+#[derive(Debug)]
+pub struct EventStream {
+    receiver: Receiver<Vec<u8>>,
+    keep_alive_interval: Duration,
+    waker: ChunkWaker,
+}
+
+impl EventStream {
+    /// Generates an event stream and the sender of its events.
+    pub fn channel() -> (EventSender, EventStream) {
+        let (sender, receiver) = mpsc::channel();
+        let waker = ChunkWaker::default();
+
+        let sender = EventSender {
+            sender: Some(sender),
+            waker: waker.clone(),
+        };
+
+        let stream = EventStream {
+            receiver,
+            keep_alive_interval: DEFAULT_KEEP_ALIVE_INTERVAL,
+            waker,
+        };
+
+        (sender, stream)
+    }
+
+    /// Sets the time without events after which a keep-alive comment is sent.
+    pub fn set_keep_alive_interval(&mut self, keep_alive_interval: Duration) {
+        self.keep_alive_interval = keep_alive_interval;
+    }
+}
+
+impl IntoResponse for EventStream {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Status::OK);
+        response.add_header("Content-Type", "text/event-stream");
+        response.add_header("Cache-Control", "no-cache");
+
+        response.body_stream = Some(BodyStream(Arc::new(Mutex::new(BodyStreamState {
+            receiver: self.receiver,
+            keep_alive_interval: self.keep_alive_interval,
+            last_chunk: Instant::now(),
+            waker: self.waker,
+        }))));
+
+        response
+    }
+}
+
+/// Body of a response that is sent in chunks as they are produced, after the handler returns.
+#[derive(Clone)]
+pub(crate) struct BodyStream(Arc<Mutex<BodyStreamState>>);
+
+struct BodyStreamState {
+    receiver: Receiver<Vec<u8>>,
+    keep_alive_interval: Duration,
+    last_chunk: Instant,
+    waker: ChunkWaker,
+}
+
+/// Result of polling a [BodyStream].
+pub(crate) enum Chunk {
+    Data(Vec<u8>),
+    Pending,
+    End,
+}
+
+impl BodyStream {
+    /// Waits up to the timeout for the next chunk of the body, returning a keep-alive comment instead if no chunk was produced for a while.
+    pub(crate) fn poll(&self, timeout: Duration) -> Chunk {
+        let mut state = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let chunk = match state.receiver.recv_timeout(timeout) {
+            Ok(data) => Chunk::Data(data),
+            Err(RecvTimeoutError::Timeout)
+                if state.last_chunk.elapsed() >= state.keep_alive_interval =>
+            {
+                Chunk::Data(KEEP_ALIVE_COMMENT.to_vec())
+            }
+            Err(RecvTimeoutError::Timeout) => Chunk::Pending,
+            Err(RecvTimeoutError::Disconnected) => Chunk::End,
+        };
+
+        if let Chunk::Data(_) = chunk {
+            state.last_chunk = Instant::now();
+        }
+
+        chunk
+    }
+
+    /// Returns the chunks already produced without waiting for more, which are sent before closing the connection when the server shuts down.
+    pub(crate) fn produced_chunks(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        std::iter::from_fn(|| match self.poll(Duration::ZERO) {
+            Chunk::Data(data) => Some(data),
+            _ => None,
+        })
+    }
+
+    /// Registers the waker woken once the next chunk is produced or the body ends. Registered before polling, so a chunk sent in between isn't missed.
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        let state = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        *state
+            .waker
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(waker.clone());
+    }
+
+    /// Waits for the next chunk of the body without blocking the thread, returning a keep-alive comment instead if no chunk is produced for a while. Never returns [Chunk::Pending].
+    #[cfg(feature = "async")]
+    pub(crate) async fn next_chunk(&self) -> Chunk {
+        loop {
+            let keep_alive_at = {
+                let state = self
+                    .0
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                state.last_chunk + state.keep_alive_interval
+            };
+
+            let chunk = std::future::poll_fn(|cx| {
+                self.register_waker(cx.waker());
+
+                match self.poll(Duration::ZERO) {
+                    Chunk::Pending => std::task::Poll::Pending,
+                    chunk => std::task::Poll::Ready(chunk),
+                }
+            });
+
+            // Once the keep-alive interval expires, the next poll returns the keep-alive comment
+            if let Ok(chunk) = tokio::time::timeout_at(keep_alive_at.into(), chunk).await {
+                return chunk;
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BodyStream")
+    }
+}
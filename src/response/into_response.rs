@@ -0,0 +1,151 @@
+use std::io::ErrorKind;
+
+use super::{Response, ResponseBuilder, Status};
+use crate::{request::RequestError, router::RouterError, Error, ServerError};
+
+/// Trait implemented by every type that can be returned by a route handler.
+///
+/// A handler can also return a `Result<T, E>` where both `T` and `E` implement the trait, errors of the [crate::Error] type will be given to the error handler of the router (check [crate::router::Router::handle_error]) before being turned into a response.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::{request::Method, response::{Response, Status}, router::{Route, Router}, Error};
+///
+/// let mut router = Router::new(String::from("/"));
+///
+/// router.handle_route(Route::new(Method::GET, "/"), |_, _| "Hello World");
+///
+/// router.handle_route(Route::new(Method::POST, "/"), |_, _| (Status::Created, "Created"));
+///
+/// router.handle_route(Route::new(Method::GET, "/file"), |_, mut res| -> Result<Response, Error> {
+///     res.send_file("tests/res/test.html")?;
+///     Ok(res)
+/// });
+/// ```
+pub trait IntoResponse {
+    /// Converts the value into a response.
+    fn into_response(self) -> Response;
+
+    /// Converts the value into a response, or into an error that should be handled by the router. By default it always returns the response generated by [IntoResponse::into_response].
+    fn into_result(self) -> Result<Response, Error>
+    where
+        Self: Sized,
+    {
+        Ok(self.into_response())
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for ResponseBuilder {
+    fn into_response(self) -> Response {
+        self.build()
+    }
+}
+
+impl IntoResponse for Status {
+    fn into_response(self) -> Response {
+        Response::with_status_body(self)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for &str {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response {
+        Response::builder()
+            .header("Content-Type", "application/octet-stream")
+            .body(self)
+            .build()
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> Response {
+        Response::new(Status::NoContent)
+    }
+}
+
+impl<T> IntoResponse for (Status, T)
+where
+    T: IntoResponse,
+{
+    fn into_response(self) -> Response {
+        let (status, body) = self;
+
+        let mut response = body.into_response();
+        response.status = status;
+        response
+    }
+}
+
+impl<T, E> IntoResponse for Result<T, E>
+where
+    T: IntoResponse,
+    E: IntoResponse,
+{
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(err) => err.into_response(),
+        }
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        match self {
+            Ok(value) => value.into_result(),
+            Err(err) => err.into_result(),
+        }
+    }
+}
+
+/// Generates a page with the status that best describes the error, without exposing its details to the client.
+impl IntoResponse for &Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::RouterError(RouterError::RouteNotFound(_)) => Status::NotFound,
+            Error::RequestError(RequestError::HeadTooLarge(_)) => {
+                Status::RequestHeaderFieldsTooLarge
+            }
+            Error::RequestError(RequestError::HttpVersionNotSupported(_)) => {
+                Status::HttpVersionNotSupported
+            }
+            Error::RequestError(RequestError::UnsupportedTransferEncoding(_)) => {
+                Status::NotImplemented
+            }
+            Error::RequestError(_) => Status::BadRequest,
+            Error::ServerError(ServerError::UnknownHost(_)) => Status::MisdirectedRequest,
+            Error::Io(err) if err.kind() == ErrorKind::NotFound => Status::NotFound,
+            Error::Io(err) if err.kind() == ErrorKind::PermissionDenied => Status::Forbidden,
+            _ => Status::InternalServerError,
+        };
+
+        status.into_response()
+    }
+}
+
+/// Errors are passed to the error handler of the router, check [crate::router::Router::handle_error].
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        (&self).into_response()
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        Err(self)
+    }
+}
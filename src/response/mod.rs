@@ -1,23 +1,39 @@
-use std::{collections::HashMap, fmt::Display, path::Path};
+use std::{collections::HashMap, fmt::Display, path::Path, sync::Arc};
 
-use crate::{package, BinaryRepresentation};
+use crate::{
+    cookie_jar::CookieJar, listener::BufferedStream, package, request::is_token, url,
+    BinaryRepresentation,
+};
 
 pub use crate::package::Package;
 
+mod builder;
+mod event_stream;
+#[allow(unused_imports)]
 pub(crate) mod file_mime;
+mod into_response;
 mod status;
 
 use crate::response::file_mime::*;
+pub use builder::ResponseBuilder;
+pub(crate) use event_stream::{BodyStream, Chunk};
+pub use event_stream::{Event, EventSender, EventStream, DEFAULT_KEEP_ALIVE_INTERVAL};
+pub use into_response::IntoResponse;
 pub use status::Status;
 
 /// Struct responsible for handling the response of a request.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Response {
     /// Status of the response
     pub status: Status,
 
     headers: HashMap<String, String>,
+    cookies: Vec<(String, String)>,
     body: Option<Vec<u8>>,
+
+    pub(crate) request_path: Option<String>,
+    pub(crate) upgrade: Option<ProtocolUpgrade>,
+    pub(crate) body_stream: Option<BodyStream>,
 }
 
 package::generate_package_getters_setters!(Response[Vec<u8>]);
@@ -28,14 +44,106 @@ impl Response {
         Response {
             status,
             headers: HashMap::new(),
+            cookies: Vec::new(),
             body: None,
+            request_path: None,
+            upgrade: None,
+            body_stream: None,
         }
     }
 
-    /// Redirects the user to the specified path.
-    pub fn redirect(&mut self, location: &str) {
-        self.add_header("Location", location);
-        self.status = Status::MovedPermanently;
+    /// Returns a [ResponseBuilder] to generate a response by chaining calls.
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    /// Generates a 200 OK response with a plain text body.
+    pub fn text<S>(body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::builder().text(body).build()
+    }
+
+    /// Generates a 200 OK response with an HTML body.
+    pub fn html<S>(body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::builder().html(body).build()
+    }
+
+    /// Generates a 200 OK response with an already serialized JSON body.
+    pub fn json<S>(body: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::builder().json(body).build()
+    }
+
+    /// Generates a 400 Bad Request response.
+    pub fn bad_request() -> Self {
+        Self::with_status_body(Status::BadRequest)
+    }
+
+    /// Generates a 404 Not Found response.
+    pub fn not_found() -> Self {
+        Self::with_status_body(Status::NotFound)
+    }
+
+    /// Generates a 500 Internal Server Error response.
+    pub fn internal_server_error() -> Self {
+        Self::with_status_body(Status::InternalServerError)
+    }
+
+    fn with_status_body(status: Status) -> Self {
+        let body = status.to_string();
+        Self::builder().status(status).text(body).build()
+    }
+
+    /// Redirects the user to the specified location with a 301 Moved Permanently status. Browsers cache this redirection and may change the method of the request to GET, consider using [Response::redirect_permanent] instead.
+    ///
+    /// Relative locations are resolved against the path of the request, and locations containing control characters (such as CR or LF) are rejected with [ResponseError::InvalidLocation].
+    pub fn redirect(&mut self, location: &str) -> Result<(), crate::Error> {
+        self.redirect_with_status(location, Status::MovedPermanently)
+    }
+
+    /// Redirects the user to the specified location with a 308 Permanent Redirect status, which keeps the method and body of the request.
+    pub fn redirect_permanent(&mut self, location: &str) -> Result<(), crate::Error> {
+        self.redirect_with_status(location, Status::PermanentRedirect)
+    }
+
+    /// Redirects the user to the specified location with a 307 Temporary Redirect status, which keeps the method and body of the request.
+    pub fn redirect_temporary(&mut self, location: &str) -> Result<(), crate::Error> {
+        self.redirect_with_status(location, Status::TemporaryRedirect)
+    }
+
+    /// Redirects the user to the specified location with a 303 See Other status, the browser will request the new location with a GET request. Useful for post/redirect/get flows.
+    pub fn see_other(&mut self, location: &str) -> Result<(), crate::Error> {
+        self.redirect_with_status(location, Status::SeeOther)
+    }
+
+    /// Redirects the user to the specified location with a 302 Found status.
+    pub fn found(&mut self, location: &str) -> Result<(), crate::Error> {
+        self.redirect_with_status(location, Status::Found)
+    }
+
+    fn redirect_with_status(&mut self, location: &str, status: Status) -> Result<(), crate::Error> {
+        if location.chars().any(|c| c.is_ascii_control()) {
+            return Err(crate::Error::ResponseError(ResponseError::InvalidLocation(
+                String::from(location),
+            )));
+        }
+
+        let location = match &self.request_path {
+            Some(request_path) => url::resolve_reference(request_path, location),
+            None => String::from(location),
+        };
+
+        self.add_header("Location", &location);
+        self.status = status;
+
+        Ok(())
     }
 
     // Should be moved to the package trait
@@ -44,9 +152,71 @@ impl Response {
         self.set_body(body.into_bytes());
     }
 
-    /// Sets a new session cookie (with the HttpOnly flag).
-    pub fn set_session_cookie(&mut self, name: &str, value: &str) {
-        self.add_header("Set-Cookie", &format!("{}={}; HttpOnly", name, value));
+    /// Sets a new cookie with the given attributes (e.g. `"Path=/"`, `"Max-Age=60"`, `"Secure"`). If a cookie with the same name was already set, it will be replaced.
+    ///
+    /// Names must be tokens and values cookie-octets (optionally between double quotes) as defined by RFC 6265, otherwise [ResponseError::InvalidCookie] is returned.
+    pub fn set_cookie(
+        &mut self,
+        name: &str,
+        value: &str,
+        attributes: &[&str],
+    ) -> Result<(), crate::Error> {
+        let valid_attributes = attributes
+            .iter()
+            .all(|attribute| !attribute.chars().any(|c| c == ';' || c.is_ascii_control()));
+
+        if !is_cookie_name(name) || !is_cookie_value(value) || !valid_attributes {
+            return Err(crate::Error::ResponseError(ResponseError::InvalidCookie(
+                String::from(name),
+            )));
+        }
+
+        let mut cookie = format!("{}={}", name, value);
+
+        for attribute in attributes {
+            cookie.push_str("; ");
+            cookie.push_str(attribute);
+        }
+
+        self.cookies.retain(|(cookie_name, _)| cookie_name != name);
+        self.cookies.push((String::from(name), cookie));
+
+        Ok(())
+    }
+
+    /// Sets a new session cookie (with the HttpOnly flag). If a cookie with the same name was already set, it will be replaced.
+    pub fn set_session_cookie(&mut self, name: &str, value: &str) -> Result<(), crate::Error> {
+        self.set_cookie(name, value, &["HttpOnly"])
+    }
+
+    /// Sets a new session cookie whose value is signed with the given [CookieJar]. Check [crate::request::CookieList::get_signed] to verify it.
+    pub fn set_signed_cookie(
+        &mut self,
+        jar: &CookieJar,
+        name: &str,
+        value: &str,
+    ) -> Result<(), crate::Error> {
+        self.set_session_cookie(name, &jar.sign(name, value))
+    }
+
+    /// Sets a new session cookie whose value is encrypted with the given [CookieJar]. Check [crate::request::CookieList::get_private] to decrypt it.
+    pub fn set_private_cookie(
+        &mut self,
+        jar: &CookieJar,
+        name: &str,
+        value: &str,
+    ) -> Result<(), crate::Error> {
+        self.set_session_cookie(name, &jar.encrypt(name, value)?)
+    }
+
+    /// Returns the value of a cookie set on the response, as it will be sent to the client.
+    pub fn get_cookie(&self, name: &str) -> Option<&str> {
+        self.cookies
+            .iter()
+            .find(|(cookie_name, _)| cookie_name == name)
+            .and_then(|(_, cookie)| cookie.split(';').next())
+            .and_then(|pair| pair.split_once('='))
+            .map(|(_, value)| value)
     }
 
     /// Sets the body of the response to the contents of a file.
@@ -74,6 +244,14 @@ impl Response {
 
 impl Response {
     pub(crate) fn pack(&mut self) {
+        // Informational, 204 No Content and 304 Not Modified responses can't have a body
+        if self.status.is_informational()
+            || matches!(self.status, Status::NoContent | Status::NotModified)
+        {
+            self.body = None;
+            return;
+        }
+
         let content_length = match self.body.as_ref() {
             Some(body) => body.len().to_string(),
             None => "0".to_string(),
@@ -85,8 +263,76 @@ impl Response {
             self.add_header("Content-Type", "text/plain");
         }
     }
+
+    /// Returns the header fields of the response, with a `Set-Cookie` field per cookie.
+    pub(crate) fn header_fields(&self) -> Vec<(&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .chain(
+                self.cookies
+                    .iter()
+                    .map(|(_, cookie)| ("Set-Cookie", cookie.as_str())),
+            )
+            .collect()
+    }
+}
+
+/// Returns true if the name of a cookie is a non empty token.
+fn is_cookie_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(is_token)
+}
+
+/// Returns true if the value of a cookie only contains cookie-octets, optionally between double quotes.
+fn is_cookie_value(value: &str) -> bool {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+
+    value
+        .bytes()
+        .all(|byte| matches!(byte, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e))
+}
+
+/// Protocol that takes over the connection once a 101 Switching Protocols response is sent (e.g. [crate::websocket]).
+#[derive(Clone)]
+pub(crate) struct ProtocolUpgrade(
+    Arc<dyn Fn(BufferedStream) -> Result<(), crate::Error> + Send + Sync>,
+);
+
+impl ProtocolUpgrade {
+    pub(crate) fn new<F>(handler: F) -> Self
+    where
+        F: Fn(BufferedStream) -> Result<(), crate::Error> + Send + Sync + 'static,
+    {
+        ProtocolUpgrade(Arc::new(handler))
+    }
+
+    /// Hands the connection over to the protocol, returning once it's done with it.
+    pub(crate) fn run(&self, stream: BufferedStream) -> Result<(), crate::Error> {
+        (self.0)(stream)
+    }
+}
+
+impl std::fmt::Debug for ProtocolUpgrade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProtocolUpgrade")
+    }
+}
+
+/// Two responses are equal if they have the same status, headers (including cookies) and body.
+impl PartialEq for Response {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status
+            && self.headers == other.headers
+            && self.cookies == other.cookies
+            && self.body == other.body
+    }
 }
 
+impl Eq for Response {}
+
 /// Implementation of the Display trait for the Response struct. WILL REPLACE NON VALID ASCII CHARS WITH "�".
 impl Display for Response {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -96,6 +342,10 @@ impl Display for Response {
             resp.push_str(&format!("{}: {}\r\n", key, value));
         }
 
+        for (_, cookie) in &self.cookies {
+            resp.push_str(&format!("Set-Cookie: {}\r\n", cookie));
+        }
+
         resp.push_str("\r\n");
 
         if let Some(body) = &self.body {
@@ -114,6 +364,10 @@ impl BinaryRepresentation for Response {
             resp.extend_from_slice(format!("{}: {}\r\n", key, value).as_bytes());
         }
 
+        for (_, cookie) in &self.cookies {
+            resp.extend_from_slice(format!("Set-Cookie: {}\r\n", cookie).as_bytes());
+        }
+
         resp.extend_from_slice("\r\n".as_bytes());
 
         if let Some(body) = &self.body {
@@ -123,3 +377,19 @@ impl BinaryRepresentation for Response {
         resp
     }
 }
+
+/// Contains all the possible errors that can occur when generating a response.
+#[derive(Debug, thiserror::Error)]
+pub enum ResponseError {
+    /// The location of a redirection contains control characters, which could be used to inject headers.
+    #[error("Invalid redirect location: {0:?}")]
+    InvalidLocation(String),
+
+    /// The client of an [EventStream] disconnected, or the server shut down, so no more events can be sent.
+    #[error("The event stream is closed")]
+    EventStreamClosed,
+
+    /// The name of a cookie isn't a token, or its value or attributes contain characters not allowed in a cookie.
+    #[error("Invalid cookie: {0:?}")]
+    InvalidCookie(String),
+}
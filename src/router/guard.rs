@@ -0,0 +1,148 @@
+use std::fmt::Display;
+
+use crate::{request::Request, response::Status};
+
+/// Condition a request has to meet to be handled by a route, added with [crate::router::Route::with_guard]. Routes with the same method and path can have different guards: the route whose media type the client prefers handles the request, then the route with the most guards met by the request, and then the route registered first.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::{request::Method, response::Response, router::{Guard, Route, Router}};
+///
+/// let mut router = Router::new(String::from("/"));
+///
+/// router.handle_route(
+///     Route::new(Method::GET, "/users").with_guard(Guard::Accept(String::from("application/json"))),
+///     |_, _| Response::json(r#"[{"id": 42}]"#),
+/// );
+///
+/// router.handle_route(
+///     Route::new(Method::GET, "/users").with_guard(Guard::Accept(String::from("text/html"))),
+///     |_, _| Response::html("<ul><li>42</li></ul>"),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Guard {
+    /// The media type of the request body, given in the `Content-Type` header, has to match (e.g. `application/json` or `text/*`). Requests that don't match any route because of this guard are answered with a 415 Unsupported Media Type status.
+    ContentType(String),
+
+    /// The client has to accept the given media type in the `Accept` header, requests without the header accept every media type. Requests that don't match any route because of this guard are answered with a 406 Not Acceptable status.
+    ///
+    /// The quality of the media type is the q-value of the most specific media range matching it (e.g. `0.8` for `application/json` in `text/html, */*;q=0.8`), and routes with higher qualities, or matched by more specific ranges, are preferred.
+    Accept(String),
+
+    /// The header with the given name has to have the given value.
+    Header(String, String),
+
+    /// The query parameter with the given key has to have the given value.
+    Query(String, String),
+}
+
+impl Guard {
+    /// Returns true if the request meets the guard.
+    pub fn check(&self, request: &Request) -> bool {
+        match self {
+            Guard::ContentType(media_type) => request
+                .find_header("Content-Type")
+                .is_some_and(|content_type| media_type_matches(media_type, essence(content_type))),
+            Guard::Accept(_) => self.accept_preference(request).is_some(),
+            Guard::Header(name, value) => request.find_header(name) == Some(value.as_str()),
+            Guard::Query(key, value) => request
+                .query
+                .as_ref()
+                .and_then(|query| query.get(key))
+                .is_some_and(|query_value| query_value == value),
+        }
+    }
+
+    /// Returns the quality (in thousandths) of the media type of a [Guard::Accept] guard, and the specificity of the media range giving it, if the client accepts the media type. Requests without an `Accept` header accept every media type with quality 1.
+    pub(crate) fn accept_preference(&self, request: &Request) -> Option<(u16, u8)> {
+        let Guard::Accept(media_type) = self else {
+            return None;
+        };
+
+        let accept = match request.find_header("Accept") {
+            Some(accept) => accept,
+            None => return Some((1000, 0)),
+        };
+
+        accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut parameters = media_range.split(';');
+                let range = parameters.next().unwrap_or_default().trim();
+
+                let quality =
+                    match parameters.find_map(|parameter| parameter.trim().strip_prefix("q=")) {
+                        Some(quality) => parse_quality(quality)?,
+                        None => 1000,
+                    };
+
+                media_type_matches(range, media_type).then(|| (range_specificity(range), quality))
+            })
+            .max()
+            .filter(|(_, quality)| *quality > 0)
+            .map(|(specificity, quality)| (quality, specificity))
+    }
+
+    /// Status of the response given when no route matches a request because of this guard.
+    pub(crate) fn rejection_status(&self) -> Option<Status> {
+        match self {
+            Guard::ContentType(_) => Some(Status::UnsupportedMediaType),
+            Guard::Accept(_) => Some(Status::NotAcceptable),
+            Guard::Header(..) | Guard::Query(..) => None,
+        }
+    }
+}
+
+impl Display for Guard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Guard::ContentType(media_type) => write!(f, "Content-Type: {}", media_type),
+            Guard::Accept(media_type) => write!(f, "Accept: {}", media_type),
+            Guard::Header(name, value) => write!(f, "{}: {}", name, value),
+            Guard::Query(key, value) => write!(f, "?{}={}", key, value),
+        }
+    }
+}
+
+/// Returns the media type of a header value without its parameters (e.g. `text/html` for `text/html; charset=utf-8`).
+fn essence(value: &str) -> &str {
+    value.split(';').next().unwrap_or_default().trim()
+}
+
+/// Parses a q-value into thousandths, returns None if it isn't a number between 0 and 1 with up to three decimals.
+fn parse_quality(quality: &str) -> Option<u16> {
+    let (integer, decimals) = quality
+        .trim()
+        .split_once('.')
+        .unwrap_or((quality.trim(), ""));
+
+    if !matches!(integer, "0" | "1")
+        || decimals.len() > 3
+        || !decimals.bytes().all(|digit| digit.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let quality = format!("{}{:0<3}", integer, decimals).parse::<u16>().ok()?;
+    (quality <= 1000).then_some(quality)
+}
+
+/// Returns how specific a media range is: 0 for `*/*`, 1 for ranges like `text/*` and 2 for whole media types.
+fn range_specificity(range: &str) -> u8 {
+    match range.split_once('/') {
+        Some(("*", _)) => 0,
+        Some((_, "*")) => 1,
+        _ => 2,
+    }
+}
+
+/// Returns true if the media type matches the range, which can use wildcards (e.g. `*/*` or `text/*`).
+fn media_type_matches(range: &str, media_type: &str) -> bool {
+    let (range_type, range_subtype) = range.split_once('/').unwrap_or((range, ""));
+    let (media_type, media_subtype) = media_type.split_once('/').unwrap_or((media_type, ""));
+
+    (range_type == "*" || range_type.eq_ignore_ascii_case(media_type))
+        && (range_subtype == "*" || range_subtype.eq_ignore_ascii_case(media_subtype))
+}
@@ -0,0 +1,159 @@
+use std::{fmt::Display, path::PathBuf};
+
+use super::{join_path, Route, Router};
+use crate::request::Method;
+
+/// What serves the requests of a [RouteEntry].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteKind {
+    /// A handler registered with [Router::handle_route].
+    Handler,
+
+    /// Static files served from the given directory, registered with [Router::handle_static].
+    Static(PathBuf),
+}
+
+/// A route served by a router or one of its subrouters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteEntry {
+    /// Method, full path and name of the route, the path includes the prefixes of the routers it's mounted in. Static mounts are listed as GET routes of the router path.
+    pub route: Route,
+
+    /// What serves the route.
+    pub kind: RouteKind,
+
+    /// Names of the middlewares the requests of the route go through, from the outermost to the innermost.
+    pub middlewares: Vec<String>,
+}
+
+/// List of every route served by a router and its subrouters, generated with [Router::route_table]. Its [Display] implementation prints a table with a route per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteTable {
+    entries: Vec<RouteEntry>,
+}
+
+impl RouteTable {
+    pub(super) fn new(router: &Router) -> Self {
+        let mut entries = Vec::new();
+        Self::collect(router, "", &[], &mut entries);
+
+        entries.sort_by(|a, b| {
+            (&a.route.path, a.route.method.to_string())
+                .cmp(&(&b.route.path, b.route.method.to_string()))
+        });
+
+        RouteTable { entries }
+    }
+
+    fn collect(
+        router: &Router,
+        parent_prefix: &str,
+        parent_middlewares: &[String],
+        entries: &mut Vec<RouteEntry>,
+    ) {
+        let prefix = format!("{}{}", parent_prefix, router.prefix());
+
+        let mut middlewares = parent_middlewares.to_vec();
+        middlewares.extend(
+            router
+                .middlewares
+                .iter()
+                .map(|middleware| String::from(middleware.name())),
+        );
+
+        for (route, _) in &router.routes {
+            entries.push(RouteEntry {
+                route: Route {
+                    path: join_path(&prefix, &route.path),
+                    name: router
+                        .route_names
+                        .iter()
+                        .find(|(_, named_route)| *named_route == route)
+                        .map(|(name, _)| name.clone()),
+                    ..route.clone()
+                },
+                kind: RouteKind::Handler,
+                middlewares: middlewares.clone(),
+            });
+        }
+
+        if let Some(static_path) = &router.static_path {
+            entries.push(RouteEntry {
+                route: Route::new(Method::GET, &join_path(&prefix, "/")),
+                kind: RouteKind::Static(static_path.clone()),
+                middlewares: middlewares.clone(),
+            });
+        }
+
+        for subrouter in router.routers.values() {
+            Self::collect(subrouter, &prefix, &middlewares, entries);
+        }
+    }
+
+    /// Returns the routes of the table, sorted by path and method.
+    pub fn entries(&self) -> &[RouteEntry] {
+        &self.entries
+    }
+
+    /// Returns true if the table contains a route with the given method and full path.
+    pub fn contains(&self, method: &Method, path: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| &entry.route.method == method && entry.route.path == path)
+    }
+}
+
+impl Display for RouteTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let method_width = self
+            .entries
+            .iter()
+            .map(|entry| entry.route.method.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        let path_width = self
+            .entries
+            .iter()
+            .map(|entry| entry.route.path.len())
+            .max()
+            .unwrap_or(0);
+
+        for entry in &self.entries {
+            let mut target = match &entry.kind {
+                RouteKind::Handler => String::from("handler"),
+                RouteKind::Static(static_path) => format!("static {}", static_path.display()),
+            };
+
+            if !entry.route.guards.is_empty() {
+                let guards = entry
+                    .route
+                    .guards
+                    .iter()
+                    .map(|guard| guard.to_string())
+                    .collect::<Vec<_>>();
+
+                target.push_str(&format!(" if {}", guards.join(" and ")));
+            }
+
+            if let Some(name) = &entry.route.name {
+                target.push_str(&format!(" ({})", name));
+            }
+
+            let mut line = format!(
+                "{:method_width$}  {:path_width$}  {}",
+                entry.route.method.to_string(),
+                entry.route.path,
+                target,
+            );
+
+            if !entry.middlewares.is_empty() {
+                line.push_str(&format!(" [{}]", entry.middlewares.join(", ")));
+            }
+
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
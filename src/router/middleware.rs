@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use crate::{request::Request, response::Response, Error};
+
+/// Continuation passed to a [Middleware], calling it runs the rest of the middleware chain and the route handler.
+pub type Next<'a> = &'a dyn Fn(Request) -> Result<Response, Error>;
+
+/// Code that runs around the handlers of a [crate::router::Router]. A middleware can modify the request before calling `next`, modify the response returned by it, or answer the request by itself without calling `next` at all.
+///
+/// Any closure with the `Fn(Request, Next) -> Result<Response, Error>` signature can be used as a middleware.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::{package::Package, router::{Next, Router}, request::Request};
+///
+/// let mut router = Router::new(String::from("/"));
+///
+/// router.add_middleware(|req: Request, next: Next| {
+///     let mut res = next(req)?;
+///     res.add_header("Server", "servidor_http");
+///     Ok(res)
+/// });
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Handles the request, `next` has to be called in order to reach the route handler.
+    fn handle(&self, request: Request, next: Next) -> Result<Response, Error>;
+
+    /// Name of the middleware shown in the route listings (check [crate::router::Router::route_table]). Defaults to the name of the type.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(Request, Next) -> Result<Response, Error> + Send + Sync,
+{
+    fn handle(&self, request: Request, next: Next) -> Result<Response, Error> {
+        self(request, next)
+    }
+}
+
+pub(crate) fn run_chain(
+    middlewares: &[Arc<dyn Middleware>],
+    request: Request,
+    handler: Next,
+) -> Result<Response, Error> {
+    match middlewares.split_first() {
+        Some((middleware, rest)) => {
+            middleware.handle(request, &|request| run_chain(rest, request, handler))
+        }
+        None => handler(request),
+    }
+}
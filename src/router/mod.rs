@@ -1,33 +1,85 @@
 #[allow(missing_docs)]
 pub mod route;
 
+mod guard;
+mod listing;
+mod middleware;
+
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
     fs::{self},
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
+pub use guard::Guard;
+pub use listing::{RouteEntry, RouteKind, RouteTable};
+pub use middleware::{Middleware, Next};
 pub use route::Route;
 
 use crate::{
-    request::Request,
-    response::{Response, Status},
+    request::{Method, Request},
+    response::{IntoResponse, Response, Status},
+    url,
+    websocket::{self, WebSocket},
     Error,
 };
 
+type Handler = Arc<dyn Fn(Request, Response) -> Result<Response, Error> + Send + Sync>;
+type ErrorHandler = Arc<dyn Fn(Error) -> Result<Response, Error> + Send + Sync>;
+type FoundRoute<'a> = (&'a Handler, HashMap<String, String>);
+
 /// Handles the routing of requests made by the client.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Router {
     path: String,
 
-    routes: HashMap<Route, fn(Request, Response) -> Response>,
-    routers: HashMap<String, Router>,
+    routes: Vec<(Route, Handler)>,
+    route_names: HashMap<String, Route>,
+    /// Subrouters by prefix, sorted so they're searched in a deterministic order.
+    routers: BTreeMap<String, Router>,
+    /// Methods handled by the routes and static files of the router and its subrouters, updated as they're added.
+    methods: HashSet<Method>,
+
+    middlewares: Vec<Arc<dyn Middleware>>,
+    error_handler: Option<ErrorHandler>,
+    not_found_handler: Option<Handler>,
+    fallback_handler: Option<Handler>,
 
     default_response: Option<Response>,
+    trailing_slash: Option<TrailingSlash>,
 
     static_path: Option<PathBuf>,
 }
 
+impl Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field("path", &self.path)
+            .field(
+                "routes",
+                &self
+                    .routes
+                    .iter()
+                    .map(|(route, _)| route)
+                    .collect::<Vec<_>>(),
+            )
+            .field("route_names", &self.route_names)
+            .field("routers", &self.routers)
+            .field("middlewares", &self.middlewares.len())
+            .field("error_handler", &self.error_handler.is_some())
+            .field("not_found_handler", &self.not_found_handler.is_some())
+            .field("fallback_handler", &self.fallback_handler.is_some())
+            .field("default_response", &self.default_response)
+            .field("trailing_slash", &self.trailing_slash)
+            .field("static_path", &self.static_path)
+            .finish()
+    }
+}
+
 impl Default for Router {
     fn default() -> Self {
         Router::new(String::from("/"))
@@ -39,21 +91,378 @@ impl Router {
     pub fn new(path: String) -> Self {
         Router {
             path,
-            routes: HashMap::new(),
-            routers: HashMap::new(),
+            routes: Vec::new(),
+            route_names: HashMap::new(),
+            routers: BTreeMap::new(),
+            methods: HashSet::new(),
+            middlewares: Vec::new(),
+            error_handler: None,
+            not_found_handler: None,
+            fallback_handler: None,
             default_response: None,
+            trailing_slash: None,
             static_path: None,
         }
     }
 
-    /// Handles a response for a given route
-    pub fn handle_route(&mut self, route: Route, handler: fn(Request, Response) -> Response) {
-        self.routes.insert(route, handler);
+    /// Handles a response for a given route. The handler can return anything that implements [IntoResponse], including a `Result` whose errors will be given to the error handler of the router.
+    ///
+    /// Routes with parameters (e.g. `/users/:id`) are only used when no route matches the path exactly, and named routes can be used to generate URLs with [Router::url_for]. HEAD requests are handled by the GET routes when no HEAD route matches them, and the server drops the body of their responses.
+    pub fn handle_route<F, R>(&mut self, mut route: Route, handler: F)
+    where
+        F: Fn(Request, Response) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        if let Some(name) = route.name.take() {
+            self.route_names.insert(name, route.clone());
+        }
+
+        let handler = Self::wrap_handler(handler);
+
+        match self
+            .routes
+            .iter_mut()
+            .find(|(handled_route, _)| *handled_route == route)
+        {
+            Some((_, handled_handler)) => *handled_handler = handler,
+            None => self.routes.push((route, handler)),
+        }
+
+        self.update_methods();
+    }
+
+    /// Handles the requests to the route with an async handler, which is awaited in the thread handling the request. Requires the `async` feature.
+    ///
+    /// The handler runs in the tokio runtime of [crate::HttpServer::listen_async], or in a runtime shared by the connections of [crate::HttpServer::listen]. The thread handling the request is blocked until the handler finishes, so with [crate::HttpServer::listen_async] every async handler being awaited holds a thread of the blocking pool of the runtime. Handlers that fail to start the shared runtime return the error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use servidor_http::{request::Method, router::{Route, Router}};
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    ///
+    /// router.handle_async_route(Route::new(Method::GET, "/slow"), |_, _| async {
+    ///     tokio::time::sleep(Duration::from_millis(10)).await;
+    ///     "Done"
+    /// });
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn handle_async_route<F, Fut, R>(&mut self, route: Route, handler: F)
+    where
+        F: Fn(Request, Response) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = R>,
+        R: IntoResponse,
+    {
+        self.handle_route(route, move |req, res| {
+            crate::async_server::block_on(|| async { handler(req, res).await.into_result() })?
+        });
+    }
+
+    /// Handles the WebSocket connections opened with a request to the route. The handler gets the request and the [WebSocket] once the handshake is done, and the connection is closed when it returns.
+    ///
+    /// Requests that aren't valid handshakes are answered with 400 Bad Request, or 426 Upgrade Required if they ask for another version of the protocol. [crate::websocket::WebSocketError]s returned by the handler are ignored, as the connection is already closed because of them.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::{request::Method, router::{Route, Router}, websocket::Message};
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    ///
+    /// router.handle_websocket(Route::new(Method::GET, "/echo"), |_, mut socket| loop {
+    ///     match socket.receive()? {
+    ///         Message::Text(text) => socket.send(Message::Text(text))?,
+    ///         Message::Binary(data) => socket.send(Message::Binary(data))?,
+    ///         Message::Close(_) => return Ok(()),
+    ///         _ => (),
+    ///     }
+    /// });
+    /// ```
+    pub fn handle_websocket<F>(&mut self, route: Route, handler: F)
+    where
+        F: Fn(Request, WebSocket) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+
+        self.handle_route(route, move |req, _| {
+            websocket::handshake(req, handler.clone())
+        });
+    }
+
+    /// Generates the URL of the route with the given name, searching in the router and then in its subrouters in the order of their prefixes, and including the prefixes of the routers the route is mounted in.
+    ///
+    /// Parameters of the route (e.g. `:id` in `/users/:id`) are replaced with the value with the same name, the rest of the values are added to the query of the URL. Values are percent-encoded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::{request::Method, router::{Route, Router}};
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    ///
+    /// let mut users_router = Router::new(String::from("/users"));
+    /// users_router.handle_route(
+    ///     Route::new(Method::GET, "/:id").with_name("user_detail"),
+    ///     |req, _| format!("User {}", req.param("id").unwrap()),
+    /// );
+    /// router.handle_router(users_router);
+    ///
+    /// let url = router.url_for("user_detail", &[("id", "42"), ("tab", "posts")]).unwrap();
+    /// assert_eq!(url, "/users/42?tab=posts");
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, Error> {
+        let route_path = self
+            .find_named_route(name, "")
+            .ok_or_else(|| Error::RouterError(RouterError::UnknownRouteName(String::from(name))))?;
+
+        let mut used_params = Vec::new();
+        let mut segments = Vec::new();
+
+        for segment in route_path.split('/') {
+            match segment.strip_prefix(':') {
+                Some(param_name) => {
+                    let (_, value) = params
+                        .iter()
+                        .find(|(key, _)| *key == param_name)
+                        .ok_or_else(|| {
+                            Error::RouterError(RouterError::MissingRouteParameter(
+                                String::from(name),
+                                String::from(param_name),
+                            ))
+                        })?;
+
+                    used_params.push(param_name);
+                    segments.push(url::percent_encode(value));
+                }
+                None => segments.push(String::from(segment)),
+            }
+        }
+
+        let mut url = segments.join("/");
+
+        let query = params
+            .iter()
+            .filter(|(key, _)| !used_params.contains(key))
+            .map(|(key, value)| {
+                format!(
+                    "{}={}",
+                    url::percent_encode(key),
+                    url::percent_encode(value)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query.join("&"));
+        }
+
+        Ok(url)
     }
 
-    /// Routes the route to a subrouter
+    fn find_named_route(&self, name: &str, parent_prefix: &str) -> Option<String> {
+        let prefix = format!("{}{}", parent_prefix, self.prefix());
+
+        if let Some(route) = self.route_names.get(name) {
+            return Some(join_path(&prefix, &route.path));
+        }
+
+        self.routers
+            .values()
+            .find_map(|router| router.find_named_route(name, &prefix))
+    }
+
+    fn wrap_handler<F, R>(handler: F) -> Handler
+    where
+        F: Fn(Request, Response) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        Arc::new(move |request, response| handler(request, response).into_result())
+    }
+
+    /// Sets the response used as template for the responses given to the handlers of the router (e.g. to add a header to every response). By default it's an empty 200 OK response.
+    ///
+    /// Subrouters without a default response of their own will use this one.
+    pub fn set_default_response(&mut self, response: Response) {
+        self.default_response = Some(response);
+    }
+
+    /// Sets how the router handles paths that only differ from its routes in a trailing slash or in duplicate slashes (e.g. `/users/` or `//users` for the `/users` route). By default paths have to match exactly.
+    ///
+    /// Subrouters without a policy of their own will use this one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::{request::{Method, Request}, response::Status, router::{Route, Router, TrailingSlash}};
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    /// router.handle_route(Route::new(Method::GET, "/users"), |_, _| "Users");
+    /// router.set_trailing_slash(TrailingSlash::Redirect);
+    ///
+    /// let res = router.handle_request(Request::new(Method::GET, "/users/", None)).unwrap();
+    /// assert_eq!(res.status, Status::PermanentRedirect);
+    /// ```
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = Some(policy);
+    }
+
+    /// Sets the handler called when no route, subrouter or static file matches the request. The response given to the handler has a 404 Not Found status.
+    ///
+    /// Subrouters without a not found handler of their own will use this one. If no handler is set, the router returns a [RouterError::RouteNotFound] error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::router::Router;
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    ///
+    /// router.handle_not_found(|req, mut res| {
+    ///     res.set_body_string(format!("<h1>{} doesn't exist</h1>", req.path.path));
+    ///     res
+    /// });
+    /// ```
+    pub fn handle_not_found<F, R>(&mut self, handler: F)
+    where
+        F: Fn(Request, Response) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.not_found_handler = Some(Self::wrap_handler(handler));
+    }
+
+    /// Sets the handler called for every request that doesn't match any route, subrouter or static file, including the requests whose method isn't handled by the router and the requests rejected by the guards of the routes. Takes precedence over the not found handler.
+    ///
+    /// Subrouters without a fallback handler of their own will use this one.
+    pub fn handle_fallback<F, R>(&mut self, handler: F)
+    where
+        F: Fn(Request, Response) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.fallback_handler = Some(Self::wrap_handler(handler));
+    }
+
+    /// Sets the handler that turns the errors produced while handling a request (by the route handlers, the middlewares or the router itself) into responses. The handler can give the error back (e.g. returning `Err(error)`) so the parent router, or the server, handles it instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::{response::{Response, Status}, router::{Router, RouterError}, Error};
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    ///
+    /// router.handle_error(|error| match error {
+    ///     Error::RouterError(RouterError::RouteNotFound(route)) => {
+    ///         Ok(Response::builder().status(Status::NotFound).html(format!("<h1>{} not found</h1>", route.path)).build())
+    ///     }
+    ///     other => Err(other),
+    /// });
+    /// ```
+    pub fn handle_error<F, R>(&mut self, handler: F)
+    where
+        F: Fn(Error) -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.error_handler = Some(Arc::new(move |error| handler(error).into_result()));
+    }
+
+    /// Routes the requests whose path starts with the path of the subrouter (e.g. `/api/v2/admin`) to it. Prefixes are matched on whole segments, so a subrouter mounted at `/api` handles `/api` and `/api/users` but not `/apix`, and the subrouter with the longest matching prefix wins.
+    ///
+    /// Handlers of the subrouter can get the original path of the request from [Request::path] and the part relative to the subrouter from [Request::remaining_path].
     pub fn handle_router(&mut self, router: Router) {
-        self.routers.insert(router.path.clone(), router);
+        self.routers.insert(String::from(router.prefix()), router);
+        self.update_methods();
+    }
+
+    /// Returns the list of every route served by the router and its subrouters, with their full paths and middlewares.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use servidor_http::{request::Method, router::{Route, Router}};
+    ///
+    /// let mut router = Router::new(String::from("/"));
+    /// router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    ///
+    /// let mut api_router = Router::new(String::from("/api"));
+    /// api_router.handle_route(Route::new(Method::POST, "/users"), |_, res| res);
+    /// router.handle_router(api_router);
+    ///
+    /// let route_table = router.route_table();
+    ///
+    /// assert!(route_table.contains(&Method::POST, "/api/users"));
+    /// println!("{}", route_table);
+    /// ```
+    pub fn route_table(&self) -> RouteTable {
+        RouteTable::new(self)
+    }
+
+    fn prefix(&self) -> &str {
+        self.path.trim_end_matches('/')
+    }
+
+    /// Returns the handler of the most specific route matching the request: routes for the method of the request win over the GET routes handling a HEAD request, then routes with fewer parameters win over routes with more, then routes whose media type the client prefers win (check [Guard::Accept]), then routes with more guards win over routes with fewer, and finally the route registered first wins. When routes match the path but not their guards, returns the status the request should be rejected with, if any.
+    fn find_route(&self, request: &Request, path: &str) -> Result<Option<FoundRoute<'_>>, Status> {
+        let mut rejection: Option<Status> = None;
+
+        let found_route = self
+            .routes
+            .iter()
+            .filter(|(route, _)| {
+                route.method == request.path.method
+                    || (request.path.method == Method::HEAD && route.method == Method::GET)
+            })
+            .filter_map(|(route, handler)| {
+                route
+                    .match_parameters(path)
+                    .map(|params| (route, handler, params))
+            })
+            .filter(
+                |(route, _, _)| match route.guards.iter().find(|guard| !guard.check(request)) {
+                    Some(guard) => {
+                        rejection = rejection
+                            .take()
+                            .into_iter()
+                            .chain(guard.rejection_status())
+                            .max_by_key(|status| status.code());
+                        false
+                    }
+                    None => true,
+                },
+            )
+            .min_by_key(|(route, _, _)| {
+                Reverse((
+                    route.method == request.path.method,
+                    route.literal_segments(),
+                    route.accept_preference(request),
+                    route.guards.len(),
+                ))
+            })
+            .map(|(_, handler, params)| (handler, params));
+
+        match (found_route, rejection) {
+            (None, Some(status)) => Err(status),
+            (found_route, _) => Ok(found_route),
+        }
+    }
+
+    fn find_subrouter(&self, path: &str) -> Option<&Router> {
+        self.routers
+            .iter()
+            .filter(|(prefix, _)| strip_path_prefix(path, prefix).is_some())
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, router)| router)
+    }
+
+    /// Adds a middleware that will run, in the order they were added, around every request handled by the router and its subrouters.
+    pub fn add_middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware + 'static,
+    {
+        self.middlewares.push(Arc::new(middleware));
     }
 
     /// Static path to serve files from
@@ -62,50 +471,196 @@ impl Router {
         P: AsRef<Path>,
     {
         self.static_path = Some(PathBuf::from(path.as_ref()));
+        self.update_methods();
+    }
+
+    /// Returns true if any route of the router, or of its subrouters, handles the given method. Static files are served for GET and HEAD requests, and HEAD requests are handled wherever GET requests are.
+    pub fn handles_method(&self, method: &Method) -> bool {
+        self.methods.contains(method)
+            || (*method == Method::HEAD && self.methods.contains(&Method::GET))
+    }
+
+    fn update_methods(&mut self) {
+        let static_methods = match self.static_path {
+            Some(_) => vec![Method::GET, Method::HEAD],
+            None => Vec::new(),
+        };
+
+        self.methods = self
+            .routes
+            .iter()
+            .map(|(route, _)| route.method.clone())
+            .chain(static_methods)
+            .chain(
+                self.routers
+                    .values()
+                    .flat_map(|router| router.methods.iter().cloned()),
+            )
+            .collect();
     }
 
-    fn not_found_handler(request: Request) -> Result<Response, Error> {
+    fn handle_unmatched(
+        request: Request,
+        mut response: Response,
+        inherited: &InheritedHandlers,
+    ) -> Result<Response, Error> {
+        if let Some(fallback_handler) = inherited.fallback_handler {
+            return fallback_handler(request, response);
+        }
+
+        if let Some(not_found_handler) = inherited.not_found_handler {
+            response.status = Status::NotFound;
+            return not_found_handler(request, response);
+        }
+
         let route = Route::new(request.path.method, request.path.path.as_str());
         Err(Error::RouterError(RouterError::RouteNotFound(route)))
     }
 
-    pub(crate) fn handle_request(&self, request: Request) -> Result<Response, Error> {
-        let mut path_str = request
-            .path
-            .path
-            .trim_start_matches(self.path.as_str())
-            .to_string();
+    /// Handles a request, running it through the middlewares of the router, and returns the response generated by the matching handler.
+    ///
+    /// The `.` and `..` segments of the path are removed before routing, as described in RFC 3986 section 5.2.4. If a middleware or a handler panics, the panic is caught and turned into a [RouterError::HandlerPanicked] error.
+    pub fn handle_request(&self, mut request: Request) -> Result<Response, Error> {
+        request.path.path = url::remove_dot_segments(&request.path.path);
+
+        let inherited = InheritedHandlers {
+            implements_method: self.handles_method(&request.path.method),
+            ..InheritedHandlers::default()
+        };
+
+        self.handle_inherited_request(request, &inherited)
+    }
+
+    fn handle_inherited_request(
+        &self,
+        mut request: Request,
+        parent_handlers: &InheritedHandlers,
+    ) -> Result<Response, Error> {
+        let is_mounted = strip_path_prefix(request.remaining_path(), self.prefix()).is_some();
 
-        if !path_str.starts_with('/') {
-            path_str.insert(0, '/');
+        if is_mounted {
+            request.mount_path.push_str(self.prefix());
         }
 
-        let request_route = Route::new(request.path.method.clone(), &path_str);
+        let inherited = InheritedHandlers {
+            is_mounted,
+            implements_method: parent_handlers.implements_method,
+            not_found_handler: self
+                .not_found_handler
+                .as_ref()
+                .or(parent_handlers.not_found_handler),
+            fallback_handler: self
+                .fallback_handler
+                .as_ref()
+                .or(parent_handlers.fallback_handler),
+            default_response: self
+                .default_response
+                .as_ref()
+                .or(parent_handlers.default_response),
+            trailing_slash: self
+                .trailing_slash
+                .unwrap_or(parent_handlers.trailing_slash),
+        };
+
+        let route = request.path.clone();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            middleware::run_chain(&self.middlewares, request, &|request| {
+                self.dispatch(request, &inherited)
+            })
+        }))
+        .unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("Unknown panic"));
+
+            Err(Error::RouterError(RouterError::HandlerPanicked(
+                route, message,
+            )))
+        });
+
+        match (result, &self.error_handler) {
+            (Err(error), Some(error_handler)) => error_handler(error),
+            (result, _) => result,
+        }
+    }
+
+    fn dispatch(
+        &self,
+        mut request: Request,
+        inherited: &InheritedHandlers,
+    ) -> Result<Response, Error> {
+        let mut path_str = String::from(request.remaining_path());
 
-        let response = self
+        let mut response = inherited
             .default_response
-            .clone()
+            .cloned()
             .unwrap_or_else(|| Response::new(Status::OK));
 
-        if let Some(handler) = self.routes.get(&request_route) {
-            return Ok(handler(request, response));
+        response.request_path = Some(request.path.path.clone());
+
+        if !inherited.is_mounted {
+            return Self::handle_unmatched(request, response, inherited);
         }
 
-        let route_segment = match path_str.split('/').nth(1) {
-            Some(route) => route,
-            None => {
-                return Self::not_found_handler(request);
+        if inherited.trailing_slash != TrailingSlash::Strict && path_str.contains("//") {
+            path_str = url::collapse_slashes(&path_str);
+
+            if inherited.trailing_slash == TrailingSlash::Redirect {
+                return Self::redirect_to_canonical(&request, &path_str, response);
             }
-        };
 
-        if let Some(subrouter) = self.routers.get(format!("/{}", route_segment).as_str()) {
-            return subrouter.handle_request(request);
+            request.path.path = format!("{}{}", request.mount_path, path_str);
+        }
+
+        match self.find_route(&request, &path_str) {
+            Ok(Some((handler, params))) => {
+                request.params = params;
+                return handler(request, response);
+            }
+            Ok(None) => (),
+            Err(status) => return Self::reject(request, response, status, inherited),
+        }
+
+        if inherited.trailing_slash != TrailingSlash::Strict {
+            let alternate_path = match path_str.strip_suffix('/') {
+                Some("") => None,
+                Some(path) => Some(String::from(path)),
+                None => Some(format!("{}/", path_str)),
+            };
+
+            let alternate_route = alternate_path.and_then(|path| {
+                self.find_route(&request, &path)
+                    .ok()
+                    .flatten()
+                    .map(|route| (path, route))
+            });
+
+            if let Some((alternate_path, (handler, params))) = alternate_route {
+                if inherited.trailing_slash == TrailingSlash::Redirect {
+                    return Self::redirect_to_canonical(&request, &alternate_path, response);
+                }
+
+                request.path.path = format!("{}{}", request.mount_path, alternate_path);
+                request.params = params;
+                return handler(request, response);
+            }
+        }
+
+        if !inherited.implements_method {
+            return Self::reject(request, response, Status::NotImplemented, inherited);
+        }
+
+        if let Some(subrouter) = self.find_subrouter(&path_str) {
+            return subrouter.handle_inherited_request(request, inherited);
         }
 
         macro_rules! check_unsafe_path {
             ($var:expr, $($unsafe_expr:expr),*) => {
                 if $($var.contains($unsafe_expr) ||)* false {
-                    return Self::not_found_handler(request);
+                    return Self::handle_unmatched(request, response, inherited);
                 }
             };
         }
@@ -124,7 +679,82 @@ impl Router {
             }
         }
 
-        Self::not_found_handler(request)
+        Self::handle_unmatched(request, response, inherited)
+    }
+
+    /// Gives the request to the fallback handler if there's one, otherwise answers it with the default response and the given status.
+    fn reject(
+        request: Request,
+        mut response: Response,
+        status: Status,
+        inherited: &InheritedHandlers,
+    ) -> Result<Response, Error> {
+        match inherited.fallback_handler {
+            Some(fallback_handler) => fallback_handler(request, response),
+            None => {
+                response.status = status;
+                Ok(response)
+            }
+        }
+    }
+
+    fn redirect_to_canonical(
+        request: &Request,
+        canonical_path: &str,
+        mut response: Response,
+    ) -> Result<Response, Error> {
+        let mut location = format!("{}{}", request.mount_path, canonical_path);
+
+        if let Some(query_string) = request.query_string() {
+            location.push('?');
+            location.push_str(query_string);
+        }
+
+        response.redirect_permanent(&location)?;
+        Ok(response)
+    }
+}
+
+/// How a [Router] handles paths that only differ from its routes in a trailing slash or in duplicate slashes, check [Router::set_trailing_slash].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    /// Paths have to match the routes exactly, `/users/` doesn't match the `/users` route.
+    #[default]
+    Strict,
+
+    /// Clients are redirected to the path of the matching route with a 308 Permanent Redirect status, which keeps the method and body of the request.
+    Redirect,
+
+    /// The request is handled by the matching route as if its path was the path of the route.
+    Match,
+}
+
+#[derive(Default)]
+struct InheritedHandlers<'a> {
+    is_mounted: bool,
+    /// Whether any route of the top-level router, the one handling the request, handles its method.
+    implements_method: bool,
+    not_found_handler: Option<&'a Handler>,
+    fallback_handler: Option<&'a Handler>,
+    default_response: Option<&'a Response>,
+    trailing_slash: TrailingSlash,
+}
+
+/// Joins the prefix of a router with the path of one of its routes.
+fn join_path(prefix: &str, path: &str) -> String {
+    match (prefix, path) {
+        ("", path) => String::from(path),
+        (prefix, "/") => String::from(prefix),
+        (prefix, path) => format!("{}{}", prefix, path),
+    }
+}
+
+/// Returns the rest of the path if it starts with the given prefix, matching whole segments.
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    match path.strip_prefix(prefix) {
+        Some("") => Some("/"),
+        Some(rest) if rest.starts_with('/') => Some(rest),
+        _ => None,
     }
 }
 
@@ -134,4 +764,16 @@ pub enum RouterError {
     /// Route not found.
     #[error("Route not found: {0:?}")]
     RouteNotFound(Route),
+
+    /// No route has the given name, check [Router::url_for].
+    #[error("No route named {0}")]
+    UnknownRouteName(String),
+
+    /// A parameter of the named route wasn't given, check [Router::url_for]. Contains the name of the route and the name of the parameter.
+    #[error("Missing parameter {1} of route {0}")]
+    MissingRouteParameter(String, String),
+
+    /// The handler of the route, or a middleware, panicked. Contains the panic message.
+    #[error("Handler of {0:?} panicked: {1}")]
+    HandlerPanicked(Route, String),
 }
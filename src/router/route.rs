@@ -1,13 +1,27 @@
-use crate::request::{Method, Request};
+use std::collections::HashMap;
+
+use super::Guard;
+use crate::{
+    request::{Method, Request},
+    url,
+};
 
 /// Represents a route of a request made by a client.
+///
+/// Segments of the path starting with `:` (e.g. `/users/:id`) are parameters that match any segment, check [Request::param] to get their values.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Route {
-    /// The method of the request, check [crate::request::RequestMethod] for supported methods.
+    /// The method of the request, check [crate::request::Method] for supported methods.
     pub method: Method,
 
     /// The path of the request.
     pub path: String,
+
+    /// Name used to generate URLs pointing to the route, check [crate::router::Router::url_for].
+    pub name: Option<String>,
+
+    /// Conditions the request has to meet to be handled by the route, check [Guard].
+    pub guards: Vec<Guard>,
 }
 
 impl Route {
@@ -16,6 +30,63 @@ impl Route {
         Route {
             method,
             path: String::from(path),
+            name: None,
+            guards: Vec::new(),
+        }
+    }
+
+    /// Gives a name to the route, used to generate URLs pointing to it with [crate::router::Router::url_for].
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(String::from(name));
+        self
+    }
+
+    /// Adds a guard to the route, the route only handles the requests that meet all of its guards.
+    pub fn with_guard(mut self, guard: Guard) -> Self {
+        self.guards.push(guard);
+        self
+    }
+
+    /// Returns the number of segments that aren't parameters, used to choose the most specific route.
+    pub(crate) fn literal_segments(&self) -> usize {
+        self.path
+            .split('/')
+            .filter(|segment| !segment.starts_with(':'))
+            .count()
+    }
+
+    /// Returns how much the client prefers the media type of the [Guard::Accept] guards of the route, the lowest if there are several. Routes without them have the lowest preference.
+    pub(crate) fn accept_preference(&self, request: &Request) -> Option<(u16, u8)> {
+        self.guards
+            .iter()
+            .filter(|guard| matches!(guard, Guard::Accept(_)))
+            .map(|guard| guard.accept_preference(request))
+            .min()
+            .flatten()
+    }
+
+    /// Matches the path against the route, returning the percent-decoded values of the parameters if it matches.
+    pub(crate) fn match_parameters(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut route_segments = self.path.split('/');
+        let mut path_segments = path.split('/');
+        let mut parameters = HashMap::new();
+
+        loop {
+            match (route_segments.next(), path_segments.next()) {
+                (Some(route_segment), Some(path_segment)) => {
+                    match route_segment.strip_prefix(':') {
+                        Some(_) if path_segment.is_empty() => return None,
+                        Some(name) => {
+                            parameters
+                                .insert(String::from(name), url::percent_decode(path_segment));
+                        }
+                        None if route_segment != path_segment => return None,
+                        None => (),
+                    }
+                }
+                (None, None) => return Some(parameters),
+                _ => return None,
+            }
         }
     }
 }
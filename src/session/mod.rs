@@ -0,0 +1,335 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::{
+    cookie_jar::CookieJar,
+    request::Request,
+    response::Response,
+    router::{Middleware, Next},
+    Error,
+};
+
+mod store;
+
+pub use store::{FileStore, MemoryStore, SessionStore};
+
+/// Key-value data stored in a session.
+pub type SessionData = HashMap<String, String>;
+
+const SESSION_ID_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionStatus {
+    New,
+    Unchanged,
+    Modified,
+    Regenerated,
+    Destroyed,
+}
+
+#[derive(Debug)]
+struct SessionState {
+    id: String,
+    previous_id: Option<String>,
+    data: SessionData,
+    status: SessionStatus,
+}
+
+/// Handle to the session of a request, available through [Request::session] when a [SessionMiddleware] is attached to the router.
+///
+/// Cloning the handle doesn't copy the session, all the clones refer to the same session data.
+#[derive(Clone)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+impl Session {
+    fn new(id: String, data: SessionData, status: SessionStatus) -> Self {
+        Session {
+            state: Arc::new(Mutex::new(SessionState {
+                id,
+                previous_id: None,
+                data,
+                status,
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn mark_modified(state: &mut SessionState) {
+        if state.status == SessionStatus::Unchanged {
+            state.status = SessionStatus::Modified;
+        }
+    }
+
+    /// Returns the id of the session.
+    pub fn id(&self) -> String {
+        self.state().id.clone()
+    }
+
+    /// Returns the value stored under the given key.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.state().data.get(key).cloned()
+    }
+
+    /// Stores a value under the given key. Returns the previous value if the key already exists.
+    pub fn set(&self, key: &str, value: &str) -> Option<String> {
+        let mut state = self.state();
+        Self::mark_modified(&mut state);
+        state.data.insert(String::from(key), String::from(value))
+    }
+
+    /// Removes the value stored under the given key. Returns the value if the key exists.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut state = self.state();
+        Self::mark_modified(&mut state);
+        state.data.remove(key)
+    }
+
+    /// Returns true if the session contains the key.
+    pub fn contains(&self, key: &str) -> bool {
+        self.state().data.contains_key(key)
+    }
+
+    /// Removes all the values of the session, keeping its id.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        Self::mark_modified(&mut state);
+        state.data.clear();
+    }
+
+    /// Gives the session a new id while keeping its data. Should be called when the privileges of the user change (e.g. after logging in) in order to prevent session fixation attacks.
+    pub fn regenerate(&self) -> Result<(), Error> {
+        let new_id = generate_session_id()?;
+
+        let mut state = self.state();
+
+        if state.status != SessionStatus::New && state.previous_id.is_none() {
+            state.previous_id = Some(state.id.clone());
+        }
+
+        state.id = new_id;
+        state.status = SessionStatus::Regenerated;
+
+        Ok(())
+    }
+
+    /// Destroys the session, removing it from the store and from the client.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.status = SessionStatus::Destroyed;
+    }
+}
+
+impl Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+
+        f.debug_struct("Session")
+            .field("id", &state.id)
+            .field("data", &state.data)
+            .finish()
+    }
+}
+
+/// Two handles are equal if they refer to the same session.
+impl PartialEq for Session {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl Eq for Session {}
+
+/// Middleware that loads the session of each request from a [SessionStore] and saves it back once the handler returns.
+///
+/// The session id is sent to the client as a cookie, which is signed when a [CookieJar] is given.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::{
+///     request::Method,
+///     router::{Route, Router},
+///     session::{MemoryStore, SessionMiddleware},
+/// };
+///
+/// let mut router = Router::new(String::from("/"));
+///
+/// router.add_middleware(SessionMiddleware::new(MemoryStore::new()));
+///
+/// router.handle_route(Route::new(Method::GET, "/visits"), |req, mut res| {
+///     let session = req.session().unwrap();
+///
+///     let visits = session.get("visits").and_then(|v| v.parse::<u32>().ok()).unwrap_or(0) + 1;
+///     session.set("visits", &visits.to_string());
+///
+///     res.set_body_string(format!("Visits: {}", visits));
+///     res
+/// });
+/// ```
+pub struct SessionMiddleware {
+    store: Arc<dyn SessionStore>,
+    cookie_name: String,
+    ttl: Duration,
+    cookie_jar: Option<CookieJar>,
+}
+
+impl SessionMiddleware {
+    /// Generates a new session middleware that keeps the sessions in the given store. By default the sessions expire after 30 minutes of inactivity and the id is stored in the `session_id` cookie.
+    pub fn new<S>(store: S) -> Self
+    where
+        S: SessionStore + 'static,
+    {
+        SessionMiddleware {
+            store: Arc::new(store),
+            cookie_name: String::from("session_id"),
+            ttl: Duration::from_secs(30 * 60),
+            cookie_jar: None,
+        }
+    }
+
+    /// Sets the name of the cookie that contains the session id.
+    pub fn set_cookie_name(&mut self, cookie_name: &str) {
+        self.cookie_name = String::from(cookie_name);
+    }
+
+    /// Sets the time after which a session expires if it isn't used.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Signs the session id cookie with the given cookie jar.
+    pub fn set_cookie_jar(&mut self, cookie_jar: CookieJar) {
+        self.cookie_jar = Some(cookie_jar);
+    }
+
+    fn load_session(&self, request: &Request) -> Result<Session, Error> {
+        let id = match &self.cookie_jar {
+            Some(jar) => request.cookies.get_signed(jar, &self.cookie_name),
+            None => request.cookies.get(&self.cookie_name).cloned(),
+        };
+
+        if let Some(id) = id.filter(|id| is_valid_session_id(id)) {
+            if let Some(data) = self.store.load(&id)? {
+                return Ok(Session::new(id, data, SessionStatus::Unchanged));
+            }
+        }
+
+        Ok(Session::new(
+            generate_session_id()?,
+            SessionData::new(),
+            SessionStatus::New,
+        ))
+    }
+
+    fn set_cookie(&self, response: &mut Response, id: &str) -> Result<(), Error> {
+        let value = match &self.cookie_jar {
+            Some(jar) => jar.sign(&self.cookie_name, id),
+            None => String::from(id),
+        };
+
+        let max_age = format!("Max-Age={}", self.ttl.as_secs());
+
+        response.set_cookie(
+            &self.cookie_name,
+            &value,
+            &["Path=/", &max_age, "HttpOnly", "SameSite=Lax"],
+        )
+    }
+
+    fn store_session(&self, session: &Session, response: &mut Response) -> Result<(), Error> {
+        let state = session.state();
+
+        if let Some(previous_id) = &state.previous_id {
+            self.store.destroy(previous_id)?;
+        }
+
+        match state.status {
+            SessionStatus::Destroyed => {
+                self.store.destroy(&state.id)?;
+                response.set_cookie(&self.cookie_name, "", &["Path=/", "Max-Age=0", "HttpOnly"])?;
+            }
+            SessionStatus::New if state.data.is_empty() => (),
+            _ => {
+                self.store.save(&state.id, &state.data, self.ttl)?;
+                self.set_cookie(response, &state.id)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Debug for SessionMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionMiddleware")
+            .field("cookie_name", &self.cookie_name)
+            .field("ttl", &self.ttl)
+            .field("cookie_jar", &self.cookie_jar)
+            .finish()
+    }
+}
+
+impl Middleware for SessionMiddleware {
+    fn handle(&self, mut request: Request, next: Next) -> Result<Response, Error> {
+        let session = self.load_session(&request)?;
+
+        request.session = Some(session.clone());
+
+        let mut response = next(request)?;
+
+        self.store_session(&session, &mut response)?;
+
+        Ok(response)
+    }
+
+    fn name(&self) -> &str {
+        "SessionMiddleware"
+    }
+}
+
+fn generate_session_id() -> Result<String, Error> {
+    let mut id = [0; SESSION_ID_LENGTH];
+
+    getrandom::getrandom(&mut id)
+        .map_err(|err| Error::SessionError(SessionError::RandomUnavailable(err.to_string())))?;
+
+    Ok(URL_SAFE_NO_PAD.encode(id))
+}
+
+fn is_valid_session_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Errors that can occur when handling sessions.
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    /// The operating system couldn't provide random bytes to generate a session id.
+    #[error("Random source unavailable: {0}")]
+    RandomUnavailable(String),
+
+    /// The session id contains characters that aren't allowed.
+    #[error("Invalid session id: {0}")]
+    InvalidSessionId(String),
+
+    /// The session store can't be used.
+    #[error("Session store unavailable: {0}")]
+    StoreUnavailable(String),
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use super::{is_valid_session_id, SessionData, SessionError};
+use crate::Error;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+type SessionMap = HashMap<String, (SessionData, Instant)>;
+
+/// Storage backend of the [super::SessionMiddleware]. Stores the data of each session under its id.
+pub trait SessionStore: Send + Sync {
+    /// Returns the data of the session, or None if it doesn't exist or has expired.
+    fn load(&self, id: &str) -> Result<Option<SessionData>, Error>;
+
+    /// Stores the data of the session, which will expire after the given time to live.
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<(), Error>;
+
+    /// Removes the session from the store.
+    fn destroy(&self, id: &str) -> Result<(), Error>;
+}
+
+/// Stores the sessions in memory, they will be lost when the server stops. Expired sessions are evicted periodically when saving new data.
+#[derive(Debug)]
+pub struct MemoryStore {
+    sessions: Mutex<SessionMap>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    /// Generates a new empty memory store.
+    pub fn new() -> Self {
+        MemoryStore {
+            sessions: Mutex::new(HashMap::new()),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Removes all the expired sessions from the store.
+    pub fn evict_expired(&self) -> Result<(), Error> {
+        let now = Instant::now();
+
+        self.lock()?.retain(|_, (_, expires_at)| *expires_at > now);
+
+        Ok(())
+    }
+
+    /// Returns the number of sessions in the store, including the expired ones that haven't been evicted yet.
+    pub fn len(&self) -> usize {
+        self.sessions
+            .lock()
+            .map(|sessions| sessions.len())
+            .unwrap_or(0)
+    }
+
+    /// Returns true if the store contains no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, SessionMap>, Error> {
+        self.sessions.lock().map_err(|_| {
+            Error::SessionError(SessionError::StoreUnavailable(String::from(
+                "memory store lock poisoned",
+            )))
+        })
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Result<Option<SessionData>, Error> {
+        let mut sessions = self.lock()?;
+
+        match sessions.get(id) {
+            Some((data, expires_at)) if *expires_at > Instant::now() => Ok(Some(data.clone())),
+            Some(_) => {
+                sessions.remove(id);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<(), Error> {
+        if sweep_due(&self.last_sweep) {
+            self.evict_expired()?;
+        }
+
+        self.lock()?
+            .insert(String::from(id), (data.clone(), Instant::now() + ttl));
+
+        Ok(())
+    }
+
+    fn destroy(&self, id: &str) -> Result<(), Error> {
+        self.lock()?.remove(id);
+        Ok(())
+    }
+}
+
+/// Stores each session in a file inside the given directory, so sessions survive server restarts. Expired sessions are removed when loaded and periodically when saving new data.
+///
+/// The first line of each file contains the expiration time as seconds since the UNIX epoch, followed by a line per key-value pair, both encoded in base64.
+#[derive(Debug)]
+pub struct FileStore {
+    directory: PathBuf,
+    last_sweep: Mutex<Instant>,
+}
+
+impl FileStore {
+    /// Generates a new file store, creating the directory if it doesn't exist.
+    pub fn new<P>(directory: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::create_dir_all(&directory)?;
+
+        Ok(FileStore {
+            directory: PathBuf::from(directory.as_ref()),
+            last_sweep: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Removes all the expired sessions from the directory.
+    pub fn evict_expired(&self) -> Result<(), Error> {
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+
+            let is_session_file = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(is_valid_session_id);
+
+            if is_session_file && Self::read_file(&path)?.is_none() {
+                Self::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        is_valid_session_id(id).then(|| self.directory.join(id))
+    }
+
+    fn read_file(path: &Path) -> Result<Option<SessionData>, Error> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut lines = content.lines();
+
+        let expires_at = lines
+            .next()
+            .and_then(|line| line.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        if expires_at <= unix_time() {
+            return Ok(None);
+        }
+
+        let mut data = SessionData::new();
+
+        for line in lines {
+            let decoded_pair = line.split_once(' ').and_then(|(key, value)| {
+                let key = String::from_utf8(URL_SAFE_NO_PAD.decode(key).ok()?).ok()?;
+                let value = String::from_utf8(URL_SAFE_NO_PAD.decode(value).ok()?).ok()?;
+                Some((key, value))
+            });
+
+            match decoded_pair {
+                Some((key, value)) => {
+                    data.insert(key, value);
+                }
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(data))
+    }
+
+    fn remove_file(path: &Path) -> Result<(), Error> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> Result<Option<SessionData>, Error> {
+        let path = match self.path(id) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let data = Self::read_file(&path)?;
+
+        if data.is_none() {
+            Self::remove_file(&path)?;
+        }
+
+        Ok(data)
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) -> Result<(), Error> {
+        if sweep_due(&self.last_sweep) {
+            self.evict_expired()?;
+        }
+
+        let path = self
+            .path(id)
+            .ok_or_else(|| Error::SessionError(SessionError::InvalidSessionId(String::from(id))))?;
+
+        let mut content = (unix_time() + ttl.as_secs()).to_string();
+
+        for (key, value) in data {
+            content.push('\n');
+            content.push_str(&URL_SAFE_NO_PAD.encode(key));
+            content.push(' ');
+            content.push_str(&URL_SAFE_NO_PAD.encode(value));
+        }
+
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, content)?;
+        fs::rename(&temporary_path, &path)?;
+
+        Ok(())
+    }
+
+    fn destroy(&self, id: &str) -> Result<(), Error> {
+        match self.path(id) {
+            Some(path) => Self::remove_file(&path),
+            None => Ok(()),
+        }
+    }
+}
+
+fn sweep_due(last_sweep: &Mutex<Instant>) -> bool {
+    match last_sweep.lock() {
+        Ok(mut last_sweep) if last_sweep.elapsed() >= SWEEP_INTERVAL => {
+            *last_sweep = Instant::now();
+            true
+        }
+        _ => false,
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
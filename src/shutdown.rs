@@ -0,0 +1,136 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::Duration,
+};
+
+/// Cloneable handle used to stop an [crate::HttpServer] from any thread, generated with [crate::HttpServer::shutdown_handle].
+///
+/// # Example
+///
+/// ```no_run
+/// use std::{thread, time::Duration};
+///
+/// use servidor_http::{HttpServer, router::Router};
+///
+/// let mut server = HttpServer::new(8080).unwrap();
+/// server.attach_router(Router::new(String::from("/")));
+///
+/// let shutdown_handle = server.shutdown_handle();
+///
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(60));
+///     shutdown_handle.shutdown();
+/// });
+///
+/// server.listen().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    state: Arc<ShutdownState>,
+}
+
+#[derive(Debug, Default)]
+struct ShutdownState {
+    shutting_down: AtomicBool,
+    connections: Mutex<usize>,
+    connections_closed: Condvar,
+    shutdown_requested: Condvar,
+    #[cfg(feature = "async")]
+    shutdown_started: tokio::sync::Notify,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Self {
+        ShutdownHandle {
+            state: Arc::new(ShutdownState::default()),
+        }
+    }
+
+    /// Starts the shutdown of the server: it stops accepting connections, closes the idle keep-alive connections and waits for the requests being handled to finish (check [crate::HttpServer::set_shutdown_timeout]).
+    pub fn shutdown(&self) {
+        self.state.shutting_down.store(true, Ordering::SeqCst);
+
+        // Notified while holding the lock, so a thread that just checked the flag is already waiting
+        let _connections = self.connections();
+        self.state.shutdown_requested.notify_all();
+
+        #[cfg(feature = "async")]
+        self.state.shutdown_started.notify_waiters();
+    }
+
+    /// Returns true once the shutdown of the server has started.
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Blocks the thread until the shutdown of the server starts.
+    pub(crate) fn wait_for_shutdown(&self) {
+        let _connections = self
+            .state
+            .shutdown_requested
+            .wait_while(self.connections(), |_| !self.is_shutting_down())
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+
+    /// Waits until the shutdown of the server starts.
+    #[cfg(feature = "async")]
+    pub(crate) async fn shutting_down(&self) {
+        let notified = self.state.shutdown_started.notified();
+        tokio::pin!(notified);
+
+        // Registered before checking the flag, so a shutdown started in between isn't missed
+        notified.as_mut().enable();
+
+        if !self.is_shutting_down() {
+            notified.await;
+        }
+    }
+
+    /// Registers an open connection, which is considered closed once the guard is dropped.
+    pub(crate) fn connection_guard(&self) -> ConnectionGuard {
+        *self.connections() += 1;
+
+        ConnectionGuard {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Waits until every connection is closed or the timeout expires. Returns true if every connection was closed.
+    pub(crate) fn wait_for_connections(&self, timeout: Duration) -> bool {
+        let (connections, _) = self
+            .state
+            .connections_closed
+            .wait_timeout_while(self.connections(), timeout, |connections| *connections > 0)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        *connections == 0
+    }
+
+    fn connections(&self) -> std::sync::MutexGuard<'_, usize> {
+        self.state
+            .connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Keeps a connection registered in the [ShutdownHandle] while it's alive.
+pub(crate) struct ConnectionGuard {
+    state: Arc<ShutdownState>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self
+            .state
+            .connections
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        *connections -= 1;
+        self.state.connections_closed.notify_all();
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufReader, ErrorKind, Read, Write},
+    mem,
+    net::TcpStream,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use rustls::{
+    crypto::ring::{default_provider, sign::any_supported_type},
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig, ServerConnection,
+};
+
+use crate::{
+    listener::Stream,
+    response::{IntoResponse, Status},
+    router::Router,
+    virtual_host::split_host,
+    Error,
+};
+
+/// Certificates of an HTTPS listener, check [crate::HttpServer::add_tls_listener].
+///
+/// The default certificate is used for the clients that don't send a server name (SNI) or whose server name has no certificate of its own. Every listener advertises `h2` and `http/1.1` through ALPN, so clients supporting HTTP/2 use it.
+///
+/// # Example
+///
+/// ```rust
+/// use servidor_http::tls::TlsConfig;
+///
+/// let mut config = TlsConfig::from_pem_files("tests/res/tls/localhost.pem", "tests/res/tls/localhost.key").unwrap();
+///
+/// config.add_certificate("*.example.com", "tests/res/tls/example.pem", "tests/res/tls/example.key").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    resolver: CertificateResolver,
+}
+
+impl TlsConfig {
+    /// Generates a config with the default certificate chain and private key stored in the given PEM files.
+    pub fn from_pem_files<C, K>(cert_path: C, key_path: K) -> Result<Self, Error>
+    where
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        Ok(TlsConfig {
+            resolver: CertificateResolver {
+                default_certificate: load_certified_key(cert_path.as_ref(), key_path.as_ref())?,
+                certificates: HashMap::new(),
+            },
+        })
+    }
+
+    /// Generates a config with the default certificate chain and private key given in PEM format.
+    pub fn from_pem(cert_pem: &[u8], key_pem: &[u8]) -> Result<Self, Error> {
+        Ok(TlsConfig {
+            resolver: CertificateResolver {
+                default_certificate: parse_certified_key(cert_pem, key_pem)?,
+                certificates: HashMap::new(),
+            },
+        })
+    }
+
+    /// Adds the certificate used when the client asks for the given server name, which can be a wildcard (e.g. `*.example.com`). Exact names win over wildcards.
+    pub fn add_certificate<C, K>(
+        &mut self,
+        server_name: &str,
+        cert_path: C,
+        key_path: K,
+    ) -> Result<(), Error>
+    where
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        let certified_key = load_certified_key(cert_path.as_ref(), key_path.as_ref())?;
+
+        self.resolver
+            .certificates
+            .insert(server_name.to_ascii_lowercase(), certified_key);
+
+        Ok(())
+    }
+
+    pub(crate) fn server_config(&self) -> Result<Arc<ServerConfig>, Error> {
+        let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|err| Error::TlsError(TlsError::Rustls(err.to_string())))?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(self.resolver.clone()));
+
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(Arc::new(config))
+    }
+}
+
+/// Chooses the certificate of a connection from the server name sent by the client.
+#[derive(Debug, Clone)]
+struct CertificateResolver {
+    default_certificate: Arc<CertifiedKey>,
+    certificates: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certificate = client_hello.server_name().and_then(|server_name| {
+            let server_name = server_name.to_ascii_lowercase();
+
+            self.certificates.get(&server_name).or_else(|| {
+                let (_, domain) = server_name.split_once('.')?;
+                self.certificates.get(&format!("*.{}", domain))
+            })
+        });
+
+        Some(certificate.unwrap_or(&self.default_certificate).clone())
+    }
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<Arc<CertifiedKey>, Error> {
+    let cert_pem = std::fs::read(cert_path)?;
+    let key_pem = std::fs::read(key_path)?;
+
+    parse_certified_key(&cert_pem, &key_pem)
+}
+
+fn parse_certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<Arc<CertifiedKey>, Error> {
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(cert_pem))
+        .collect::<Result<Vec<CertificateDer<'static>>, io::Error>>()
+        .map_err(|err| Error::TlsError(TlsError::InvalidCertificate(err.to_string())))?;
+
+    if certificates.is_empty() {
+        return Err(Error::TlsError(TlsError::InvalidCertificate(String::from(
+            "No certificate found",
+        ))));
+    }
+
+    let private_key: PrivateKeyDer<'static> =
+        rustls_pemfile::private_key(&mut BufReader::new(key_pem))
+            .map_err(|err| Error::TlsError(TlsError::InvalidPrivateKey(err.to_string())))?
+            .ok_or_else(|| {
+                Error::TlsError(TlsError::InvalidPrivateKey(String::from(
+                    "No private key found",
+                )))
+            })?;
+
+    let signing_key = any_supported_type(&private_key)
+        .map_err(|err| Error::TlsError(TlsError::InvalidPrivateKey(err.to_string())))?;
+
+    Ok(Arc::new(CertifiedKey::new(certificates, signing_key)))
+}
+
+/// Bytes read from the socket at once, enough for a whole TLS record.
+const RECEIVE_BUFFER_SIZE: usize = 16 * 1024 + 256;
+
+/// Time given to the client to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TLS stream of an HTTPS connection, the handshake is done with [Stream::handshake]. The TLS connection is shared with the readers returned by [Stream::try_clone_reader], and it's never locked while waiting for the socket, so the stream can be written while a reader waits for data.
+pub(crate) struct TlsStream {
+    connection: Arc<Mutex<ServerConnection>>,
+    socket: TcpStream,
+    /// Bytes read from the socket that the TLS connection didn't take yet.
+    received: Vec<u8>,
+    alpn_protocol: Option<Vec<u8>>,
+}
+
+pub(crate) fn accept(config: &Arc<ServerConfig>, stream: TcpStream) -> Result<TlsStream, Error> {
+    let connection = ServerConnection::new(config.clone())
+        .map_err(|err| Error::TlsError(TlsError::Rustls(err.to_string())))?;
+
+    Ok(TlsStream {
+        connection: Arc::new(Mutex::new(connection)),
+        socket: stream,
+        received: Vec::new(),
+        alpn_protocol: None,
+    })
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            {
+                let mut connection = lock(&self.connection);
+
+                match connection.reader().read(buf) {
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => (),
+                    result => return result,
+                }
+
+                if !self.received.is_empty() {
+                    let taken = connection.read_tls(&mut self.received.as_slice())?;
+                    self.received.drain(..taken);
+
+                    process_packets(&mut connection, &mut self.socket)?;
+                    continue;
+                }
+            }
+
+            let mut buffer = [0; RECEIVE_BUFFER_SIZE];
+            let read = self.socket.read(&mut buffer)?;
+
+            // Reading nothing tells the TLS connection the client closed the socket
+            if read == 0 {
+                lock(&self.connection).read_tls(&mut io::empty())?;
+            }
+
+            self.received.extend_from_slice(&buffer[..read]);
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut connection = lock(&self.connection);
+
+        let written = connection.writer().write(buf)?;
+        write_records(&mut connection, &mut self.socket)?;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut connection = lock(&self.connection);
+
+        connection.writer().flush()?;
+        write_records(&mut connection, &mut self.socket)?;
+
+        self.socket.flush()
+    }
+}
+
+impl Stream for TlsStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    fn try_clone_reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(TlsStream {
+            connection: self.connection.clone(),
+            socket: self.socket.try_clone()?,
+            received: mem::take(&mut self.received),
+            alpn_protocol: self.alpn_protocol.clone(),
+        }))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        let mut connection = lock(&self.connection);
+
+        connection.send_close_notify();
+        write_records(&mut connection, &mut self.socket)
+    }
+
+    fn handshake(&mut self) -> io::Result<()> {
+        let mut connection = lock(&self.connection);
+
+        self.socket.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        self.socket.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+        let mut result = Ok(());
+
+        while connection.is_handshaking() && result.is_ok() {
+            result = connection.complete_io(&mut self.socket).map(|_| ());
+        }
+
+        self.socket.set_read_timeout(None)?;
+        self.socket.set_write_timeout(None)?;
+        result?;
+
+        self.alpn_protocol = connection.alpn_protocol().map(<[u8]>::to_vec);
+
+        Ok(())
+    }
+
+    fn is_encrypted(&self) -> bool {
+        true
+    }
+
+    fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+}
+
+/// Locks the TLS connection of a stream, which stays usable if a thread panicked while holding it.
+fn lock(connection: &Mutex<ServerConnection>) -> MutexGuard<'_, ServerConnection> {
+    connection.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Processes the records taken by the TLS connection, sending the records it answers with (e.g. handshake messages or alerts).
+fn process_packets(connection: &mut ServerConnection, socket: &mut TcpStream) -> io::Result<()> {
+    let result = connection.process_new_packets();
+    write_records(connection, socket)?;
+
+    result
+        .map(|_| ())
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+/// Sends the records the TLS connection has ready to the socket.
+fn write_records(connection: &mut ServerConnection, socket: &mut TcpStream) -> io::Result<()> {
+    while connection.wants_write() {
+        connection.write_tls(socket)?;
+    }
+
+    Ok(())
+}
+
+/// Router that redirects every request to the same URL using HTTPS on the given port, used by the plain HTTP listeners added with [crate::HttpServer::add_https_redirect_listener].
+pub(crate) fn https_redirect_router(https_port: u16) -> Router {
+    let mut router = Router::new(String::from("/"));
+
+    router.handle_fallback(move |req, mut res| {
+        let host = match req.host().and_then(split_host) {
+            Some((host, _)) => host,
+            None => return Ok(Status::BadRequest.into_response()),
+        };
+
+        let target = match origin_target(req.target()) {
+            Some(target) => target,
+            None => return Ok(Status::BadRequest.into_response()),
+        };
+
+        let location = match https_port {
+            443 => format!("https://{}{}", host, target),
+            port => format!("https://{}:{}{}", host, port, target),
+        };
+
+        res.redirect_permanent(&location)?;
+        Ok::<_, Error>(res)
+    });
+
+    router
+}
+
+/// Returns the path and query of an origin-form (`/users?id=1`) or absolute-form (`http://host/users?id=1`) request target, or None for the other forms (`*` or `host:port`).
+fn origin_target(target: &str) -> Option<String> {
+    if target.starts_with('/') {
+        return Some(String::from(target));
+    }
+
+    let (scheme, rest) = target.split_once("://")?;
+
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+
+    match rest.find(['/', '?']) {
+        Some(index) if rest[index..].starts_with('/') => Some(String::from(&rest[index..])),
+        Some(index) => Some(format!("/{}", &rest[index..])),
+        None => Some(String::from("/")),
+    }
+}
+
+/// Errors that can occur when setting up TLS.
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    /// The certificate chain couldn't be read from the PEM data.
+    #[error("Invalid certificate: {0}")]
+    InvalidCertificate(String),
+
+    /// The private key couldn't be read from the PEM data, or its type isn't supported.
+    #[error("Invalid private key: {0}")]
+    InvalidPrivateKey(String),
+
+    /// Error of the TLS library.
+    #[error("TLS error: {0}")]
+    Rustls(String),
+}
@@ -0,0 +1,133 @@
+/// Removes the `.` and `..` segments of a path, following the algorithm of RFC 3986 section 5.2.4.
+pub(crate) fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output: Vec<&str> = Vec::new();
+
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../") {
+            input = rest;
+        } else if let Some(rest) = input.strip_prefix("./") {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            output.pop();
+        } else if input == "/.." {
+            input = "/";
+            output.pop();
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let segment_end = input
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| c == '/')
+                .map(|(pos, _)| pos)
+                .unwrap_or(input.len());
+            let (segment, rest) = input.split_at(segment_end);
+            output.push(segment);
+            input = rest;
+        }
+    }
+
+    output.concat()
+}
+
+/// Replaces every sequence of slashes of a path with a single slash.
+pub(crate) fn collapse_slashes(path: &str) -> String {
+    let mut collapsed = String::with_capacity(path.len());
+
+    for c in path.chars() {
+        if c != '/' || !collapsed.ends_with('/') {
+            collapsed.push(c);
+        }
+    }
+
+    collapsed
+}
+
+/// Resolves a reference (e.g. the value of a `Location` header) against the path of a request, following RFC 3986 section 5.2.2. References with a scheme or an authority are returned unchanged.
+pub(crate) fn resolve_reference(base_path: &str, reference: &str) -> String {
+    let has_scheme = reference
+        .split_once(':')
+        .map(|(scheme, _)| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+        .unwrap_or(false);
+
+    if has_scheme || reference.starts_with("//") {
+        return String::from(reference);
+    }
+
+    let base_path = base_path.split(['?', '#']).next().unwrap_or("/");
+
+    if reference.is_empty() || reference.starts_with('?') || reference.starts_with('#') {
+        return format!("{}{}", base_path, reference);
+    }
+
+    let (reference_path, suffix) = match reference.find(['?', '#']) {
+        Some(pos) => reference.split_at(pos),
+        None => (reference, ""),
+    };
+
+    let merged_path = if reference_path.starts_with('/') {
+        String::from(reference_path)
+    } else {
+        match base_path.rfind('/') {
+            Some(pos) => format!("{}{}", &base_path[..=pos], reference_path),
+            None => format!("/{}", reference_path),
+        }
+    };
+
+    format!("{}{}", remove_dot_segments(&merged_path), suffix)
+}
+
+/// Percent-encodes every character of the value except the unreserved ones, so it can be used as a path segment or a query key or value.
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::new();
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+/// Decodes the percent-encoded bytes of a path segment or a query key or value. Invalid escapes are kept as they are, and bytes that aren't valid UTF-8 are replaced.
+pub(crate) fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let escaped_byte = bytes
+            .get(position + 1..position + 3)
+            .filter(|hex| bytes[position] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped_byte {
+            Some(byte) => {
+                decoded.push(byte);
+                position += 3;
+            }
+            None => {
+                decoded.push(bytes[position]);
+                position += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
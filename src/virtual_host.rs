@@ -0,0 +1,92 @@
+use crate::{router::Router, Error, ServerError};
+
+/// Host pattern of a router attached with [crate::HttpServer::attach_host_router].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HostPattern {
+    host: String,
+    wildcard: bool,
+    port: Option<u16>,
+}
+
+impl HostPattern {
+    /// Returns true if the host and port of a `Host` header match the pattern. Requests without a port are assumed to use the default port of the listener.
+    pub(crate) fn matches(&self, host: &str, port: Option<u16>, default_port: u16) -> bool {
+        if self
+            .port
+            .is_some_and(|pattern_port| pattern_port != port.unwrap_or(default_port))
+        {
+            return false;
+        }
+
+        match self.wildcard {
+            true => host
+                .strip_suffix(self.host.as_str())
+                .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+            false => host == self.host,
+        }
+    }
+
+    /// Exact patterns are more specific than wildcards, longer wildcards are more specific than shorter ones and patterns with a port are more specific than those without.
+    fn specificity(&self) -> (bool, usize, bool) {
+        (!self.wildcard, self.host.len(), self.port.is_some())
+    }
+}
+
+impl TryFrom<&str> for HostPattern {
+    type Error = Error;
+
+    fn try_from(pattern: &str) -> Result<Self, Self::Error> {
+        let invalid_pattern =
+            || Error::ServerError(ServerError::InvalidHostPattern(String::from(pattern)));
+
+        let (host, port) = split_host(pattern).ok_or_else(invalid_pattern)?;
+
+        let (host, wildcard) = match host.strip_prefix("*.") {
+            Some(domain) => (domain, true),
+            None => (host.as_str(), false),
+        };
+
+        if host.is_empty() || host.contains('*') {
+            return Err(invalid_pattern());
+        }
+
+        Ok(HostPattern {
+            host: String::from(host),
+            wildcard,
+            port,
+        })
+    }
+}
+
+/// Splits the value of a `Host` header into the lowercase host and the port, IPv6 addresses keep their brackets (e.g. `[::1]:8080`). Returns None if the port isn't a number.
+pub(crate) fn split_host(value: &str) -> Option<(String, Option<u16>)> {
+    let value = value.trim().to_ascii_lowercase();
+
+    let port_separator = match value.rfind(']') {
+        Some(bracket) => value[bracket..].find(':').map(|pos| bracket + pos),
+        None => value.rfind(':'),
+    };
+
+    match port_separator {
+        Some(pos) => {
+            let port = value[pos + 1..].parse::<u16>().ok()?;
+            Some((String::from(&value[..pos]), Some(port)))
+        }
+        None => Some((value, None)),
+    }
+}
+
+/// Returns the router whose pattern matches the `Host` header most specifically, assuming the given default port for headers without one.
+pub(crate) fn find_host_router<'a>(
+    host_routers: &'a [(HostPattern, Router)],
+    host_header: &str,
+    default_port: u16,
+) -> Option<&'a Router> {
+    let (host, port) = split_host(host_header)?;
+
+    host_routers
+        .iter()
+        .filter(|(pattern, _)| pattern.matches(&host, port, default_port))
+        .max_by_key(|(pattern, _)| pattern.specificity())
+        .map(|(_, router)| router)
+}
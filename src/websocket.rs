@@ -0,0 +1,438 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::Arc,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    listener::BufferedStream,
+    package::Package,
+    request::Request,
+    response::{ProtocolUpgrade, Response, Status},
+    Error,
+};
+
+/// GUID appended to the `Sec-WebSocket-Key` of the handshake to compute the `Sec-WebSocket-Accept`, from RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Only version of the protocol supported, the one of RFC 6455.
+const WEBSOCKET_VERSION: &str = "13";
+
+/// Largest message a [WebSocket] receives unless [WebSocket::set_max_message_size] is used.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Largest payload of the control frames (ping, pong and close).
+const MAX_CONTROL_PAYLOAD: usize = 125;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// Close code of the connections closed normally.
+pub const CLOSE_NORMAL: u16 = 1000;
+
+/// Close code of the connections closed because the client broke the protocol.
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// Close code of the connections closed because a text message wasn't valid UTF-8.
+pub const CLOSE_INVALID_DATA: u16 = 1007;
+
+/// Close code of the connections closed because a message was bigger than the limit.
+pub const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+/// Message sent through a [WebSocket].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// UTF-8 text message.
+    Text(String),
+
+    /// Binary message.
+    Binary(Vec<u8>),
+
+    /// Ping, a pong with the same payload is sent back automatically when it's received.
+    Ping(Vec<u8>),
+
+    /// Pong, answering a ping.
+    Pong(Vec<u8>),
+
+    /// Closes the connection, with the close code and the reason if any. The close is echoed automatically when it's received.
+    Close(Option<(u16, String)>),
+}
+
+/// Frame of a WebSocket connection, already unmasked.
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Connection with a client that completed the WebSocket handshake, check [crate::router::Router::handle_websocket].
+///
+/// Fragmented messages are put together before being returned, and every frame of the client must be masked. The connection is closed with a close frame when the socket is dropped.
+pub struct WebSocket {
+    stream: BufferedStream,
+    max_message_size: usize,
+    fragments: Option<(u8, Vec<u8>)>,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl WebSocket {
+    pub(crate) fn new(stream: BufferedStream) -> Self {
+        WebSocket {
+            stream,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            fragments: None,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    /// Sets the largest message the socket receives, bigger messages close the connection with [CLOSE_MESSAGE_TOO_BIG].
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Returns the largest message the socket receives.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Waits for the next message of the client. Messages breaking the protocol close the connection with the matching close code and return the error.
+    pub fn receive(&mut self) -> Result<Message, Error> {
+        if self.close_received {
+            return Err(WebSocketError::ConnectionClosed.into());
+        }
+
+        match self.read_message() {
+            Err(Error::WebSocketError(error)) => {
+                if let Some(code) = error.close_code() {
+                    self.close_received = true;
+                    let _ = self.close(code, "");
+                }
+
+                Err(error.into())
+            }
+            result => result,
+        }
+    }
+
+    /// Sends a message to the client. Sending a close message is the same as calling [WebSocket::close].
+    pub fn send(&mut self, message: Message) -> Result<(), Error> {
+        match message {
+            Message::Text(text) => self.write_frame(OPCODE_TEXT, text.as_bytes()),
+            Message::Binary(data) => self.write_frame(OPCODE_BINARY, &data),
+            Message::Ping(data) => self.write_frame(OPCODE_PING, &data),
+            Message::Pong(data) => self.write_frame(OPCODE_PONG, &data),
+            Message::Close(Some((code, reason))) => self.close(code, &reason),
+            Message::Close(None) => self.close(CLOSE_NORMAL, ""),
+        }
+    }
+
+    /// Starts the closing handshake with the given close code and reason, no more messages can be sent afterwards.
+    pub fn close(&mut self, code: u16, reason: &str) -> Result<(), Error> {
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend_from_slice(reason.as_bytes());
+
+        self.write_frame(OPCODE_CLOSE, &payload)?;
+        self.close_sent = true;
+
+        Ok(())
+    }
+
+    fn read_message(&mut self) -> Result<Message, Error> {
+        loop {
+            let frame = self.read_frame()?;
+
+            match frame.opcode {
+                OPCODE_TEXT | OPCODE_BINARY if self.fragments.is_some() => {
+                    return Err(WebSocketError::InvalidFrame(String::from(
+                        "New message before the end of the fragmented one",
+                    ))
+                    .into())
+                }
+                OPCODE_TEXT | OPCODE_BINARY => self.fragments = Some((frame.opcode, frame.payload)),
+                OPCODE_CONTINUATION => {
+                    let (_, data) = self.fragments.as_mut().ok_or_else(|| {
+                        WebSocketError::InvalidFrame(String::from(
+                            "Continuation frame without a message to continue",
+                        ))
+                    })?;
+
+                    if data.len() + frame.payload.len() > self.max_message_size {
+                        return Err(WebSocketError::MessageTooBig(self.max_message_size).into());
+                    }
+
+                    data.extend_from_slice(&frame.payload);
+                }
+                OPCODE_PING => {
+                    self.write_frame(OPCODE_PONG, &frame.payload)?;
+                    return Ok(Message::Ping(frame.payload));
+                }
+                OPCODE_PONG => return Ok(Message::Pong(frame.payload)),
+                OPCODE_CLOSE => return self.receive_close(frame.payload),
+                opcode => {
+                    return Err(WebSocketError::InvalidFrame(format!(
+                        "Unknown opcode {:#x}",
+                        opcode
+                    ))
+                    .into())
+                }
+            }
+
+            if frame.fin {
+                if let Some((opcode, data)) = self.fragments.take() {
+                    return match opcode {
+                        OPCODE_TEXT => String::from_utf8(data)
+                            .map(Message::Text)
+                            .map_err(|_| WebSocketError::InvalidUtf8.into()),
+                        _ => Ok(Message::Binary(data)),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Reads the close code and reason of a close frame, echoing it if the socket didn't start the closing handshake.
+    fn receive_close(&mut self, payload: Vec<u8>) -> Result<Message, Error> {
+        let close = match payload.as_slice() {
+            [] => None,
+            [a, b, reason @ ..] => {
+                let reason =
+                    String::from_utf8(reason.to_vec()).map_err(|_| WebSocketError::InvalidUtf8)?;
+                Some((u16::from_be_bytes([*a, *b]), reason))
+            }
+            _ => {
+                return Err(WebSocketError::InvalidFrame(String::from(
+                    "Close frame with a 1 byte payload",
+                ))
+                .into())
+            }
+        };
+
+        self.close_received = true;
+
+        if !self.close_sent {
+            let code = close.as_ref().map_or(CLOSE_NORMAL, |(code, _)| *code);
+            self.close(code, "")?;
+        }
+
+        Ok(Message::Close(close))
+    }
+
+    fn read_frame(&mut self) -> Result<Frame, Error> {
+        let mut header = [0; 2];
+
+        match self.stream.read_exact(&mut header) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                self.close_received = true;
+                return Err(WebSocketError::ConnectionClosed.into());
+            }
+            result => result?,
+        }
+
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0f;
+
+        if header[0] & 0x70 != 0 {
+            return Err(WebSocketError::InvalidFrame(String::from("Reserved bits are set")).into());
+        }
+
+        if header[1] & 0x80 == 0 {
+            return Err(WebSocketError::UnmaskedFrame.into());
+        }
+
+        let length = match header[1] & 0x7f {
+            126 => {
+                let mut length = [0; 2];
+                self.stream.read_exact(&mut length)?;
+                u16::from_be_bytes(length) as u64
+            }
+            127 => {
+                let mut length = [0; 8];
+                self.stream.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            length => length as u64,
+        };
+
+        if opcode & 0x8 != 0 && (!fin || length > MAX_CONTROL_PAYLOAD as u64) {
+            return Err(WebSocketError::InvalidFrame(String::from(
+                "Control frames can't be fragmented nor longer than 125 bytes",
+            ))
+            .into());
+        }
+
+        if length > self.max_message_size as u64 {
+            return Err(WebSocketError::MessageTooBig(self.max_message_size).into());
+        }
+
+        let mut mask = [0; 4];
+        self.stream.read_exact(&mut mask)?;
+
+        let mut payload = vec![0; length as usize];
+        self.stream.read_exact(&mut payload)?;
+
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Frame {
+            fin,
+            opcode,
+            payload,
+        })
+    }
+
+    /// Writes an unfragmented frame, frames sent by the server are never masked.
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), Error> {
+        if self.close_sent {
+            return Err(WebSocketError::ConnectionClosed.into());
+        }
+
+        if opcode & 0x8 != 0 && payload.len() > MAX_CONTROL_PAYLOAD {
+            return Err(WebSocketError::InvalidFrame(String::from(
+                "Control frames can't be longer than 125 bytes",
+            ))
+            .into());
+        }
+
+        let mut frame = vec![0x80 | opcode];
+
+        match payload.len() {
+            length @ 0..=125 => frame.push(length as u8),
+            length @ 126..=0xffff => {
+                frame.push(126);
+                frame.extend_from_slice(&(length as u16).to_be_bytes());
+            }
+            length => {
+                frame.push(127);
+                frame.extend_from_slice(&(length as u64).to_be_bytes());
+            }
+        }
+
+        frame.extend_from_slice(payload);
+
+        let stream = self.stream.get_mut();
+        stream.write_all(&frame)?;
+        stream.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for WebSocket {
+    fn drop(&mut self) {
+        if !self.close_sent && !self.close_received {
+            let _ = self.close(CLOSE_NORMAL, "");
+        }
+
+        let _ = self.stream.get_mut().close();
+    }
+}
+
+impl std::fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("max_message_size", &self.max_message_size)
+            .field("close_sent", &self.close_sent)
+            .field("close_received", &self.close_received)
+            .finish()
+    }
+}
+
+/// Answers the WebSocket handshake of the request. Valid handshakes get a 101 Switching Protocols response that hands the connection over to the handler once it's sent.
+pub(crate) fn handshake<F>(request: Request, handler: Arc<F>) -> Response
+where
+    F: Fn(Request, WebSocket) -> Result<(), Error> + Send + Sync + 'static,
+{
+    let has_token = |header: &str, token: &str| {
+        request.find_header(header).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        })
+    };
+
+    if !has_token("Upgrade", "websocket") || !has_token("Connection", "upgrade") {
+        return Response::bad_request();
+    }
+
+    if request.find_header("Sec-WebSocket-Version") != Some(WEBSOCKET_VERSION) {
+        let mut response = Response::new(Status::UpgradeRequired);
+        response.add_header("Sec-WebSocket-Version", WEBSOCKET_VERSION);
+        return response;
+    }
+
+    let key = match request.find_header("Sec-WebSocket-Key") {
+        Some(key) if STANDARD.decode(key).is_ok_and(|key| key.len() == 16) => key,
+        _ => return Response::bad_request(),
+    };
+
+    let mut response = Response::new(Status::SwitchingProtocol);
+    response.add_header("Upgrade", "websocket");
+    response.add_header("Connection", "Upgrade");
+    response.add_header("Sec-WebSocket-Accept", &accept_key(key));
+
+    // Errors of the protocol were already answered closing the connection
+    response.upgrade = Some(ProtocolUpgrade::new(move |stream| {
+        match handler(request.clone(), WebSocket::new(stream)) {
+            Err(Error::WebSocketError(_)) => Ok(()),
+            result => result,
+        }
+    }));
+
+    response
+}
+
+/// Computes the `Sec-WebSocket-Accept` of the handshake from the `Sec-WebSocket-Key` of the client.
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+
+    STANDARD.encode(hasher.finalize())
+}
+
+/// Contains all the possible errors that can occur in a WebSocket connection.
+#[derive(Debug, thiserror::Error)]
+pub enum WebSocketError {
+    /// The client sent a frame without masking it.
+    #[error("The client sent an unmasked frame")]
+    UnmaskedFrame,
+
+    /// The client sent a frame breaking the protocol.
+    #[error("Invalid frame: {0}")]
+    InvalidFrame(String),
+
+    /// The client sent a message bigger than the limit of the socket.
+    #[error("Message bigger than the limit of {0} bytes")]
+    MessageTooBig(usize),
+
+    /// The client sent a text message or a close reason that isn't valid UTF-8.
+    #[error("Text message isn't valid UTF-8")]
+    InvalidUtf8,
+
+    /// The connection is closed, or closing, so no more messages can be exchanged.
+    #[error("The WebSocket connection is closed")]
+    ConnectionClosed,
+}
+
+impl WebSocketError {
+    /// Returns the close code the connection is closed with after the error, if the error is caused by the client.
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            WebSocketError::UnmaskedFrame | WebSocketError::InvalidFrame(_) => {
+                Some(CLOSE_PROTOCOL_ERROR)
+            }
+            WebSocketError::MessageTooBig(_) => Some(CLOSE_MESSAGE_TOO_BIG),
+            WebSocketError::InvalidUtf8 => Some(CLOSE_INVALID_DATA),
+            WebSocketError::ConnectionClosed => None,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+// Every test crate includes this module but only uses some of its helpers
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::thread::{self, JoinHandle};
+
+use servidor_http::router::Router;
+use servidor_http::{Error, HttpServer, ShutdownHandle};
+
+/// Starts a server on a free local port, returning its address, its shutdown handle and the thread it listens in.
+pub fn start_server(router: Router) -> (SocketAddr, ShutdownHandle, JoinHandle<Result<(), Error>>) {
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    (address, shutdown_handle, server_thread)
+}
+
+/// Starts an async server on a free local port, returning its address, its shutdown handle and the task it listens in. Must be called inside a tokio runtime.
+#[cfg(feature = "async")]
+pub fn start_async_server(
+    router: Router,
+) -> (
+    SocketAddr,
+    ShutdownHandle,
+    tokio::task::JoinHandle<Result<(), Error>>,
+) {
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_task = tokio::spawn(async move { server.listen_async().await });
+
+    (address, shutdown_handle, server_task)
+}
@@ -0,0 +1,216 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use servidor_http::package::Package;
+use servidor_http::request::{Method, Request};
+use servidor_http::response::{Event, EventStream};
+use servidor_http::router::{Route, Router};
+use servidor_http::websocket::Message;
+use servidor_http::HttpServer;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use common::start_async_server;
+
+fn router() -> Router {
+    let mut router = Router::new(String::from("/"));
+
+    router.handle_route(Route::new(Method::GET, "/sync"), |_, _| {
+        thread::sleep(Duration::from_millis(10));
+        "Sync"
+    });
+
+    router.handle_async_route(Route::new(Method::GET, "/async"), |_, _| async {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        "Async world"
+    });
+
+    router.handle_async_route(
+        Route::new(Method::GET, "/async/:name"),
+        |req, _| async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            format!("Async {}", req.param("name").unwrap())
+        },
+    );
+
+    router.handle_route(Route::new(Method::GET, "/events"), |_, _| {
+        let (sender, stream) = EventStream::channel();
+
+        thread::spawn(move || {
+            for id in 1..=3 {
+                thread::sleep(Duration::from_millis(20));
+                sender
+                    .send(Event::new("tick").with_id(id.to_string()))
+                    .unwrap();
+            }
+        });
+
+        stream
+    });
+
+    router.handle_websocket(Route::new(Method::GET, "/echo"), |_, mut socket| loop {
+        match socket.receive()? {
+            Message::Text(text) => socket.send(Message::Text(text))?,
+            Message::Close(_) => return Ok(()),
+            _ => (),
+        }
+    });
+
+    router
+}
+
+async fn read_response(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(stream.read_u8().await.unwrap());
+    }
+
+    let head = String::from_utf8(response.clone()).unwrap();
+    let content_length = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Length: "))
+        .map_or(0, |length| length.parse().unwrap());
+
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await.unwrap();
+    response.extend(body);
+
+    String::from_utf8(response).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_server_handles_sync_and_async_routes() {
+    let (address, shutdown_handle, server_task) = start_async_server(router());
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+
+    // Both kinds of handlers are used through the same keep-alive connection
+    for (path, body) in [
+        ("/sync", "Sync"),
+        ("/async", "Async world"),
+        ("/async/rust", "Async rust"),
+    ] {
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+            .await
+            .unwrap();
+
+        let response = read_response(&mut stream).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Connection: keep-alive\r\n"));
+        assert!(response.ends_with(body));
+    }
+
+    shutdown_handle.shutdown();
+    assert!(server_task.await.unwrap().is_ok());
+
+    // The idle connection is closed once the server shuts down
+    let mut rest = Vec::new();
+    assert_eq!(stream.read_to_end(&mut rest).await.unwrap(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_server_streams_events() {
+    let (address, shutdown_handle, server_task) = start_async_server(router());
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.contains("Content-Type: text/event-stream"));
+    assert_eq!(
+        body,
+        "id: 1\ndata: tick\n\nid: 2\ndata: tick\n\nid: 3\ndata: tick\n\n"
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_task.await.unwrap().is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_server_hands_websockets_over() {
+    let (address, shutdown_handle, server_task) = start_async_server(router());
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+
+    // The frame is sent with the handshake, so it's read by the async connection before the handover
+    let mut request = b"GET /echo HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n".to_vec();
+    let mask = [1, 2, 3, 4];
+    request.extend([0x81, 0x80 | 5]);
+    request.extend(mask);
+    request.extend(
+        b"Hello"
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    stream.write_all(&request).await.unwrap();
+
+    let head = read_response(&mut stream).await;
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+
+    let mut frame = [0; 7];
+    stream.read_exact(&mut frame).await.unwrap();
+    assert_eq!(&frame, b"\x81\x05Hello");
+
+    drop(stream);
+
+    shutdown_handle.shutdown();
+    assert!(server_task.await.unwrap().is_ok());
+}
+
+#[test]
+fn async_routes_work_with_the_threaded_server() {
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router());
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET /async/threads HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("Async threads"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn async_routes_handled_from_a_runtime_worker() {
+    let request =
+        Request::try_from("GET /async/worker HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let response = router().handle_request(request).unwrap();
+
+    assert_eq!(response.get_body().unwrap(), b"Async worker".to_vec());
+}
+
+#[tokio::test]
+async fn async_routes_handled_from_a_current_thread_runtime() {
+    let request =
+        Request::try_from("GET /async/current HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let response = router().handle_request(request).unwrap();
+
+    assert_eq!(response.get_body().unwrap(), b"Async current".to_vec());
+}
@@ -0,0 +1,92 @@
+use servidor_http::cookie_jar::{CookieJar, Key};
+use servidor_http::request::Request;
+use servidor_http::response::{Response, Status};
+
+const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+const OTHER_SECRET: &[u8] = b"fedcba9876543210fedcba9876543210";
+
+fn request_with_cookie(name: &str, value: &str) -> Request {
+    let req_str = format!("GET / HTTP/1.1\r\nCookie: {}={}\r\n", name, value);
+    Request::try_from(req_str.as_str()).unwrap()
+}
+
+#[test]
+fn signed_cookie_roundtrip() {
+    let jar = CookieJar::new(Key::from_secret(SECRET).unwrap());
+
+    let mut res = Response::new(Status::OK);
+    res.set_signed_cookie(&jar, "user", "42").unwrap();
+
+    let value = res.get_cookie("user").unwrap();
+    assert!(value.ends_with("42"));
+
+    let req = request_with_cookie("user", value);
+    assert_eq!(
+        req.cookies.get_signed(&jar, "user"),
+        Some(String::from("42"))
+    );
+}
+
+#[test]
+fn tampered_signed_cookie() {
+    let jar = CookieJar::new(Key::from_secret(SECRET).unwrap());
+
+    let signed_value = jar.sign("user", "42");
+    let tampered_value = format!("{}1", signed_value.trim_end_matches("42"));
+
+    let req = request_with_cookie("user", &tampered_value);
+    assert_eq!(req.cookies.get_signed(&jar, "user"), None);
+
+    let req = request_with_cookie("admin", &signed_value);
+    assert_eq!(req.cookies.get_signed(&jar, "admin"), None);
+}
+
+#[test]
+fn private_cookie_roundtrip() {
+    let jar = CookieJar::new(Key::from_secret(SECRET).unwrap());
+
+    let mut res = Response::new(Status::OK);
+    res.set_private_cookie(&jar, "flash", "Saved!").unwrap();
+
+    let value = res.get_cookie("flash").unwrap();
+    assert!(!value.contains("Saved!"));
+
+    let req = request_with_cookie("flash", value);
+    assert_eq!(
+        req.cookies.get_private(&jar, "flash"),
+        Some(String::from("Saved!"))
+    );
+    assert_eq!(req.cookies.get_signed(&jar, "flash"), None);
+}
+
+#[test]
+fn cookie_key_rotation() {
+    let mut jar = CookieJar::new(Key::from_secret(SECRET).unwrap());
+
+    let signed_value = jar.sign("user", "42");
+    let encrypted_value = jar.encrypt("user", "42").unwrap();
+
+    jar.rotate(Key::from_secret(OTHER_SECRET).unwrap());
+
+    assert_eq!(jar.verify("user", &signed_value), Some(String::from("42")));
+    assert_eq!(
+        jar.decrypt("user", &encrypted_value),
+        Some(String::from("42"))
+    );
+    assert_ne!(jar.sign("user", "42"), signed_value);
+
+    jar.clear_rotated_keys();
+
+    assert_eq!(jar.verify("user", &signed_value), None);
+    assert_eq!(jar.decrypt("user", &encrypted_value), None);
+}
+
+#[test]
+fn short_cookie_key() {
+    match Key::from_secret(b"short").unwrap_err() {
+        servidor_http::Error::CookieJarError(
+            servidor_http::cookie_jar::CookieJarError::KeyTooShort(5),
+        ) => (),
+        _ => unreachable!(),
+    }
+}
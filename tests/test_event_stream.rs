@@ -0,0 +1,174 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use servidor_http::request::Method;
+use servidor_http::response::{Event, EventStream};
+use servidor_http::router::{Route, Router};
+
+use common::start_server;
+
+fn request_events(address: SocketAddr, headers: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(format!("GET /events HTTP/1.1\r\nHost: localhost\r\n{}\r\n", headers).as_bytes())
+        .unwrap();
+
+    stream
+}
+
+#[test]
+fn event_stream_sends_events_after_the_handler_returns() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/events"), |req, _| {
+        let (sender, stream) = EventStream::channel();
+        let first_id = req
+            .last_event_id()
+            .and_then(|id| id.parse::<u32>().ok())
+            .map_or(1, |id| id + 1);
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+
+            sender
+                .send(Event::new("first\nline").with_id(first_id.to_string()))
+                .unwrap();
+            sender
+                .send(
+                    Event::new("second")
+                        .with_id((first_id + 1).to_string())
+                        .with_event("update"),
+                )
+                .unwrap();
+        });
+
+        stream
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = request_events(address, "Last-Event-ID: 41\r\n");
+
+    // The connection is closed once every sender is dropped
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let headers = head.split("\r\n").collect::<Vec<_>>();
+    assert_eq!(headers[0], "HTTP/1.1 200 OK");
+    assert!(headers.contains(&"Content-Type: text/event-stream"));
+    assert!(headers.contains(&"Cache-Control: no-cache"));
+    assert!(!head.contains("Content-Length"));
+    assert_eq!(
+        body,
+        "id: 42\ndata: first\ndata: line\n\nid: 43\nevent: update\ndata: second\n\n"
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn event_stream_sends_keep_alive_comments() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/events"), |_, _| {
+        let (sender, mut stream) = EventStream::channel();
+        stream.set_keep_alive_interval(Duration::from_millis(20));
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            sender.send(Event::new("done")).unwrap();
+        });
+
+        stream
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = request_events(address, "");
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(body.starts_with(": keep-alive\n\n"));
+    assert!(body.ends_with("data: done\n\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn event_sender_fails_once_the_client_disconnects() {
+    let (closed_sender, closed_receiver) = mpsc::channel();
+    let closed_sender = Mutex::new(closed_sender);
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/events"), move |_, _| {
+        let (sender, stream) = EventStream::channel();
+        let closed_sender = closed_sender.lock().unwrap().clone();
+
+        thread::spawn(move || {
+            while sender.send(Event::new("tick")).is_ok() {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            closed_sender.send(()).unwrap();
+        });
+
+        stream
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = request_events(address, "");
+
+    let mut buffer = [0; 1024];
+    let read = stream.read(&mut buffer).unwrap();
+    assert!(String::from_utf8_lossy(&buffer[..read]).starts_with("HTTP/1.1 200 OK\r\n"));
+    drop(stream);
+
+    assert!(closed_receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn event_stream_ends_when_the_server_shuts_down() {
+    let (sender_slot, sender_receiver) = mpsc::channel();
+    let sender_slot = Mutex::new(sender_slot);
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/events"), move |_, _| {
+        let (sender, stream) = EventStream::channel();
+
+        // The sender is kept alive by the test, so the stream never ends by itself
+        sender_slot.lock().unwrap().send(sender).unwrap();
+
+        stream
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = request_events(address, "");
+    let sender = sender_receiver
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap();
+    sender.send(Event::new("hello")).unwrap();
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.ends_with("data: hello\n\n"));
+}
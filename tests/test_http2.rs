@@ -0,0 +1,667 @@
+mod common;
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use servidor_http::request::{Method, Package};
+use servidor_http::response::{Event, EventStream, Status};
+use servidor_http::router::{Route, Router};
+use servidor_http::HttpServer;
+
+use common::start_server;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: Vec<u8>,
+}
+
+fn connect(address: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream.write_all(PREFACE).unwrap();
+    write_frame(&mut stream, SETTINGS, 0, 0, &[]);
+
+    stream
+}
+
+fn write_frame(stream: &mut TcpStream, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+    let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+    frame.extend_from_slice(&[kind, flags]);
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.extend_from_slice(payload);
+
+    stream.write_all(&frame).unwrap();
+}
+
+fn read_frame(stream: &mut TcpStream) -> Option<Frame> {
+    let mut header = [0; 9];
+    stream.read_exact(&mut header).ok()?;
+
+    let length = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+    let mut payload = vec![0; length];
+    stream.read_exact(&mut payload).ok()?;
+
+    Some(Frame {
+        kind: header[3],
+        flags: header[4],
+        stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]),
+        payload,
+    })
+}
+
+/// Header block of a GET request to the path, without using the dynamic table nor the Huffman code.
+fn get_request(path: &str) -> Vec<u8> {
+    let mut block = vec![0x82, 0x86, 0x04, path.len() as u8];
+    block.extend_from_slice(path.as_bytes());
+    block.extend_from_slice(&[0x01, 9]);
+    block.extend_from_slice(b"localhost");
+    block
+}
+
+/// Reads frames until the given number of streams end, returning the header block and the body of each stream in the order they ended.
+fn read_responses(stream: &mut TcpStream, count: usize) -> Vec<(u32, Vec<u8>, Vec<u8>)> {
+    let mut streams: HashMap<u32, (Vec<u8>, Vec<u8>)> = HashMap::new();
+    let mut responses = Vec::new();
+
+    while responses.len() < count {
+        let frame = read_frame(stream).expect("Connection closed before the responses ended");
+
+        let (header_block, body) = streams.entry(frame.stream_id).or_default();
+
+        match frame.kind {
+            HEADERS => header_block.extend_from_slice(&frame.payload),
+            DATA => body.extend_from_slice(&frame.payload),
+            _ => continue,
+        }
+
+        if frame.flags & END_STREAM != 0 {
+            let (header_block, body) = streams.remove(&frame.stream_id).unwrap();
+            responses.push((frame.stream_id, header_block, body));
+        }
+    }
+
+    responses
+}
+
+#[test]
+fn server_serves_http2_with_prior_knowledge() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/hello"), |req, _| {
+        format!(
+            "Hello {} over {}",
+            req.host().unwrap_or_default(),
+            req.http_version()
+        )
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &get_request("/hello"),
+    );
+
+    let responses = read_responses(&mut stream, 1);
+    let (stream_id, header_block, body) = &responses[0];
+
+    assert_eq!(*stream_id, 1);
+    // `:status: 200` is the entry 8 of the static table
+    assert_eq!(header_block[0], 0x88);
+    assert_eq!(body, b"Hello localhost over HTTP/2");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_keeps_http2_connections_open_after_handler_errors() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "Hello");
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &get_request("/missing"),
+    );
+
+    let responses = read_responses(&mut stream, 1);
+    // `:status: 404` is the entry 13 of the static table
+    assert_eq!(responses[0].1[0], 0x8d);
+
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        3,
+        &get_request("/hello"),
+    );
+
+    let responses = read_responses(&mut stream, 1);
+    assert_eq!(responses[0].0, 3);
+    assert_eq!(responses[0].2, b"Hello");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_upgrades_to_h2c() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "Hello h2c");
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQCAAAAAAIAAAAA\r\n\r\n")
+        .unwrap();
+
+    let mut response_head = Vec::new();
+    while !response_head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        response_head.push(byte[0]);
+    }
+
+    let response_head = String::from_utf8(response_head).unwrap();
+    assert!(response_head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(response_head.contains("Upgrade: h2c\r\n"));
+
+    stream.write_all(PREFACE).unwrap();
+    write_frame(&mut stream, SETTINGS, 0, 0, &[]);
+
+    let responses = read_responses(&mut stream, 1);
+    assert_eq!(responses[0].0, 1);
+    assert_eq!(responses[0].2, b"Hello h2c");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_decodes_hpack_header_blocks() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |req, _| {
+        format!(
+            "{} {:?}",
+            req.host().unwrap_or_default(),
+            req.get_header_list().get("Cache-Control")
+        )
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+
+    // Requests of the appendix C.4 of RFC 7541: Huffman encoded strings, the second request indexing the `:authority` the first one added to the dynamic table
+    let first_request = [
+        0x82, 0x86, 0x84, 0x41, 0x8c, 0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90,
+        0xf4, 0xff,
+    ];
+    let second_request = [
+        0x82, 0x86, 0x84, 0xbe, 0x58, 0x86, 0xa8, 0xeb, 0x10, 0x64, 0x9c, 0xbf,
+    ];
+
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &first_request,
+    );
+    assert_eq!(read_responses(&mut stream, 1)[0].2, b"www.example.com None");
+
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        3,
+        &second_request,
+    );
+    assert_eq!(
+        read_responses(&mut stream, 1)[0].2,
+        b"www.example.com Some(\"no-cache\")"
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_multiplexes_http2_streams() {
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let receiver = Mutex::new(receiver);
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(
+        Route::new(Method::GET, "/first"),
+        move |_, _| match receiver
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(5))
+        {
+            Ok(()) => "first",
+            Err(_) => "timed out",
+        },
+    );
+    router.handle_route(Route::new(Method::GET, "/second"), move |_, _| {
+        sender.lock().unwrap().send(()).unwrap();
+        "second"
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &get_request("/first"),
+    );
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        3,
+        &get_request("/second"),
+    );
+
+    // The first request is only answered with `first` if the second one is handled while it waits
+    let mut responses = read_responses(&mut stream, 2);
+    responses.sort();
+    assert_eq!(responses[0].2, b"first");
+    assert_eq!(responses[1].2, b"second");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_respects_http2_flow_control() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/large"), |_, _| {
+        "a".repeat(100_000)
+    });
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &get_request("/large"),
+    );
+
+    let mut received = 0;
+    while received < 65_535 {
+        let frame = read_frame(&mut stream).unwrap();
+
+        if frame.kind == DATA {
+            assert!(frame.payload.len() <= 16_384);
+            received += frame.payload.len();
+        }
+    }
+
+    assert_eq!(received, 65_535);
+
+    // The windows are exhausted, so the server waits for them to be updated
+    stream
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    assert!(read_frame(&mut stream).is_none_or(|frame| frame.kind != DATA));
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let increment = 100_000u32.to_be_bytes();
+    write_frame(&mut stream, WINDOW_UPDATE, 0, 0, &increment);
+    write_frame(&mut stream, WINDOW_UPDATE, 0, 1, &increment);
+
+    let responses = read_responses(&mut stream, 1);
+    assert_eq!(received + responses[0].2.len(), 100_000);
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_resets_malformed_http2_requests() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "Hello");
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+
+    // `:method` and `:scheme` without `:path`
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &[0x82, 0x86],
+    );
+
+    let reset = loop {
+        let frame = read_frame(&mut stream).unwrap();
+
+        if frame.kind == RST_STREAM {
+            break frame;
+        }
+    };
+
+    assert_eq!(reset.stream_id, 1);
+    assert_eq!(reset.payload, 1u32.to_be_bytes());
+
+    // The connection is still usable
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        3,
+        &get_request("/hello"),
+    );
+    assert_eq!(read_responses(&mut stream, 1)[0].2, b"Hello");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_streams_event_streams_over_http2() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/events"), |_, _| {
+        let (sender, stream) = EventStream::channel();
+
+        thread::spawn(move || {
+            for id in 1..=3 {
+                thread::sleep(Duration::from_millis(20));
+                sender
+                    .send(Event::new("tick").with_id(id.to_string()))
+                    .unwrap();
+            }
+        });
+
+        stream
+    });
+    router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "Hello");
+
+    let (address, shutdown_handle, server_thread) = start_server(router);
+
+    let mut stream = connect(address);
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        1,
+        &get_request("/events"),
+    );
+
+    // Other streams are answered while the event stream is open
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        3,
+        &get_request("/hello"),
+    );
+
+    let mut responses = read_responses(&mut stream, 2);
+    responses.sort();
+
+    assert_eq!(responses[1].2, b"Hello");
+    assert_eq!(
+        responses[0].2,
+        b"id: 1\ndata: tick\n\nid: 2\ndata: tick\n\nid: 3\ndata: tick\n\n"
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_limits_http2_request_bodies() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::POST, "/upload"), |req, _| {
+        format!("Received {}", req.get_body_string())
+    });
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_max_body_size(16);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut stream = connect(address);
+
+    // The settings of the server, and the acknowledgement of the ones of the client
+    while read_frame(&mut stream).is_some_and(|frame| frame.kind != SETTINGS || frame.flags == 0) {}
+
+    // Header block of a POST request to `/upload`
+    let mut post_request = vec![0x83, 0x86, 0x04, 7];
+    post_request.extend_from_slice(b"/upload");
+    post_request.extend_from_slice(&[0x01, 9]);
+    post_request.extend_from_slice(b"localhost");
+
+    write_frame(&mut stream, HEADERS, END_HEADERS, 1, &post_request);
+    write_frame(&mut stream, DATA, 0, 1, b"0123456789");
+
+    // The windows aren't refilled while the body is buffered
+    stream
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    assert!(read_frame(&mut stream).is_none());
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    write_frame(&mut stream, DATA, 0, 1, b"0123456789");
+
+    let mut frames = Vec::new();
+    while let Some(frame) = read_frame(&mut stream) {
+        let is_reset = frame.kind == RST_STREAM;
+        frames.push(frame);
+
+        if is_reset {
+            break;
+        }
+    }
+
+    // The body over the limit is answered with 413, then the stream is reset with NO_ERROR
+    let response = frames
+        .iter()
+        .find(|frame| frame.kind == HEADERS && frame.stream_id == 1)
+        .unwrap();
+    assert!(response.payload.starts_with(&[0x08, 3, b'4', b'1', b'3']));
+
+    let reset = frames.last().unwrap();
+    assert_eq!(reset.stream_id, 1);
+    assert_eq!(reset.payload, 0u32.to_be_bytes());
+
+    // The connection window taken by the discarded body is given back
+    assert!(frames.iter().any(|frame| frame.kind == WINDOW_UPDATE
+        && frame.stream_id == 0
+        && frame.payload == 20u32.to_be_bytes()));
+
+    // Bodies up to the limit are still handled
+    write_frame(&mut stream, HEADERS, END_HEADERS, 3, &post_request);
+    write_frame(&mut stream, DATA, END_STREAM, 3, b"0123456789abcdef");
+    assert_eq!(
+        read_responses(&mut stream, 1)[0].2,
+        b"Received 0123456789abcdef"
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_closes_http2_connections_with_oversized_header_blocks() {
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(Router::new(String::from("/")));
+
+    let (error_sender, error_receiver) = mpsc::channel();
+    let error_sender = Mutex::new(error_sender);
+    server.set_error_hook(move |error| {
+        let _ = error_sender.lock().unwrap().send(error.to_string());
+    });
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut stream = connect(address);
+
+    // A header block that never ends, sent through CONTINUATION frames until it's over 64 KiB
+    write_frame(&mut stream, HEADERS, 0, 1, &get_request("/"));
+
+    for _ in 0..4 {
+        write_frame(&mut stream, CONTINUATION, 0, 1, &[0; 16_384]);
+    }
+
+    let go_away = loop {
+        let frame = read_frame(&mut stream).unwrap();
+
+        if frame.kind == GOAWAY {
+            break frame;
+        }
+    };
+
+    // COMPRESSION_ERROR, as the header block can't be decoded anymore
+    assert_eq!(go_away.payload[4..], 0x9u32.to_be_bytes());
+    assert_eq!(
+        error_receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+        "HTTP/2 header fields larger than 65536 bytes"
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_rejects_http2_requests_before_their_body() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::POST, "/upload"), |_, _| "Uploaded");
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_max_body_size(16);
+    server.set_continue_hook(|req| match req.get_header_list().contains_key("X-Token") {
+        true => Ok(()),
+        false => Err(Status::Forbidden),
+    });
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut stream = connect(address);
+
+    // Header block of a POST request to `/upload`, followed by the given headers
+    let post_request = |headers: &[u8]| {
+        let mut block = vec![0x83, 0x86, 0x04, 7];
+        block.extend_from_slice(b"/upload");
+        block.extend_from_slice(&[0x01, 9]);
+        block.extend_from_slice(b"localhost");
+        block.extend_from_slice(headers);
+        block
+    };
+
+    // `x-token: 1` and `content-length: 1000`, over the limit
+    let mut headers = vec![0x00, 7];
+    headers.extend_from_slice(b"x-token");
+    headers.extend_from_slice(&[1, b'1', 0x0f, 0x0d, 4]);
+    headers.extend_from_slice(b"1000");
+
+    // Without `x-token`, rejected by the continue hook
+    let requests = [
+        (1, post_request(&headers), b"413"),
+        (3, post_request(&[]), b"403"),
+    ];
+
+    for (stream_id, header_block, status) in requests {
+        // No DATA frame is sent, the requests are answered from their headers alone
+        write_frame(&mut stream, HEADERS, END_HEADERS, stream_id, &header_block);
+
+        let mut response = None;
+
+        let reset = loop {
+            let frame = read_frame(&mut stream).unwrap();
+
+            match frame.kind {
+                HEADERS => response = Some(frame),
+                RST_STREAM => break frame,
+                _ => (),
+            }
+        };
+
+        let response = response.unwrap();
+        assert_eq!(response.stream_id, stream_id);
+        assert_eq!(response.payload[..2], [0x08, 3]);
+        assert_eq!(&response.payload[2..5], status);
+
+        // NO_ERROR, the client can stop sending the body
+        assert_eq!(reset.stream_id, stream_id);
+        assert_eq!(reset.payload, 0u32.to_be_bytes());
+    }
+
+    // The body the client had already sent is discarded
+    write_frame(&mut stream, DATA, END_STREAM, 1, b"0123456789");
+
+    let mut headers = vec![0x00, 7];
+    headers.extend_from_slice(b"x-token");
+    headers.extend_from_slice(&[1, b'1']);
+
+    write_frame(
+        &mut stream,
+        HEADERS,
+        END_HEADERS,
+        5,
+        &post_request(&headers),
+    );
+    write_frame(&mut stream, DATA, END_STREAM, 5, b"0123456789");
+    assert_eq!(read_responses(&mut stream, 1)[0].2, b"Uploaded");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
@@ -67,7 +67,7 @@ macro_rules! generate_request_method_type_tests {
     };
 }
 
-generate_request_method_type_tests!(get_request, GET; post_request, POST; put_request, PUT; delete_request, DELETE; head_request, HEAD);
+generate_request_method_type_tests!(get_request, GET; post_request, POST; put_request, PUT; delete_request, DELETE; head_request, HEAD; patch_request, PATCH; options_request, OPTIONS; trace_request, TRACE; connect_request, CONNECT);
 
 #[test]
 fn request_with_invalid_method() {
@@ -83,6 +83,17 @@ fn request_with_invalid_method() {
     }
 }
 
+#[test]
+fn request_with_extension_method() {
+    let req_str = "PROPFIND /index.html HTTP/1.1\n";
+    let req = request::Request::try_from(req_str).unwrap();
+
+    assert_eq!(
+        req,
+        request::Request::new(Method::Other(String::from("PROPFIND")), "/index.html", None)
+    );
+}
+
 #[test]
 fn weird_request_method() {
     let method_str = "ECHO";
@@ -124,3 +135,174 @@ fn request_with_cookies() {
     assert_eq!(req.cookies.get("cookie1").unwrap(), "value1");
     assert_eq!(req.cookies.get("cookie2").unwrap(), "value2");
 }
+
+fn parse_error(head: &[u8]) -> request::RequestError {
+    let mut buffer = head.to_vec();
+
+    match request::RequestParser::new().parse(&mut buffer) {
+        Err(servidor_http::Error::RequestError(error)) => error,
+        result => panic!("Expected an error, got {:?}", result),
+    }
+}
+
+#[test]
+fn parser_resumes_on_partial_reads() {
+    let head = b"\r\nPOST /upload?name=a HTTP/1.1\r\nHost: example.com\r\nContent-Length: 11\r\n\r\nhello world";
+    let head_length = head.len() - 11;
+
+    let mut parser = request::RequestParser::new();
+    let mut buffer = Vec::new();
+
+    // The bytes are given one by one, only the last one completes the head
+    for (i, byte) in head[..head_length].iter().enumerate() {
+        buffer.push(*byte);
+
+        let status = parser.parse(&mut buffer).unwrap();
+
+        match i + 1 == head_length {
+            true => assert_eq!(status, request::ParseStatus::Complete(head_length)),
+            false => assert_eq!(status, request::ParseStatus::Partial),
+        }
+    }
+
+    buffer.extend_from_slice(&head[head_length..]);
+    assert_eq!(
+        parser.parse(&mut buffer).unwrap(),
+        request::ParseStatus::Complete(head_length)
+    );
+
+    let parsed_head = parser.head(&buffer).unwrap();
+    assert_eq!(parsed_head.method(), "POST");
+    assert_eq!(parsed_head.target(), "/upload?name=a");
+    assert_eq!(parsed_head.http_version(), "HTTP/1.1");
+    assert_eq!(
+        parsed_head.headers(),
+        &[
+            ("Host", &b"example.com"[..]),
+            ("Content-Length", &b"11"[..])
+        ]
+    );
+    assert_eq!(parsed_head.content_length(), Some(11));
+    assert_eq!(parsed_head.as_bytes(), &head[..head_length]);
+}
+
+#[test]
+fn parser_accepts_bare_lf_and_folded_headers() {
+    let mut buffer =
+        b"GET / HTTP/1.1\nX-Folded: first\r\n\tsecond\n  third \nX-Empty:\n\nbody".to_vec();
+
+    let mut parser = request::RequestParser::new();
+    assert_eq!(
+        parser.parse(&mut buffer).unwrap(),
+        request::ParseStatus::Complete(buffer.len() - 4)
+    );
+
+    let parsed_head = parser.head(&buffer).unwrap();
+    assert_eq!(
+        parsed_head.header("x-folded"),
+        Some(&b"first  \tsecond   third"[..])
+    );
+    assert_eq!(parsed_head.header("X-Empty"), Some(&b""[..]));
+}
+
+#[test]
+fn parser_rejects_malformed_heads() {
+    assert!(matches!(
+        parse_error(b"GET / HTTP/1.1\rHost: example.com\r\n\r\n"),
+        request::RequestError::InvalidRequest(_)
+    ));
+    assert!(matches!(
+        parse_error(b"GET / HTTP/1.1\r\nHost: exa\rmple.com\r\n\r\n"),
+        request::RequestError::InvalidHeader(_)
+    ));
+    assert!(matches!(
+        parse_error(b"GET / HTTP/1.1\r\nHost : example.com\r\n\r\n"),
+        request::RequestError::InvalidHeader(_)
+    ));
+    assert!(matches!(
+        parse_error(b"GET / HTTP/1.1\r\n Host: example.com\r\n\r\n"),
+        request::RequestError::InvalidHeader(_)
+    ));
+    assert!(matches!(
+        parse_error(b"GET / HTTP/1.1\r\nX-Null: a\0b\r\n\r\n"),
+        request::RequestError::InvalidHeader(_)
+    ));
+    assert!(matches!(
+        parse_error(b"GET /  HTTP/1.1\r\n\r\n"),
+        request::RequestError::InvalidRequest(_)
+    ));
+    assert!(matches!(
+        parse_error(b"GET  HTTP/1.1\r\n\r\n"),
+        request::RequestError::NoUrlFound
+    ));
+}
+
+#[test]
+fn parser_rejects_ambiguous_body_lengths() {
+    assert!(matches!(
+        parse_error(b"POST / HTTP/1.1\r\nContent-Length: 5\r\ncontent-length: 5\r\n\r\n"),
+        request::RequestError::DuplicateContentLength
+    ));
+
+    for length in ["5, 5", "+5", "-1", "0x10", "", "99999999999999999999999"] {
+        let head = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", length);
+
+        match parse_error(head.as_bytes()) {
+            request::RequestError::InvalidContentLength(value) => assert_eq!(value, length),
+            error => panic!("Unexpected error for {:?}: {:?}", length, error),
+        }
+    }
+
+    assert!(matches!(
+        parse_error(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+        request::RequestError::UnsupportedTransferEncoding(_)
+    ));
+}
+
+#[test]
+fn parser_limits_the_head_size() {
+    let mut parser = request::RequestParser::new();
+    parser.set_max_head_size(32);
+
+    let mut buffer = b"GET / HTTP/1.1\r\nX-Long: ".to_vec();
+    assert_eq!(
+        parser.parse(&mut buffer).unwrap(),
+        request::ParseStatus::Partial
+    );
+
+    buffer.extend_from_slice(&[b'a'; 16]);
+    assert!(matches!(
+        parser.parse(&mut buffer),
+        Err(servidor_http::Error::RequestError(
+            request::RequestError::HeadTooLarge(32)
+        ))
+    ));
+}
+
+#[test]
+fn request_keeps_non_utf8_header_values() {
+    let mut data = b"GET / HTTP/1.1\r\nX-Latin: caf\xe9\r\nX-Utf8: caf\xc3\xa9\r\n\r\n".to_vec();
+
+    let mut parser = request::RequestParser::new();
+    parser.parse(&mut data).unwrap();
+    assert_eq!(
+        parser.head(&data).unwrap().header("X-Latin"),
+        Some(&b"caf\xe9"[..])
+    );
+
+    let req = request::Request::try_from(data).unwrap();
+    assert_eq!(req.get_header_list()["X-Latin"], "café");
+    assert_eq!(req.get_header_list()["X-Utf8"], "café");
+}
+
+#[test]
+fn request_with_unsupported_version() {
+    let req = request::Request::try_from("GET / HTTP/2.0\r\n\r\n");
+
+    match req.unwrap_err() {
+        servidor_http::Error::RequestError(request::RequestError::HttpVersionNotSupported(
+            version,
+        )) => assert_eq!(version, "HTTP/2.0"),
+        _ => unreachable!(),
+    }
+}
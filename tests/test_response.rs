@@ -1,5 +1,5 @@
 use servidor_http::response::Response;
-use servidor_http::response::{Package, Status};
+use servidor_http::response::{Package, ResponseError, Status};
 
 #[test]
 fn basic_response_to_string() {
@@ -75,20 +75,159 @@ fn response_with_headers_and_body_to_string() {
 #[test]
 fn response_with_cookies() {
     let mut response = Response::new(Status::OK);
-    response.set_session_cookie("test", "ok");
+    response.set_session_cookie("test", "ok").unwrap();
 
     let response_str = response.to_string();
 
     assert!(response_str.contains("Set-Cookie: test=ok"));
 }
 
+#[test]
+fn response_with_invalid_cookies() {
+    let mut response = Response::new(Status::OK);
+
+    assert!(response.set_session_cookie("", "ok").is_err());
+    assert!(response.set_session_cookie("bad name", "ok").is_err());
+    assert!(response
+        .set_session_cookie("test", "ok; Domain=evil")
+        .is_err());
+    assert!(response.set_session_cookie("test", "a b").is_err());
+    assert!(response
+        .set_session_cookie("test", "ok\r\nX-Injected: 1")
+        .is_err());
+    assert!(response
+        .set_cookie("test", "ok", &["Path=/\r\nX-Injected: 1"])
+        .is_err());
+    assert_eq!(response.get_cookie("test"), None);
+
+    response.set_session_cookie("test", "\"quoted\"").unwrap();
+    assert_eq!(response.get_cookie("test"), Some("\"quoted\""));
+}
+
 #[test]
 fn response_with_redirect() {
     let mut response = Response::new(Status::Processing);
-    response.redirect("/test");
+    response.redirect("/test").unwrap();
 
     let response_str = response.to_string();
 
     assert!(response_str.contains("HTTP/1.1 301 Moved Permanently"));
     assert!(response_str.contains("Location: /test"));
 }
+
+#[test]
+fn response_redirect_variants() {
+    let mut response = Response::new(Status::OK);
+
+    response.redirect_permanent("/new").unwrap();
+    assert_eq!(response.status, Status::PermanentRedirect);
+
+    response.redirect_temporary("/new").unwrap();
+    assert_eq!(response.status, Status::TemporaryRedirect);
+
+    response.see_other("/new").unwrap();
+    assert_eq!(response.status, Status::SeeOther);
+
+    response.found("https://example.com/new").unwrap();
+    assert_eq!(response.status, Status::Found);
+    assert!(response
+        .to_string()
+        .contains("Location: https://example.com/new"));
+}
+
+#[test]
+fn response_redirect_header_injection() {
+    let mut response = Response::new(Status::OK);
+
+    match response
+        .see_other("/new\r\nSet-Cookie: admin=1")
+        .unwrap_err()
+    {
+        servidor_http::Error::ResponseError(ResponseError::InvalidLocation(_)) => (),
+        _ => unreachable!(),
+    }
+
+    assert_eq!(response.status, Status::OK);
+    assert!(!response.has_header("Location"));
+}
+
+#[test]
+fn status_code_roundtrip() {
+    for code in 100..=599 {
+        let status = Status::try_from(code).unwrap();
+
+        assert_eq!(status.code(), code);
+        assert!(status.to_string().starts_with(&code.to_string()));
+        assert_eq!(Status::try_from(status.code()).unwrap(), status);
+    }
+
+    assert_eq!(Status::try_from(204).unwrap(), Status::NoContent);
+    assert_eq!(Status::try_from(429).unwrap(), Status::TooManyRequests);
+    assert_eq!(
+        Status::try_from(599).unwrap(),
+        Status::Other(599, String::from("Unknown"))
+    );
+
+    assert!(Status::try_from(99).is_err());
+    assert!(Status::try_from(600).is_err());
+    assert!(Status::try_from(999).is_err());
+    assert!(Status::try_from(1000).is_err());
+}
+
+#[test]
+fn status_display() {
+    assert_eq!(Status::EarlyHints.to_string(), "103 Early Hints");
+    assert_eq!(Status::NoContent.to_string(), "204 No Content");
+    assert_eq!(
+        Status::Other(299, String::from("Custom")).to_string(),
+        "299 Custom"
+    );
+}
+
+#[test]
+fn status_classes() {
+    assert!(Status::Continue.is_informational());
+    assert!(Status::NoContent.is_success());
+    assert!(Status::PermanentRedirect.is_redirect());
+    assert!(Status::TooManyRequests.is_client_error());
+    assert!(Status::GatewayTimeout.is_server_error());
+
+    assert!(!Status::OK.is_client_error());
+    assert!(!Status::NotFound.is_server_error());
+    assert!(Status::Other(299, String::from("Custom")).is_success());
+}
+
+#[test]
+fn response_builder() {
+    let response = Response::builder()
+        .status(Status::Created)
+        .header("Location", "/users/42")
+        .cookie("last_user", "42")
+        .unwrap()
+        .json(r#"{"id": 42}"#)
+        .build();
+
+    assert_eq!(response.status, Status::Created);
+    assert_eq!(response.get_cookie("last_user"), Some("42"));
+
+    let headers = response.get_header_list();
+    assert_eq!(headers.get("Location").unwrap(), "/users/42");
+    assert_eq!(headers.get("Content-Type").unwrap(), "application/json");
+
+    assert_eq!(response.get_body().unwrap(), br#"{"id": 42}"#.to_vec());
+}
+
+#[test]
+fn response_convenience_constructors() {
+    let response = Response::html("<h1>Hi</h1>");
+    assert_eq!(response.status, Status::OK);
+    assert!(response.to_string().contains("Content-Type: text/html"));
+    assert!(response.to_string().ends_with("<h1>Hi</h1>"));
+
+    let response = Response::text("Hi");
+    assert!(response.to_string().contains("Content-Type: text/plain"));
+
+    let response = Response::not_found();
+    assert_eq!(response.status, Status::NotFound);
+    assert!(response.to_string().ends_with("404 Not Found"));
+}
@@ -0,0 +1,762 @@
+use std::path::PathBuf;
+
+use servidor_http::package::Package;
+use servidor_http::request::{Method, Request};
+use servidor_http::response::{IntoResponse, Response, Status};
+use servidor_http::router::{Guard, Route, RouteKind, Router, RouterError, TrailingSlash};
+use servidor_http::session::{MemoryStore, SessionMiddleware};
+use servidor_http::Error;
+
+fn request(method: &str, path: &str) -> Request {
+    let req_str = format!("{} {} HTTP/1.1\r\n", method, path);
+    Request::try_from(req_str.as_str()).unwrap()
+}
+
+#[test]
+fn route_with_extension_method() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(
+        Route::new(Method::Other(String::from("PROPFIND")), "/files"),
+        |_, mut res| {
+            res.set_body_string(String::from("propfind"));
+            res
+        },
+    );
+
+    let res = router
+        .handle_request(request("PROPFIND", "/files"))
+        .unwrap();
+    assert_eq!(res.status, Status::OK);
+    assert!(res.to_string().ends_with("propfind"));
+}
+
+#[test]
+fn unhandled_method_not_implemented() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+
+    let mut sub_router = Router::new(String::from("/api"));
+    sub_router.handle_route(Route::new(Method::PATCH, "/item"), |_, res| res);
+    router.handle_router(sub_router);
+
+    let res = router
+        .handle_request(request("PATCH", "/api/item"))
+        .unwrap();
+    assert_eq!(res.status, Status::OK);
+
+    let res = router.handle_request(request("MKCOL", "/")).unwrap();
+    assert_eq!(res.status, Status::NotImplemented);
+
+    let res = router.handle_request(request("DELETE", "/")).unwrap();
+    assert_eq!(res.status, Status::NotImplemented);
+
+    assert!(router.handle_request(request("PATCH", "/missing")).is_err());
+
+    // A method handled anywhere in the server is implemented, even under other prefixes
+    let mut admin_router = Router::new(String::from("/admin"));
+    admin_router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    router.handle_router(admin_router);
+
+    let mut default_response = Response::new(Status::OK);
+    default_response.add_header("Server", "test");
+    router.set_default_response(default_response);
+
+    assert!(router
+        .handle_request(request("PATCH", "/admin/item"))
+        .is_err());
+
+    let res = router.handle_request(request("MKCOL", "/admin")).unwrap();
+    assert_eq!(res.status, Status::NotImplemented);
+    assert!(res.to_string().contains("Server: test\r\n"));
+}
+
+#[test]
+fn head_requests_handled_by_get_routes() {
+    let mut api_router = Router::new(String::from("/api"));
+    api_router.handle_route(Route::new(Method::GET, "/items"), |_, _| "Items");
+    api_router.handle_route(Route::new(Method::GET, "/users"), |_, _| "Users");
+    api_router.handle_route(Route::new(Method::HEAD, "/users"), |_, _| "Head");
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_router(api_router);
+
+    assert!(router.handles_method(&Method::GET));
+    assert!(router.handles_method(&Method::HEAD));
+    assert!(!router.handles_method(&Method::POST));
+
+    let res = router
+        .handle_request(request("HEAD", "/api/items"))
+        .unwrap();
+    assert_eq!(res.status, Status::OK);
+    assert!(res.to_string().ends_with("Items"));
+
+    let res = router
+        .handle_request(request("HEAD", "/api/users"))
+        .unwrap();
+    assert!(res.to_string().ends_with("Head"));
+
+    assert!(router
+        .handle_request(request("HEAD", "/api/missing"))
+        .is_err());
+
+    let res = router
+        .handle_request(request("POST", "/api/items"))
+        .unwrap();
+    assert_eq!(res.status, Status::NotImplemented);
+}
+
+#[test]
+fn relative_redirect_resolution() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::POST, "/users/new"), |_, mut res| {
+        res.see_other("../accounts/./42?created=1").unwrap();
+        res
+    });
+    router.handle_route(Route::new(Method::GET, "/users/list"), |_, mut res| {
+        res.found("page2").unwrap();
+        res
+    });
+
+    let res = router
+        .handle_request(request("POST", "/users/new"))
+        .unwrap();
+    assert!(res.to_string().contains("Location: /accounts/42?created=1"));
+
+    let res = router
+        .handle_request(request("GET", "/users/list"))
+        .unwrap();
+    assert!(res.to_string().contains("Location: /users/page2"));
+}
+
+#[test]
+fn handlers_returning_into_response() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/str"), |_, _| "Hello");
+    router.handle_route(Route::new(Method::GET, "/string"), |req, _| req.path.path);
+    router.handle_route(Route::new(Method::POST, "/tuple"), |_, _| {
+        (Status::Created, String::from("Created"))
+    });
+    router.handle_route(Route::new(Method::GET, "/bytes"), |_, _| vec![1u8, 2, 3]);
+
+    let res = router.handle_request(request("GET", "/str")).unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\nHello"));
+
+    let res = router.handle_request(request("GET", "/string")).unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n/string"));
+
+    let res = router.handle_request(request("POST", "/tuple")).unwrap();
+    assert_eq!(res.status, Status::Created);
+
+    let res = router.handle_request(request("GET", "/bytes")).unwrap();
+    assert!(res
+        .to_string()
+        .contains("Content-Type: application/octet-stream"));
+}
+
+#[test]
+fn handler_errors() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(
+        Route::new(Method::GET, "/missing"),
+        |_, mut res| -> Result<Response, Error> {
+            res.send_file("tests/res/missing.html")?;
+            Ok(res)
+        },
+    );
+    router.handle_route(Route::new(Method::GET, "/custom"), |_, _| {
+        Err::<Response, _>((Status::Forbidden, "Forbidden"))
+    });
+
+    let error = router
+        .handle_request(request("GET", "/missing"))
+        .unwrap_err();
+    assert_eq!(error.into_response().status, Status::NotFound);
+
+    let res = router.handle_request(request("GET", "/custom")).unwrap();
+    assert_eq!(res.status, Status::Forbidden);
+}
+
+#[test]
+fn router_error_handlers() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_error(|error| match error {
+        Error::RouterError(RouterError::RouteNotFound(route)) => {
+            Ok((Status::NotFound, format!("{} not found", route.path)))
+        }
+        other => Err(other),
+    });
+
+    let mut sub_router = Router::new(String::from("/files"));
+    sub_router.handle_route(
+        Route::new(Method::GET, "/missing"),
+        |_, mut res| -> Result<Response, Error> {
+            res.send_file("tests/res/missing.html")?;
+            Ok(res)
+        },
+    );
+    sub_router.handle_error(|error| match error {
+        Error::Io(_) => Ok(Response::html("<h1>File not found</h1>")),
+        other => Err(other),
+    });
+    router.handle_router(sub_router);
+
+    let res = router
+        .handle_request(request("GET", "/files/missing"))
+        .unwrap();
+    assert!(res.to_string().ends_with("<h1>File not found</h1>"));
+
+    let res = router.handle_request(request("GET", "/nowhere")).unwrap();
+    assert_eq!(res.status, Status::NotFound);
+    assert!(res.to_string().ends_with("/nowhere not found"));
+}
+
+#[test]
+fn panicking_handler() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/panic"), |_, _| -> Response {
+        panic!("Handler failed")
+    });
+
+    match router.handle_request(request("GET", "/panic")).unwrap_err() {
+        Error::RouterError(RouterError::HandlerPanicked(route, message)) => {
+            assert_eq!(route.path, "/panic");
+            assert_eq!(message, "Handler failed");
+        }
+        _ => unreachable!(),
+    }
+
+    router.handle_error(|error: Error| error.into_response());
+
+    let res = router.handle_request(request("GET", "/panic")).unwrap();
+    assert_eq!(res.status, Status::InternalServerError);
+}
+
+#[test]
+fn router_default_response() {
+    let mut router = Router::new(String::from("/"));
+
+    let mut default_response = Response::new(Status::OK);
+    default_response.add_header("Server", "servidor_http");
+    router.set_default_response(default_response);
+
+    router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+
+    let mut sub_router = Router::new(String::from("/sub"));
+    sub_router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    router.handle_router(sub_router);
+
+    let res = router.handle_request(request("GET", "/")).unwrap();
+    assert!(res.has_header("Server"));
+
+    let res = router.handle_request(request("GET", "/sub/")).unwrap();
+    assert!(res.has_header("Server"));
+}
+
+#[test]
+fn router_not_found_handlers() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    router.handle_not_found(|req, _| format!("root: {} not found", req.path.path));
+
+    let mut inheriting_router = Router::new(String::from("/inherit"));
+    inheriting_router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    router.handle_router(inheriting_router);
+
+    let mut overriding_router = Router::new(String::from("/override"));
+    overriding_router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    overriding_router.handle_not_found(|_, mut res| {
+        res.set_body_string(String::from("override: not found"));
+        res
+    });
+    router.handle_router(overriding_router);
+
+    let res = router.handle_request(request("GET", "/missing")).unwrap();
+    assert!(res.to_string().ends_with("root: /missing not found"));
+
+    let res = router
+        .handle_request(request("GET", "/inherit/missing"))
+        .unwrap();
+    assert!(res
+        .to_string()
+        .ends_with("root: /inherit/missing not found"));
+
+    let res = router
+        .handle_request(request("GET", "/override/missing"))
+        .unwrap();
+    assert_eq!(res.status, Status::NotFound);
+    assert!(res.to_string().ends_with("override: not found"));
+}
+
+#[test]
+fn router_fallback_handler() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "index");
+    router.handle_not_found(|_, _| "not found");
+    router.handle_fallback(|req, _| format!("fallback: {}", req.path.path));
+
+    let res = router.handle_request(request("GET", "/")).unwrap();
+    assert!(res.to_string().ends_with("index"));
+
+    let res = router.handle_request(request("GET", "/app/page")).unwrap();
+    assert_eq!(res.status, Status::OK);
+    assert!(res.to_string().ends_with("fallback: /app/page"));
+
+    let res = router.handle_request(request("MKCOL", "/dir")).unwrap();
+    assert!(res.to_string().ends_with("fallback: /dir"));
+}
+
+#[test]
+fn nested_routers_with_multi_segment_prefixes() {
+    let mut router = Router::new(String::from("/"));
+
+    let mut api_router = Router::new(String::from("/api"));
+    api_router.handle_route(Route::new(Method::GET, "/x"), |_, _| "api");
+
+    let mut admin_router = Router::new(String::from("/api/v2/admin"));
+    admin_router.handle_route(Route::new(Method::GET, "/users"), |req, _| {
+        format!(
+            "admin {} {} {}",
+            req.path.path,
+            req.mount_path(),
+            req.remaining_path()
+        )
+    });
+
+    let mut reports_router = Router::new(String::from("/reports/"));
+    reports_router.handle_route(Route::new(Method::GET, "/"), |req, _| {
+        format!("reports {}", req.mount_path())
+    });
+    admin_router.handle_router(reports_router);
+
+    router.handle_router(api_router);
+    router.handle_router(admin_router);
+
+    let res = router.handle_request(request("GET", "/api/x")).unwrap();
+    assert!(res.to_string().ends_with("api"));
+
+    let res = router
+        .handle_request(request("GET", "/api/v2/admin/users"))
+        .unwrap();
+    assert!(res
+        .to_string()
+        .ends_with("admin /api/v2/admin/users /api/v2/admin /users"));
+
+    let res = router
+        .handle_request(request("GET", "/api/v2/admin/reports"))
+        .unwrap();
+    assert!(res.to_string().ends_with("reports /api/v2/admin/reports"));
+
+    assert!(router.handle_request(request("GET", "/apix/x")).is_err());
+    assert!(router.handle_request(request("GET", "/api/api/x")).is_err());
+}
+
+#[test]
+fn router_route_table() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    router.handle_static("./tests/res/static");
+    router.add_middleware(SessionMiddleware::new(MemoryStore::new()));
+
+    let mut admin_router = Router::new(String::from("/api/admin"));
+    admin_router.handle_route(Route::new(Method::GET, "/"), |_, res| res);
+    admin_router.handle_route(Route::new(Method::DELETE, "/users"), |_, res| res);
+    router.handle_router(admin_router);
+
+    let route_table = router.route_table();
+    let entries = route_table.entries();
+
+    assert_eq!(entries.len(), 4);
+
+    assert_eq!(entries[0].route, Route::new(Method::GET, "/"));
+    assert_eq!(entries[1].route, Route::new(Method::GET, "/"));
+    assert!(entries
+        .iter()
+        .any(|entry| entry.kind == RouteKind::Static(PathBuf::from("./tests/res/static"))));
+
+    assert_eq!(entries[2].route, Route::new(Method::GET, "/api/admin"));
+    assert_eq!(
+        entries[3].route,
+        Route::new(Method::DELETE, "/api/admin/users")
+    );
+    assert_eq!(entries[3].kind, RouteKind::Handler);
+    assert_eq!(
+        entries[3].middlewares,
+        vec![String::from("SessionMiddleware")]
+    );
+
+    let listing = route_table.to_string();
+    assert_eq!(listing.lines().count(), 4);
+    assert!(listing.contains("DELETE  /api/admin/users  handler [SessionMiddleware]"));
+}
+
+#[test]
+fn route_parameters() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/users/:id"), |req, _| {
+        format!("user {}", req.param("id").unwrap())
+    });
+    router.handle_route(Route::new(Method::GET, "/users/me"), |_, _| "me");
+    router.handle_route(
+        Route::new(Method::GET, "/users/:id/posts/:post"),
+        |req, _| {
+            format!(
+                "post {} of {}",
+                req.param("post").unwrap(),
+                req.param("id").unwrap()
+            )
+        },
+    );
+
+    let res = router.handle_request(request("GET", "/users/42")).unwrap();
+    assert!(res.to_string().ends_with("user 42"));
+
+    let res = router.handle_request(request("GET", "/users/me")).unwrap();
+    assert!(res.to_string().ends_with("me"));
+
+    let res = router
+        .handle_request(request("GET", "/users/42/posts/7"))
+        .unwrap();
+    assert!(res.to_string().ends_with("post 7 of 42"));
+
+    assert!(router.handle_request(request("GET", "/users/")).is_err());
+    assert!(router
+        .handle_request(request("GET", "/users/42/posts"))
+        .is_err());
+}
+
+#[test]
+fn reverse_routing_round_trip() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(
+        Route::new(Method::GET, "/u/:id").with_name("u"),
+        |req, _| req.param("id").unwrap().clone(),
+    );
+
+    for value in ["a b", "a b/c", "café", "100%", "%41"] {
+        let url = router.url_for("u", &[("id", value)]).unwrap();
+        let res = router.handle_request(request("GET", &url)).unwrap();
+
+        assert!(res.to_string().ends_with(&format!("\r\n\r\n{}", value)));
+    }
+
+    // Invalid escapes are kept as they are
+    let res = router.handle_request(request("GET", "/u/100%zz")).unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n100%zz"));
+}
+
+#[test]
+fn reverse_routing() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/").with_name("index"), |_, res| {
+        res
+    });
+
+    let mut admin_router = Router::new(String::from("/admin"));
+    admin_router.handle_route(
+        Route::new(Method::GET, "/users/:id").with_name("admin_user"),
+        |_, res| res,
+    );
+
+    let mut api_router = Router::new(String::from("/api/v1"));
+    api_router.handle_router(admin_router);
+    router.handle_router(api_router);
+
+    assert_eq!(router.url_for("index", &[]).unwrap(), "/");
+    assert_eq!(
+        router.url_for("admin_user", &[("id", "42")]).unwrap(),
+        "/api/v1/admin/users/42"
+    );
+    assert_eq!(
+        router
+            .url_for(
+                "admin_user",
+                &[("id", "a b/c"), ("page", "2"), ("q", "x&y")]
+            )
+            .unwrap(),
+        "/api/v1/admin/users/a%20b%2Fc?page=2&q=x%26y"
+    );
+
+    assert!(matches!(
+        router.url_for("missing", &[]),
+        Err(Error::RouterError(RouterError::UnknownRouteName(name))) if name == "missing"
+    ));
+    assert!(matches!(
+        router.url_for("admin_user", &[]),
+        Err(Error::RouterError(RouterError::MissingRouteParameter(_, param))) if param == "id"
+    ));
+
+    let res = router
+        .handle_request(request("GET", "/api/v1/admin/users/42"))
+        .unwrap();
+    assert_eq!(res.status, Status::OK);
+
+    let table = router.route_table();
+    let entry = table
+        .entries()
+        .iter()
+        .find(|entry| entry.route.path == "/api/v1/admin/users/:id")
+        .unwrap();
+    assert_eq!(entry.route.name.as_deref(), Some("admin_user"));
+}
+
+#[test]
+fn reverse_routing_with_duplicate_names() {
+    for _ in 0..20 {
+        let mut router = Router::new(String::from("/"));
+
+        for prefix in ["/c", "/a", "/b"] {
+            let mut sub_router = Router::new(String::from(prefix));
+            sub_router.handle_route(
+                Route::new(Method::GET, "/list").with_name("list"),
+                |_, res| res,
+            );
+            router.handle_router(sub_router);
+        }
+
+        assert_eq!(router.url_for("list", &[]).unwrap(), "/a/list");
+
+        router.handle_route(
+            Route::new(Method::GET, "/list").with_name("list"),
+            |_, res| res,
+        );
+        assert_eq!(router.url_for("list", &[]).unwrap(), "/list");
+    }
+}
+
+#[test]
+fn trailing_slash_policies() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/users"), |req, _| req.path.path);
+    router.handle_route(Route::new(Method::GET, "/docs/"), |req, _| req.path.path);
+
+    let mut api_router = Router::new(String::from("/api"));
+    api_router.handle_route(Route::new(Method::POST, "/items/:id"), |req, _| {
+        format!("item {}", req.param("id").unwrap())
+    });
+    router.handle_router(api_router);
+
+    assert!(router.handle_request(request("GET", "/users/")).is_err());
+    assert!(router.handle_request(request("GET", "//users")).is_err());
+
+    router.set_trailing_slash(TrailingSlash::Match);
+
+    let res = router.handle_request(request("GET", "/users/")).unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n/users"));
+
+    let res = router.handle_request(request("GET", "/docs")).unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n/docs/"));
+
+    let res = router.handle_request(request("GET", "//users")).unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n/users"));
+
+    let res = router
+        .handle_request(request("POST", "/api//items/7/"))
+        .unwrap();
+    assert!(res.to_string().ends_with("item 7"));
+
+    router.set_trailing_slash(TrailingSlash::Redirect);
+
+    let res = router
+        .handle_request(request("GET", "/users/?page=2"))
+        .unwrap();
+    assert_eq!(res.status, Status::PermanentRedirect);
+    assert_eq!(
+        res.get_header_list().get("Location").unwrap(),
+        "/users?page=2"
+    );
+
+    // The query is kept as it was sent, with its escapes and repeated keys
+    let res = router
+        .handle_request(request("GET", "/users/?name=%C3%A9&tag=a&tag=b"))
+        .unwrap();
+    assert_eq!(
+        res.get_header_list().get("Location").unwrap(),
+        "/users?name=%C3%A9&tag=a&tag=b"
+    );
+
+    let res = router
+        .handle_request(request("POST", "/api/items/7/"))
+        .unwrap();
+    assert_eq!(res.status, Status::PermanentRedirect);
+    assert_eq!(
+        res.get_header_list().get("Location").unwrap(),
+        "/api/items/7"
+    );
+
+    let res = router.handle_request(request("GET", "//docs")).unwrap();
+    assert_eq!(res.status, Status::PermanentRedirect);
+    assert_eq!(res.get_header_list().get("Location").unwrap(), "/docs");
+
+    let res = router.handle_request(request("GET", "/users")).unwrap();
+    assert_eq!(res.status, Status::OK);
+}
+
+#[test]
+fn dot_segments_removed_before_routing() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/users"), |req, _| req.path.path);
+
+    let res = router
+        .handle_request(request("GET", "/admin/../users"))
+        .unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n/users"));
+
+    let res = router
+        .handle_request(request("GET", "/./a/b/../../users"))
+        .unwrap();
+    assert!(res.to_string().ends_with("\r\n\r\n/users"));
+
+    let res = router
+        .handle_request(request("GET", "/../../users"))
+        .unwrap();
+    assert_eq!(res.status, Status::OK);
+}
+
+#[test]
+fn accept_guards_rank_routes_deterministically() {
+    let browser_accept = "Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+    let with_header = |header: &str| {
+        let req_str = format!("GET /users HTTP/1.1\r\n{}\r\n", header);
+        Request::try_from(req_str.as_str()).unwrap()
+    };
+
+    // Every router has its own hash seeds, so the ranking can't depend on them
+    for _ in 0..20 {
+        let mut router = Router::new(String::from("/"));
+        router.handle_route(
+            Route::new(Method::GET, "/users")
+                .with_guard(Guard::Accept(String::from("application/json"))),
+            |_, _| Response::json("[]"),
+        );
+        router.handle_route(
+            Route::new(Method::GET, "/users").with_guard(Guard::Accept(String::from("text/html"))),
+            |_, _| Response::html("<ul></ul>"),
+        );
+
+        let res = router.handle_request(with_header(browser_accept)).unwrap();
+        assert!(res.to_string().ends_with("<ul></ul>"));
+
+        let res = router
+            .handle_request(with_header("Accept: text/*;q=0.5, application/json;q=0.6"))
+            .unwrap();
+        assert!(res.to_string().ends_with("[]"));
+
+        // Without preferences, the route registered first wins
+        let res = router.handle_request(with_header("X-Other: 1")).unwrap();
+        assert!(res.to_string().ends_with("[]"));
+
+        let res = router.handle_request(with_header("Accept: */*")).unwrap();
+        assert!(res.to_string().ends_with("[]"));
+    }
+}
+
+#[test]
+fn route_guards() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(
+        Route::new(Method::GET, "/users")
+            .with_guard(Guard::Accept(String::from("application/json"))),
+        |_, _| Response::json("[]"),
+    );
+    router.handle_route(
+        Route::new(Method::GET, "/users").with_guard(Guard::Accept(String::from("text/html"))),
+        |_, _| Response::html("<ul></ul>"),
+    );
+    router.handle_route(
+        Route::new(Method::POST, "/users")
+            .with_guard(Guard::ContentType(String::from("application/json"))),
+        |_, _| "json",
+    );
+    router.handle_route(
+        Route::new(Method::POST, "/users")
+            .with_guard(Guard::ContentType(String::from("application/json")))
+            .with_guard(Guard::Header(String::from("X-Version"), String::from("2"))),
+        |_, _| "json v2",
+    );
+    router.handle_route(
+        Route::new(Method::GET, "/search")
+            .with_guard(Guard::Query(String::from("format"), String::from("csv"))),
+        |_, _| "csv",
+    );
+    router.handle_route(Route::new(Method::GET, "/search"), |_, _| "default");
+
+    let with_header = |method: &str, path: &str, header: &str| {
+        let req_str = format!("{} {} HTTP/1.1\r\n{}\r\n", method, path, header);
+        Request::try_from(req_str.as_str()).unwrap()
+    };
+
+    let res = router
+        .handle_request(with_header("GET", "/users", "Accept: application/json"))
+        .unwrap();
+    assert!(res.to_string().ends_with("[]"));
+
+    let res = router
+        .handle_request(with_header(
+            "GET",
+            "/users",
+            "accept: text/html;q=0.9, application/json;q=0",
+        ))
+        .unwrap();
+    assert!(res.to_string().ends_with("<ul></ul>"));
+
+    let res = router
+        .handle_request(with_header("GET", "/users", "Accept: image/png"))
+        .unwrap();
+    assert_eq!(res.status, Status::NotAcceptable);
+
+    let res = router
+        .handle_request(with_header(
+            "POST",
+            "/users",
+            "Content-Type: application/json; charset=utf-8",
+        ))
+        .unwrap();
+    assert!(res.to_string().ends_with("json"));
+
+    let res = router
+        .handle_request(with_header(
+            "POST",
+            "/users",
+            "Content-Type: application/json\r\nX-Version: 2",
+        ))
+        .unwrap();
+    assert!(res.to_string().ends_with("json v2"));
+
+    let res = router
+        .handle_request(with_header("POST", "/users", "Content-Type: text/plain"))
+        .unwrap();
+    assert_eq!(res.status, Status::UnsupportedMediaType);
+
+    let res = router
+        .handle_request(request("GET", "/search?format=csv"))
+        .unwrap();
+    assert!(res.to_string().ends_with("csv"));
+
+    let res = router
+        .handle_request(request("GET", "/search?format=json"))
+        .unwrap();
+    assert!(res.to_string().ends_with("default"));
+
+    assert!(router
+        .route_table()
+        .to_string()
+        .contains("handler if Content-Type: application/json and X-Version: 2"));
+
+    let mut default_response = Response::new(Status::OK);
+    default_response.add_header("Server", "test");
+    router.set_default_response(default_response);
+
+    let res = router
+        .handle_request(with_header("GET", "/users", "Accept: image/png"))
+        .unwrap();
+    assert_eq!(res.status, Status::NotAcceptable);
+    assert!(res.to_string().contains("Server: test\r\n"));
+
+    router.handle_fallback(|_, _| "fallback");
+
+    let res = router
+        .handle_request(with_header("POST", "/users", "Content-Type: text/plain"))
+        .unwrap();
+    assert!(res.to_string().ends_with("fallback"));
+}
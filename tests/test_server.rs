@@ -0,0 +1,550 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use servidor_http::package::Package;
+use servidor_http::request::Method;
+use servidor_http::response::Status;
+use servidor_http::router::{Route, Router, RouterError};
+use servidor_http::{Error, HttpServer, ServerError};
+
+fn send_request(address: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn server_survives_panicking_handler() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "Still alive");
+    router.handle_route(Route::new(Method::GET, "/panic"), |req, _| {
+        let query = req.query.unwrap();
+        query.get("say").unwrap().clone()
+    });
+
+    let (sender, receiver) = mpsc::channel();
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_error_hook(move |error| {
+        if let Error::RouterError(RouterError::HandlerPanicked(route, message)) = error {
+            sender.send((route.path.clone(), message.clone())).unwrap();
+        }
+    });
+
+    thread::spawn(move || server.listen());
+
+    let response = send_request(
+        address,
+        "GET /panic HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error"));
+
+    let (path, message) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(path, "/panic");
+    assert!(message.contains("None"));
+
+    let response = send_request(
+        address,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("Still alive"));
+}
+
+#[test]
+fn server_routes_by_host() {
+    let mut default_router = Router::new(String::from("/"));
+    default_router.handle_route(Route::new(Method::GET, "/"), |_, _| "Default");
+
+    let mut api_router = Router::new(String::from("/"));
+    api_router.handle_route(Route::new(Method::GET, "/"), |_, _| "API");
+
+    let mut tenant_router = Router::new(String::from("/"));
+    tenant_router.handle_route(Route::new(Method::GET, "/"), |req, _| {
+        format!("Tenant {}", req.host().unwrap())
+    });
+
+    let mut admin_router = Router::new(String::from("/"));
+    admin_router.handle_route(Route::new(Method::GET, "/"), |_, _| "Admin");
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(default_router);
+    server
+        .attach_host_router("api.example.com", api_router)
+        .unwrap();
+    server
+        .attach_host_router("*.example.com", tenant_router)
+        .unwrap();
+    server
+        .attach_host_router("admin.example.com:8443", admin_router)
+        .unwrap();
+    server.set_error_hook(|_| ());
+
+    assert!(matches!(
+        server.attach_host_router("*.", Router::default()),
+        Err(Error::ServerError(ServerError::InvalidHostPattern(_)))
+    ));
+    assert!(matches!(
+        server.attach_host_router("example.com:http", Router::default()),
+        Err(Error::ServerError(ServerError::InvalidHostPattern(_)))
+    ));
+
+    thread::spawn(move || server.listen());
+
+    let get = |host: &str| {
+        send_request(
+            address,
+            &format!(
+                "GET / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                host
+            ),
+        )
+    };
+
+    assert!(get("api.example.com").ends_with("API"));
+    assert!(get("API.Example.com:80").ends_with("API"));
+    assert!(get("shop.example.com").ends_with("Tenant shop.example.com"));
+    assert!(get("a.b.example.com:8080").ends_with("Tenant a.b.example.com:8080"));
+    assert!(get("admin.example.com:8443").ends_with("Admin"));
+    assert!(get("admin.example.com").ends_with("Tenant admin.example.com"));
+    assert!(get("example.com").ends_with("Default"));
+    assert!(get("[::1]:8080").ends_with("Default"));
+
+    let response = send_request(address, "GET / HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+
+    let response = send_request(
+        address,
+        "GET / HTTP/1.1\r\nHost: api.example.com\r\nhost: shop.example.com\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+
+    let response = send_request(address, "GET / HTTP/1.0\r\n\r\n");
+    assert!(response.ends_with("Default"));
+}
+
+#[test]
+fn server_shuts_down_gracefully() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "Hello");
+    router.handle_route(Route::new(Method::GET, "/slow"), |_, _| {
+        thread::sleep(Duration::from_millis(500));
+        "Slow"
+    });
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_keep_alive_timeout(Duration::from_secs(30));
+    server.set_shutdown_timeout(Duration::from_secs(5));
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut idle_stream = TcpStream::connect(address).unwrap();
+    idle_stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    idle_stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+
+    let mut response = Vec::new();
+    let mut buffer = [0; 1024];
+    while !response.ends_with(b"Hello") {
+        let bytes_read = idle_stream.read(&mut buffer).unwrap();
+        assert!(bytes_read > 0);
+        response.extend_from_slice(&buffer[..bytes_read]);
+    }
+    assert!(String::from_utf8_lossy(&response).contains("Connection: keep-alive"));
+
+    let slow_request = thread::spawn(move || {
+        send_request(address, "GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+    });
+
+    thread::sleep(Duration::from_millis(200));
+    shutdown_handle.shutdown();
+    assert!(shutdown_handle.is_shutting_down());
+
+    let response = slow_request.join().unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("Connection: close"));
+    assert!(response.ends_with("Slow"));
+
+    let mut rest = String::new();
+    idle_stream.read_to_string(&mut rest).unwrap();
+    assert!(rest.is_empty());
+
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_serves_several_listeners() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "Hello");
+
+    let mut server = HttpServer::bind(("localhost", 0)).unwrap();
+    let first_address = server.local_addr().unwrap();
+    let second_address = server.add_listener("127.0.0.1:0").unwrap();
+    server.attach_router(router);
+
+    assert_ne!(first_address.port(), 0);
+    assert_ne!(second_address.port(), 0);
+    assert_eq!(
+        server.local_addrs().unwrap(),
+        vec![first_address, second_address]
+    );
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    for address in [first_address, second_address] {
+        let response = send_request(
+            address,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.ends_with("Hello"));
+    }
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn server_listens_on_unix_socket() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use servidor_http::UnixSocketOptions;
+
+    let socket_path =
+        std::env::temp_dir().join(format!("servidor_http_test_{}.sock", std::process::id()));
+    let file_path =
+        std::env::temp_dir().join(format!("servidor_http_test_{}.txt", std::process::id()));
+
+    drop(UnixListener::bind(&socket_path).unwrap());
+    std::fs::write(&file_path, "not a socket").unwrap();
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "Hello from a socket");
+
+    let mut options = UnixSocketOptions::new();
+    options.set_permissions(0o600);
+
+    let mut server = HttpServer::bind_unix(&socket_path, &options).unwrap();
+    server.attach_router(router);
+
+    assert!(matches!(
+        server.local_addr(),
+        Err(Error::ServerError(ServerError::NoTcpListener))
+    ));
+    assert!(matches!(
+        server.add_unix_listener(&file_path, &options),
+        Err(Error::ServerError(ServerError::NotASocket(_)))
+    ));
+
+    let mode = std::fs::metadata(&socket_path)
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || {
+        let result = server.listen();
+        drop(server);
+        result
+    });
+
+    let mut stream = UnixStream::connect(&socket_path).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("Hello from a socket"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+    assert!(!socket_path.exists());
+
+    std::fs::remove_file(&file_path).unwrap();
+}
+
+#[test]
+fn server_handles_expect_continue() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::POST, "/upload"), |req, _| {
+        format!("Received {}", req.get_body_string())
+    });
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_continue_hook(|req| match req.content_length() {
+        Some(length) if length > 16 => Err(Status::PayloadTooLarge),
+        _ => Ok(()),
+    });
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let connect = || {
+        let stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+    };
+
+    // The body is only sent once the server answers with 100 Continue
+    let mut stream = connect();
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n")
+        .unwrap();
+
+    let mut interim_response = [0; 25];
+    stream.read_exact(&mut interim_response).unwrap();
+    assert_eq!(&interim_response, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+    stream.write_all(b"hello").unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("Received hello"));
+
+    // Rejected requests are answered without waiting for the body
+    let mut stream = connect();
+    stream
+        .write_all(b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000000\r\nExpect: 100-continue\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413 "));
+    assert!(response.contains("Connection: close\r\n"));
+
+    let response = send_request(
+        address,
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nExpect: something-else\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 417 Expectation Failed\r\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_rejects_bodies_over_the_limit() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::POST, "/upload"), |req, _| {
+        format!("Received {}", req.get_body_string())
+    });
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_max_body_size(16);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let response = send_request(
+        address,
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 16\r\nConnection: close\r\n\r\n0123456789abcdef",
+    );
+    assert!(response.ends_with("Received 0123456789abcdef"));
+
+    // The body isn't allocated nor waited for
+    let response = send_request(
+        address,
+        "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1099511627776\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 413 "));
+    assert!(response.contains("Connection: close\r\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_parses_requests_received_in_pieces() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::POST, "/echo"), |req, _| {
+        format!(
+            "{} {}",
+            req.get_header_list()["X-Folded"],
+            req.get_body_string()
+        )
+    });
+
+    let (sender, receiver) = mpsc::channel();
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server.set_error_hook(move |error| {
+        if let Error::RequestError(error) = error {
+            sender.send(error.to_string()).unwrap();
+        }
+    });
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // The head is split in the middle of the lines and of the line breaks
+    for piece in [
+        "POST /ec",
+        "ho HTTP/1.1\r",
+        "\nHost: localhost\nX-Folded: first\r\n",
+        "  second\r\nConnection: close\r\nContent-Len",
+        "gth: 5\r\n\r",
+        "\nhello",
+    ] {
+        stream.write_all(piece.as_bytes()).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("first    second hello"));
+
+    let response = send_request(
+        address,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nContent-Length: 50\r\n\r\nhello",
+    );
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.contains("Connection: close\r\n"));
+
+    let error = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(error, "Duplicate Content-Length header");
+
+    let response = send_request(
+        address,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_sends_no_content_without_body_headers() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::DELETE, "/item"), |_, _| ());
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let response = send_request(
+        address,
+        "DELETE /item HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"));
+    assert!(!response.contains("Content-Length"));
+    assert!(!response.contains("Content-Type"));
+    assert!(response.ends_with("\r\n\r\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_answers_head_requests_without_body() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "Hello");
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let response = send_request(
+        address,
+        "HEAD / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Length: 5\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_keeps_listening_after_connection_errors() {
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |_, _| "Hello");
+
+    let mut server = HttpServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let response = send_request(
+        address,
+        "GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    drop(TcpStream::connect(address).unwrap());
+    thread::sleep(Duration::from_millis(100));
+    assert!(!server_thread.is_finished());
+
+    let response = send_request(
+        address,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.ends_with("Hello"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_shutdown_wakes_listeners_on_every_interface() {
+    let mut server = HttpServer::bind("0.0.0.0:0").unwrap();
+    server.attach_router(Router::default());
+
+    let shutdown_handle = server.shutdown_handle();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || sender.send(server.listen()).unwrap());
+
+    thread::sleep(Duration::from_millis(50));
+    shutdown_handle.shutdown();
+
+    let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(result.is_ok());
+}
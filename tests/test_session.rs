@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use servidor_http::request::{Method, Request};
+use servidor_http::response::Response;
+use servidor_http::router::{Route, Router};
+use servidor_http::session::{
+    FileStore, MemoryStore, SessionData, SessionMiddleware, SessionStore,
+};
+
+fn session_router() -> Router {
+    let mut router = Router::new(String::from("/"));
+
+    router.add_middleware(SessionMiddleware::new(MemoryStore::new()));
+
+    router.handle_route(Route::new(Method::GET, "/visits"), |req, mut res| {
+        let session = req.session().unwrap();
+
+        let visits = session
+            .get("visits")
+            .and_then(|visits| visits.parse::<u32>().ok())
+            .unwrap_or(0)
+            + 1;
+        session.set("visits", &visits.to_string());
+
+        res.set_body_string(visits.to_string());
+        res
+    });
+
+    router.handle_route(Route::new(Method::GET, "/login"), |req, res| {
+        req.session().unwrap().regenerate().unwrap();
+        res
+    });
+
+    router.handle_route(Route::new(Method::GET, "/logout"), |req, res| {
+        req.session().unwrap().destroy();
+        res
+    });
+
+    router
+}
+
+fn request(path: &str, session_id: Option<&str>) -> Request {
+    let req_str = match session_id {
+        Some(id) => format!("GET {} HTTP/1.1\r\nCookie: session_id={}\r\n", path, id),
+        None => format!("GET {} HTTP/1.1\r\n", path),
+    };
+
+    Request::try_from(req_str.as_str()).unwrap()
+}
+
+fn body(res: &Response) -> String {
+    res.to_string()
+        .split("\r\n\r\n")
+        .last()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn session_persists_between_requests() {
+    let router = session_router();
+
+    let res = router.handle_request(request("/visits", None)).unwrap();
+    assert_eq!(body(&res), "1");
+
+    let session_id = res.get_cookie("session_id").unwrap().to_string();
+
+    let res = router
+        .handle_request(request("/visits", Some(&session_id)))
+        .unwrap();
+    assert_eq!(body(&res), "2");
+    assert_eq!(res.get_cookie("session_id"), Some(session_id.as_str()));
+}
+
+#[test]
+fn unknown_session_id_starts_new_session() {
+    let router = session_router();
+
+    let res = router
+        .handle_request(request("/visits", Some("forged-id")))
+        .unwrap();
+
+    assert_eq!(body(&res), "1");
+    assert_ne!(res.get_cookie("session_id"), Some("forged-id"));
+}
+
+#[test]
+fn session_regeneration() {
+    let router = session_router();
+
+    let res = router.handle_request(request("/visits", None)).unwrap();
+    let old_id = res.get_cookie("session_id").unwrap().to_string();
+
+    let res = router
+        .handle_request(request("/login", Some(&old_id)))
+        .unwrap();
+    let new_id = res.get_cookie("session_id").unwrap().to_string();
+    assert_ne!(old_id, new_id);
+
+    let res = router
+        .handle_request(request("/visits", Some(&new_id)))
+        .unwrap();
+    assert_eq!(body(&res), "2");
+
+    let res = router
+        .handle_request(request("/visits", Some(&old_id)))
+        .unwrap();
+    assert_eq!(body(&res), "1");
+}
+
+#[test]
+fn session_destroy() {
+    let router = session_router();
+
+    let res = router.handle_request(request("/visits", None)).unwrap();
+    let session_id = res.get_cookie("session_id").unwrap().to_string();
+
+    let res = router
+        .handle_request(request("/logout", Some(&session_id)))
+        .unwrap();
+    assert!(res.to_string().contains("Max-Age=0"));
+
+    let res = router
+        .handle_request(request("/visits", Some(&session_id)))
+        .unwrap();
+    assert_eq!(body(&res), "1");
+}
+
+#[test]
+fn memory_store_expiration() {
+    let store = MemoryStore::new();
+
+    let mut data = SessionData::new();
+    data.insert(String::from("key"), String::from("value"));
+
+    store.save("expired", &data, Duration::ZERO).unwrap();
+    store.save("alive", &data, Duration::from_secs(60)).unwrap();
+
+    assert_eq!(store.load("expired").unwrap(), None);
+    assert_eq!(store.load("alive").unwrap(), Some(data));
+
+    store.evict_expired().unwrap();
+    assert_eq!(store.len(), 1);
+}
+
+#[test]
+fn file_store_roundtrip() {
+    let directory =
+        std::env::temp_dir().join(format!("servidor_http_sessions_{}", std::process::id()));
+    let store = FileStore::new(&directory).unwrap();
+
+    let mut data = SessionData::new();
+    data.insert(String::from("user"), String::from("42"));
+    data.insert(String::from("message"), String::from("multi\nline = value"));
+
+    store
+        .save("session", &data, Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(store.load("session").unwrap(), Some(data.clone()));
+
+    store.save("expired", &data, Duration::ZERO).unwrap();
+    assert_eq!(store.load("expired").unwrap(), None);
+    assert!(!directory.join("expired").exists());
+
+    assert_eq!(store.load("../session").unwrap(), None);
+
+    store.destroy("session").unwrap();
+    assert_eq!(store.load("session").unwrap(), None);
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
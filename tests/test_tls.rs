@@ -0,0 +1,296 @@
+#![cfg(feature = "tls")]
+
+use std::io::{BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rustls::crypto::ring::default_provider;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use servidor_http::request::Method;
+use servidor_http::router::{Route, Router};
+use servidor_http::tls::{TlsConfig, TlsError};
+use servidor_http::{Error, HttpServer};
+
+fn read_certificate(path: &str) -> CertificateDer<'static> {
+    let pem = std::fs::read(path).unwrap();
+    let certificate = rustls_pemfile::certs(&mut BufReader::new(pem.as_slice()))
+        .next()
+        .unwrap()
+        .unwrap();
+
+    certificate
+}
+
+fn client_config(alpn_protocols: &[&[u8]]) -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add(read_certificate("tests/res/tls/ca.pem")).unwrap();
+
+    let mut config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn_protocols
+        .iter()
+        .map(|protocol| protocol.to_vec())
+        .collect();
+
+    Arc::new(config)
+}
+
+fn connect_tls(
+    address: SocketAddr,
+    server_name: &str,
+    alpn_protocols: &[&[u8]],
+) -> StreamOwned<ClientConnection, TcpStream> {
+    let server_name = ServerName::try_from(String::from(server_name)).unwrap();
+    let connection = ClientConnection::new(client_config(alpn_protocols), server_name).unwrap();
+
+    let stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    StreamOwned::new(connection, stream)
+}
+
+fn send_tls_request(
+    address: SocketAddr,
+    server_name: &str,
+) -> (String, StreamOwned<ClientConnection, TcpStream>) {
+    let mut tls_stream = connect_tls(address, server_name, &[b"http/1.1"]);
+    tls_stream
+        .write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    tls_stream.read_to_string(&mut response).unwrap();
+
+    (response, tls_stream)
+}
+
+#[test]
+fn tls_config_rejects_invalid_pem() {
+    assert!(matches!(
+        TlsConfig::from_pem(b"not a certificate", b"not a key"),
+        Err(Error::TlsError(TlsError::InvalidCertificate(_)))
+    ));
+
+    let certificate = std::fs::read("tests/res/tls/localhost.pem").unwrap();
+    assert!(matches!(
+        TlsConfig::from_pem(&certificate, b"not a key"),
+        Err(Error::TlsError(TlsError::InvalidPrivateKey(_)))
+    ));
+}
+
+#[test]
+fn server_serves_https_with_sni() {
+    let mut config =
+        TlsConfig::from_pem_files("tests/res/tls/localhost.pem", "tests/res/tls/localhost.key")
+            .unwrap();
+    config
+        .add_certificate(
+            "*.example.com",
+            "tests/res/tls/example.pem",
+            "tests/res/tls/example.key",
+        )
+        .unwrap();
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "Hello over TLS");
+
+    let mut server = HttpServer::bind_tls("127.0.0.1:0", &config).unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let (response, stream) = send_tls_request(address, "localhost");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("Hello over TLS"));
+    assert_eq!(stream.conn.alpn_protocol(), Some(b"http/1.1".as_slice()));
+    assert_eq!(
+        stream.conn.peer_certificates().unwrap()[0],
+        read_certificate("tests/res/tls/localhost.pem")
+    );
+
+    let (response, stream) = send_tls_request(address, "shop.example.com");
+    assert!(response.ends_with("Hello over TLS"));
+    assert_eq!(
+        stream.conn.peer_certificates().unwrap()[0],
+        read_certificate("tests/res/tls/example.pem")
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn https_host_patterns_default_to_port_443() {
+    let config =
+        TlsConfig::from_pem_files("tests/res/tls/localhost.pem", "tests/res/tls/localhost.key")
+            .unwrap();
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "Default host");
+
+    let mut https_router = Router::new(String::from("/"));
+    https_router.handle_route(Route::new(Method::GET, "/hello"), |_, _| "HTTPS host");
+
+    let mut server = HttpServer::bind_tls("127.0.0.1:0", &config).unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+    server
+        .attach_host_router("localhost:443", https_router)
+        .unwrap();
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    // The request is sent with `Host: localhost`, without a port
+    let (response, _) = send_tls_request(address, "localhost");
+    assert!(response.ends_with("HTTPS host"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_negotiates_http2_through_alpn() {
+    let config =
+        TlsConfig::from_pem_files("tests/res/tls/localhost.pem", "tests/res/tls/localhost.key")
+            .unwrap();
+
+    let mut router = Router::new(String::from("/"));
+    router.handle_route(Route::new(Method::GET, "/"), |req, _| {
+        format!("Hello over {}", req.http_version())
+    });
+
+    let mut server = HttpServer::bind_tls("127.0.0.1:0", &config).unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(router);
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let mut stream = connect_tls(address, "localhost", &[b"h2", b"http/1.1"]);
+
+    // Connection preface, an empty SETTINGS frame and a HEADERS frame ending the stream 1 with `GET https://localhost/`
+    let mut request = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".to_vec();
+    request.extend_from_slice(&[0, 0, 0, 0x4, 0, 0, 0, 0, 0]);
+    request.extend_from_slice(&[0, 0, 14, 0x1, 0x5, 0, 0, 0, 1, 0x82, 0x87, 0x84, 0x01, 9]);
+    request.extend_from_slice(b"localhost");
+    stream.write_all(&request).unwrap();
+
+    assert_eq!(stream.conn.alpn_protocol(), Some(b"h2".as_slice()));
+
+    let body = loop {
+        let mut header = [0; 9];
+        stream.read_exact(&mut header).unwrap();
+
+        let mut payload =
+            vec![0; u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize];
+        stream.read_exact(&mut payload).unwrap();
+
+        // DATA frame of the stream 1
+        if header[3] == 0x0 && header[8] == 1 {
+            break payload;
+        }
+    };
+
+    assert_eq!(body, b"Hello over HTTP/2");
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_starts_http2_after_the_alpn_handshake() {
+    let config =
+        TlsConfig::from_pem_files("tests/res/tls/localhost.pem", "tests/res/tls/localhost.key")
+            .unwrap();
+
+    let mut server = HttpServer::bind_tls("127.0.0.1:0", &config).unwrap();
+    let address = server.local_addr().unwrap();
+    server.attach_router(Router::new(String::from("/")));
+    // The client disconnects without closing the HTTP/2 connection
+    server.set_error_hook(|_| ());
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    // Nothing is sent after the handshake, so the server can only know it's HTTP/2 through ALPN
+    let mut stream = connect_tls(address, "localhost", &[b"h2"]);
+
+    let mut header = [0; 9];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(stream.conn.alpn_protocol(), Some(b"h2".as_slice()));
+
+    // SETTINGS frame of the stream 0, the first frame of the server
+    assert_eq!(header[3], 0x4);
+    assert_eq!(&header[5..], &[0, 0, 0, 0]);
+
+    drop(stream);
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn server_redirects_http_to_https() {
+    let config =
+        TlsConfig::from_pem_files("tests/res/tls/localhost.pem", "tests/res/tls/localhost.key")
+            .unwrap();
+
+    let mut server = HttpServer::bind_tls("127.0.0.1:0", &config).unwrap();
+    let redirect_address = server
+        .add_https_redirect_listener("127.0.0.1:0", 8443)
+        .unwrap();
+    server.attach_router(Router::new(String::from("/")));
+
+    let shutdown_handle = server.shutdown_handle();
+    let server_thread = thread::spawn(move || server.listen());
+
+    let send_request = |request_line: &str| {
+        let mut stream = TcpStream::connect(redirect_address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(
+                format!(
+                    "{}\r\nHost: Example.com:8080\r\nConnection: close\r\n\r\n",
+                    request_line
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = send_request("POST /users?name=%C3%A9&tag=a&tag=b HTTP/1.1");
+    assert!(response.starts_with("HTTP/1.1 308 Permanent Redirect"));
+    assert!(
+        response.contains("Location: https://example.com:8443/users?name=%C3%A9&tag=a&tag=b\r\n")
+    );
+
+    let response = send_request("GET http://example.com:8080/users?id=1 HTTP/1.1");
+    assert!(response.contains("Location: https://example.com:8443/users?id=1\r\n"));
+
+    let response = send_request("GET http://example.com HTTP/1.1");
+    assert!(response.contains("Location: https://example.com:8443/\r\n"));
+
+    let response = send_request("OPTIONS * HTTP/1.1");
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
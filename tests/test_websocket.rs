@@ -0,0 +1,218 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use servidor_http::request::Method;
+use servidor_http::router::{Route, Router};
+use servidor_http::websocket::Message;
+
+use common::start_server;
+
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CONTINUATION: u8 = 0x0;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+/// Key and accept of the handshake example of RFC 6455.
+const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+const ACCEPT: &str = "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=";
+
+fn router() -> Router {
+    let mut router = Router::new(String::from("/"));
+    router.handle_websocket(Route::new(Method::GET, "/echo"), |_, mut socket| {
+        socket.set_max_message_size(1024);
+
+        loop {
+            match socket.receive()? {
+                Message::Text(text) => socket.send(Message::Text(text))?,
+                Message::Binary(data) => socket.send(Message::Binary(data))?,
+                Message::Close(_) => return Ok(()),
+                _ => (),
+            }
+        }
+    });
+
+    router
+}
+
+fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        head.push(byte[0]);
+    }
+
+    String::from_utf8(head).unwrap()
+}
+
+fn connect(address: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    stream
+        .write_all(format!("GET /echo HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n", KEY).as_bytes())
+        .unwrap();
+
+    let head = read_head(&mut stream);
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+    assert!(head.contains(&format!("Sec-WebSocket-Accept: {}\r\n", ACCEPT)));
+
+    stream
+}
+
+fn write_frame(stream: &mut TcpStream, fin: bool, opcode: u8, payload: &[u8], masked: bool) {
+    let mut frame = vec![if fin { 0x80 | opcode } else { opcode }];
+    let mask_bit = if masked { 0x80 } else { 0 };
+
+    match payload.len() {
+        length @ 0..=125 => frame.push(mask_bit | length as u8),
+        length => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+    }
+
+    if masked {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+    } else {
+        frame.extend_from_slice(payload);
+    }
+
+    stream.write_all(&frame).unwrap();
+}
+
+fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).unwrap();
+
+    assert_eq!(header[0] & 0x80, 0x80, "Server frames are never fragmented");
+    assert_eq!(header[1] & 0x80, 0, "Server frames are never masked");
+
+    let length = match header[1] & 0x7f {
+        126 => {
+            let mut length = [0; 2];
+            stream.read_exact(&mut length).unwrap();
+            u16::from_be_bytes(length) as usize
+        }
+        length => length as usize,
+    };
+
+    let mut payload = vec![0; length];
+    stream.read_exact(&mut payload).unwrap();
+
+    (header[0] & 0x0f, payload)
+}
+
+#[test]
+fn websocket_echoes_messages() {
+    let (address, shutdown_handle, server_thread) = start_server(router());
+
+    let mut stream = connect(address);
+
+    write_frame(&mut stream, true, TEXT, b"Hello", true);
+    assert_eq!(read_frame(&mut stream), (TEXT, b"Hello".to_vec()));
+
+    write_frame(&mut stream, true, BINARY, &[0, 1, 2, 255], true);
+    assert_eq!(read_frame(&mut stream), (BINARY, vec![0, 1, 2, 255]));
+
+    let long_text = "a".repeat(500);
+    write_frame(&mut stream, true, TEXT, long_text.as_bytes(), true);
+    assert_eq!(read_frame(&mut stream), (TEXT, long_text.into_bytes()));
+
+    write_frame(&mut stream, true, CLOSE, &1000u16.to_be_bytes(), true);
+    assert_eq!(
+        read_frame(&mut stream),
+        (CLOSE, 1000u16.to_be_bytes().to_vec())
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn websocket_reassembles_fragmented_messages() {
+    let (address, shutdown_handle, server_thread) = start_server(router());
+
+    let mut stream = connect(address);
+
+    // Pings can be sent between the fragments of a message
+    write_frame(&mut stream, false, TEXT, b"Hel", true);
+    write_frame(&mut stream, true, PING, b"ping", true);
+    write_frame(&mut stream, false, CONTINUATION, b"lo ", true);
+    write_frame(&mut stream, true, CONTINUATION, b"world", true);
+
+    assert_eq!(read_frame(&mut stream), (PONG, b"ping".to_vec()));
+    assert_eq!(read_frame(&mut stream), (TEXT, b"Hello world".to_vec()));
+
+    shutdown_handle.shutdown();
+    drop(stream);
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn websocket_closes_on_protocol_errors() {
+    let (address, shutdown_handle, server_thread) = start_server(router());
+
+    let mut stream = connect(address);
+    write_frame(&mut stream, true, TEXT, b"Hello", false);
+    assert_eq!(
+        read_frame(&mut stream),
+        (CLOSE, 1002u16.to_be_bytes().to_vec())
+    );
+
+    let mut stream = connect(address);
+    write_frame(&mut stream, true, TEXT, &[0xff, 0xfe], true);
+    assert_eq!(
+        read_frame(&mut stream),
+        (CLOSE, 1007u16.to_be_bytes().to_vec())
+    );
+
+    let mut stream = connect(address);
+    write_frame(&mut stream, false, BINARY, &[0; 1000], true);
+    write_frame(&mut stream, true, CONTINUATION, &[0; 1000], true);
+    assert_eq!(
+        read_frame(&mut stream),
+        (CLOSE, 1009u16.to_be_bytes().to_vec())
+    );
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}
+
+#[test]
+fn websocket_rejects_invalid_handshakes() {
+    let (address, shutdown_handle, server_thread) = start_server(router());
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    assert!(read_head(&mut stream).starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(format!("GET /echo HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 8\r\n\r\n", KEY).as_bytes())
+        .unwrap();
+
+    let head = read_head(&mut stream);
+    assert!(head.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    assert!(head.contains("Sec-WebSocket-Version: 13\r\n"));
+
+    shutdown_handle.shutdown();
+    assert!(server_thread.join().unwrap().is_ok());
+}